@@ -1,78 +1,89 @@
-use clap::Parser;
+use crate::config::Preset;
+use clap::{Parser, Subcommand};
+use fourcorners::report::ReportFormat;
+use fourcorners::BenchmarkConfig;
+use std::path::PathBuf;
+
+/// Actions other than running the benchmark
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// List the disks on this machine with their size and model, to find the path to pass to --device
+    #[command(visible_alias = "list-devices")]
+    List,
+}
 
+/// The `4c` command line: the benchmark settings plus what only the binary does
 #[derive(Parser, Debug, Clone)]
 #[command(name = "4c", version, about = "4Corners Disk Benchmark - CLI")]
 pub struct Args {
-    /// Device or file path(s) - can specify multiple times or comma-separated
-    /// On Windows: use \\.\PhysicalDrive4 or just 4
-    #[arg(short, long)]
-    pub device: Vec<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
 
-    /// Test duration in seconds
-    #[arg(long, default_value_t = 30)]
-    pub duration: u32,
+    /// List the disks on this machine with their size and model, then exit (same as `4c list`)
+    #[arg(long)]
+    pub list_devices: bool,
 
-    /// Read throughput threads
-    #[arg(long, default_value_t = 30)]
-    pub read_tp_threads: u32,
+    /// Load settings from a TOML profile; flags given on the command line override it
+    #[arg(long)]
+    pub config: Option<PathBuf>,
 
-    /// Write throughput threads
-    #[arg(long, default_value_t = 16)]
-    pub write_tp_threads: u32,
+    /// Start from a built-in profile: latency (4K QD1, 1 thread), iops (4K, 16 threads x QD32)
+    /// or throughput (1M sequential, 1 thread x QD8); --config and explicit flags override it
+    #[arg(long, value_enum)]
+    pub preset: Option<Preset>,
 
-    /// Read IOPS threads
-    #[arg(long, default_value_t = 120)]
-    pub read_iops_threads: u32,
+    /// Print the effective settings as a TOML profile and exit
+    #[arg(long)]
+    pub dump_config: bool,
 
-    /// Write IOPS threads
-    #[arg(long, default_value_t = 120)]
-    pub write_iops_threads: u32,
+    #[command(flatten)]
+    pub bench: BenchmarkConfig,
 
-    /// Read throughput queue depth per thread
-    #[arg(long, default_value_t = 1)]
-    pub read_tp_qd: u32,
+    /// Don't ask before write tests or --prep overwrite a raw device
+    #[arg(long, short = 'y')]
+    pub yes: bool,
 
-    /// Write throughput queue depth per thread
-    #[arg(long, default_value_t = 1)]
-    pub write_tp_qd: u32,
+    /// Write to a device even if it has mounted filesystems (Windows)
+    #[arg(long)]
+    pub force: bool,
 
-    /// Read IOPS queue depth per thread
-    #[arg(long, default_value_t = 1)]
-    pub read_iops_qd: u32,
+    /// Report file formats to write: text, json, csv, md, html (comma-separated)
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "text,json,csv")]
+    pub format: Vec<ReportFormat>,
 
-    /// Write IOPS queue depth per thread
-    #[arg(long, default_value_t = 1)]
-    pub write_iops_qd: u32,
+    /// Directory for the report files (created if missing)
+    #[arg(long, default_value = ".")]
+    pub output_dir: PathBuf,
 
-    /// Read throughput block size (KB)
-    #[arg(long, default_value_t = 128)]
-    pub read_tp_bs: u32,
+    /// Compare this run against a saved JSON report and print the change per metric
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
 
-    /// Write throughput block size (KB)
-    #[arg(long, default_value_t = 64)]
-    pub write_tp_bs: u32,
+    /// Append this run's report as one JSON line to a history file (created if missing)
+    #[arg(long)]
+    pub history: Option<PathBuf>,
 
-    /// Read IOPS block size (KB)
-    #[arg(long, default_value_t = 4)]
-    pub read_iops_bs: u32,
+    /// Send the results as InfluxDB line protocol: POSTed to an http:// write URL, or appended to a file
+    #[arg(long, value_name = "PATH_OR_URL")]
+    pub influx: Option<String>,
 
-    /// Write IOPS block size (KB)
-    #[arg(long, default_value_t = 4)]
-    pub write_iops_bs: u32,
+    /// Print nothing but the final report, as JSON on stdout (errors still go to stderr)
+    #[arg(short, long, visible_alias = "json-stdout")]
+    pub quiet: bool,
 
-    /// Prep device before testing (writes random data)
+    /// Resolve each device, print its size and confirm it opens for reading, then exit without writing anything
     #[arg(long)]
-    pub prep: bool,
+    pub dry_run: bool,
 
-    /// Create a file device before testing
+    /// Check that each device opens with direct I/O and reads one aligned block, then exit
     #[arg(long)]
-    pub create_file: bool,
+    pub check: bool,
 
-    /// File device size in GB (if creating)
-    #[arg(long, default_value_t = 10)]
-    pub file_size: u64,
+    /// Measure the alignment penalty: run a short aligned and misaligned read pass, then exit
+    #[arg(long)]
+    pub align_check: bool,
 
-    /// Tests to run: all, read-tp, write-tp, read-iops, write-iops (comma-separated)
-    #[arg(long, default_value = "all")]
-    pub tests: String,
+    /// Offset shift in bytes used for the misaligned pass of --align-check (default: the logical sector size)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub misalign_bytes: Option<u64>,
 }
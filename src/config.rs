@@ -0,0 +1,487 @@
+use fourcorners::engine::{
+    AccessPattern, BlockSizeMix, OffsetDistribution, QdSemantics, WritePattern, MAX_HISTOGRAM_BITS, MIN_HISTOGRAM_BITS,
+};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::io;
+use fourcorners::BenchmarkConfig;
+use std::path::Path;
+
+/// A saved benchmark profile. Keys mirror the long CLI flags with `_` in place
+/// of `-`; any key left out keeps the CLI default.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+    pub duration: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ops_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_iops: Option<u64>,
+    pub warmup: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_runtime: Option<u64>,
+    pub tests: Option<String>,
+
+    pub read_tp_threads: Option<u32>,
+    pub write_tp_threads: Option<u32>,
+    pub read_iops_threads: Option<u32>,
+    pub write_iops_threads: Option<u32>,
+    pub mixed_threads: Option<u32>,
+
+    pub read_tp_qd: Option<u32>,
+    pub write_tp_qd: Option<u32>,
+    pub read_iops_qd: Option<u32>,
+    pub write_iops_qd: Option<u32>,
+    pub mixed_qd: Option<u32>,
+    pub qd_semantics: Option<QdSemantics>,
+
+    pub read_tp_bs: Option<u32>,
+    pub write_tp_bs: Option<u32>,
+    pub read_iops_bs: Option<u32>,
+    pub write_iops_bs: Option<u32>,
+    pub mixed_bs: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bs_sweep: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qd_sweep: Vec<u32>,
+    pub repeat: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rw_mix: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bs_mix: Option<BlockSizeMix>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_percent: Option<u8>,
+    pub tp_pattern: Option<AccessPattern>,
+    pub write_pattern: Option<WritePattern>,
+    pub distribution: Option<OffsetDistribution>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skew: Option<f64>,
+    pub fsync_every: Option<u32>,
+    pub latency_sample_rate: Option<u32>,
+    pub histogram_bits: Option<u32>,
+    pub range_start: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range_len: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Named starting points for common measurements. Each is a built-in profile:
+/// a `--config` file and flags on the command line override its settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    /// Raw access latency: 4K random, one thread at QD1, every I/O timed
+    Latency,
+    /// Small-block random IOPS: 4K random, 16 threads at QD32
+    Iops,
+    /// Large-block sequential bandwidth: 1M sequential, one thread at QD8
+    Throughput,
+}
+
+impl Preset {
+    pub fn profile(self) -> Config {
+        match self {
+            Preset::Latency => Config {
+                tests: Some("read-iops,write-iops".to_string()),
+                read_iops_threads: Some(1),
+                write_iops_threads: Some(1),
+                read_iops_qd: Some(1),
+                write_iops_qd: Some(1),
+                read_iops_bs: Some(4),
+                write_iops_bs: Some(4),
+                latency_sample_rate: Some(1),
+                ..Config::default()
+            },
+            Preset::Iops => Config {
+                tests: Some("read-iops,write-iops".to_string()),
+                read_iops_threads: Some(16),
+                write_iops_threads: Some(16),
+                read_iops_qd: Some(32),
+                write_iops_qd: Some(32),
+                read_iops_bs: Some(4),
+                write_iops_bs: Some(4),
+                ..Config::default()
+            },
+            Preset::Throughput => Config {
+                tests: Some("read-tp,write-tp".to_string()),
+                read_tp_threads: Some(1),
+                write_tp_threads: Some(1),
+                read_tp_qd: Some(8),
+                write_tp_qd: Some(8),
+                read_tp_bs: Some(1024),
+                write_tp_bs: Some(1024),
+                tp_pattern: Some(AccessPattern::Sequential),
+                ..Config::default()
+            },
+        }
+    }
+}
+
+/// Take the file value unless the flag was given explicitly on the command line
+fn merge<T: Clone>(matches: &ArgMatches, id: &str, file: Option<&T>, arg: &mut T) {
+    if let Some(value) = file {
+        if matches.value_source(id) != Some(ValueSource::CommandLine) {
+            *arg = value.clone();
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let config: Self = toml::from_str(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        if config.rw_mix.is_some_and(|pct| pct > 100) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "rw_mix must be 0-100",
+            ));
+        }
+        if config
+            .histogram_bits
+            .is_some_and(|bits| !(MIN_HISTOGRAM_BITS..=MAX_HISTOGRAM_BITS).contains(&bits))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("histogram_bits must be {}-{}", MIN_HISTOGRAM_BITS, MAX_HISTOGRAM_BITS),
+            ));
+        }
+        if config.latency_sample_rate == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "latency_sample_rate must be at least 1",
+            ));
+        }
+        if config.fill_percent.is_some_and(|p| !(1..=100).contains(&p)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "fill_percent must be 1-100",
+            ));
+        }
+        if config.repeat == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "repeat must be at least 1",
+            ));
+        }
+        if config.target_iops == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "target_iops must be at least 1",
+            ));
+        }
+        if config.max_runtime == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "max_runtime must be at least 1",
+            ));
+        }
+        if config.skew.is_some_and(|v| !(v.is_finite() && v > 0.0)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "skew must be a positive number",
+            ));
+        }
+        if config.device_size == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "device_size must be greater than 0",
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Capture the effective settings so they can be written back out
+    pub fn from_args(args: &BenchmarkConfig) -> Self {
+        Self {
+            devices: args.device.clone(),
+            duration: Some(args.duration),
+            size_limit: args.size_limit,
+            ops_limit: args.ops_limit,
+            target_iops: args.target_iops,
+            warmup: Some(args.warmup),
+            max_runtime: args.max_runtime,
+            tests: Some(args.tests.clone()),
+            read_tp_threads: Some(args.read_tp_threads),
+            write_tp_threads: Some(args.write_tp_threads),
+            read_iops_threads: Some(args.read_iops_threads),
+            write_iops_threads: Some(args.write_iops_threads),
+            mixed_threads: Some(args.mixed_threads),
+            read_tp_qd: Some(args.read_tp_qd),
+            write_tp_qd: Some(args.write_tp_qd),
+            read_iops_qd: Some(args.read_iops_qd),
+            write_iops_qd: Some(args.write_iops_qd),
+            mixed_qd: Some(args.mixed_qd),
+            qd_semantics: Some(args.qd_semantics),
+            read_tp_bs: Some(args.read_tp_bs),
+            write_tp_bs: Some(args.write_tp_bs),
+            read_iops_bs: Some(args.read_iops_bs),
+            write_iops_bs: Some(args.write_iops_bs),
+            mixed_bs: Some(args.mixed_bs),
+            bs_sweep: args.bs_sweep.clone(),
+            qd_sweep: args.qd_sweep.clone(),
+            repeat: Some(args.repeat),
+            rw_mix: args.rw_mix,
+            bs_mix: args.bs_mix.clone(),
+            fill_percent: args.fill_percent,
+            tp_pattern: Some(args.tp_pattern),
+            write_pattern: Some(args.write_pattern),
+            distribution: Some(args.distribution),
+            skew: args.skew,
+            fsync_every: Some(args.fsync_every),
+            latency_sample_rate: Some(args.latency_sample_rate),
+            histogram_bits: Some(args.histogram_bits),
+            range_start: Some(args.range_start),
+            range_len: args.range_len,
+            device_size: args.device_size,
+            seed: args.seed,
+        }
+    }
+
+    /// Fill `args` from the profile; flags given on the command line win
+    pub fn apply(&self, args: &mut BenchmarkConfig, matches: &ArgMatches) {
+        if !self.devices.is_empty() {
+            merge(matches, "device", Some(&self.devices), &mut args.device);
+        }
+        merge(
+            matches,
+            "duration",
+            self.duration.as_ref(),
+            &mut args.duration,
+        );
+        merge(
+            matches,
+            "size_limit",
+            self.size_limit.map(Some).as_ref(),
+            &mut args.size_limit,
+        );
+        merge(
+            matches,
+            "ops_limit",
+            self.ops_limit.map(Some).as_ref(),
+            &mut args.ops_limit,
+        );
+        merge(
+            matches,
+            "target_iops",
+            self.target_iops.map(Some).as_ref(),
+            &mut args.target_iops,
+        );
+        merge(matches, "warmup", self.warmup.as_ref(), &mut args.warmup);
+        merge(
+            matches,
+            "max_runtime",
+            self.max_runtime.map(Some).as_ref(),
+            &mut args.max_runtime,
+        );
+        merge(matches, "tests", self.tests.as_ref(), &mut args.tests);
+
+        merge(
+            matches,
+            "read_tp_threads",
+            self.read_tp_threads.as_ref(),
+            &mut args.read_tp_threads,
+        );
+        merge(
+            matches,
+            "write_tp_threads",
+            self.write_tp_threads.as_ref(),
+            &mut args.write_tp_threads,
+        );
+        merge(
+            matches,
+            "read_iops_threads",
+            self.read_iops_threads.as_ref(),
+            &mut args.read_iops_threads,
+        );
+        merge(
+            matches,
+            "write_iops_threads",
+            self.write_iops_threads.as_ref(),
+            &mut args.write_iops_threads,
+        );
+        merge(
+            matches,
+            "mixed_threads",
+            self.mixed_threads.as_ref(),
+            &mut args.mixed_threads,
+        );
+
+        merge(
+            matches,
+            "read_tp_qd",
+            self.read_tp_qd.as_ref(),
+            &mut args.read_tp_qd,
+        );
+        merge(
+            matches,
+            "write_tp_qd",
+            self.write_tp_qd.as_ref(),
+            &mut args.write_tp_qd,
+        );
+        merge(
+            matches,
+            "read_iops_qd",
+            self.read_iops_qd.as_ref(),
+            &mut args.read_iops_qd,
+        );
+        merge(
+            matches,
+            "write_iops_qd",
+            self.write_iops_qd.as_ref(),
+            &mut args.write_iops_qd,
+        );
+        merge(
+            matches,
+            "mixed_qd",
+            self.mixed_qd.as_ref(),
+            &mut args.mixed_qd,
+        );
+        merge(
+            matches,
+            "qd_semantics",
+            self.qd_semantics.as_ref(),
+            &mut args.qd_semantics,
+        );
+
+        merge(
+            matches,
+            "read_tp_bs",
+            self.read_tp_bs.as_ref(),
+            &mut args.read_tp_bs,
+        );
+        merge(
+            matches,
+            "write_tp_bs",
+            self.write_tp_bs.as_ref(),
+            &mut args.write_tp_bs,
+        );
+        merge(
+            matches,
+            "read_iops_bs",
+            self.read_iops_bs.as_ref(),
+            &mut args.read_iops_bs,
+        );
+        merge(
+            matches,
+            "write_iops_bs",
+            self.write_iops_bs.as_ref(),
+            &mut args.write_iops_bs,
+        );
+        merge(
+            matches,
+            "mixed_bs",
+            self.mixed_bs.as_ref(),
+            &mut args.mixed_bs,
+        );
+        if !self.bs_sweep.is_empty() {
+            merge(matches, "bs_sweep", Some(&self.bs_sweep), &mut args.bs_sweep);
+        }
+        if !self.qd_sweep.is_empty() {
+            merge(matches, "qd_sweep", Some(&self.qd_sweep), &mut args.qd_sweep);
+        }
+        merge(
+            matches,
+            "repeat",
+            self.repeat.as_ref(),
+            &mut args.repeat,
+        );
+
+        merge(
+            matches,
+            "rw_mix",
+            self.rw_mix.map(Some).as_ref(),
+            &mut args.rw_mix,
+        );
+        merge(
+            matches,
+            "bs_mix",
+            self.bs_mix.clone().map(Some).as_ref(),
+            &mut args.bs_mix,
+        );
+        merge(
+            matches,
+            "fill_percent",
+            self.fill_percent.map(Some).as_ref(),
+            &mut args.fill_percent,
+        );
+        merge(
+            matches,
+            "tp_pattern",
+            self.tp_pattern.as_ref(),
+            &mut args.tp_pattern,
+        );
+        merge(
+            matches,
+            "write_pattern",
+            self.write_pattern.as_ref(),
+            &mut args.write_pattern,
+        );
+        merge(
+            matches,
+            "distribution",
+            self.distribution.as_ref(),
+            &mut args.distribution,
+        );
+        merge(
+            matches,
+            "skew",
+            self.skew.map(Some).as_ref(),
+            &mut args.skew,
+        );
+        merge(
+            matches,
+            "fsync_every",
+            self.fsync_every.as_ref(),
+            &mut args.fsync_every,
+        );
+        merge(
+            matches,
+            "latency_sample_rate",
+            self.latency_sample_rate.as_ref(),
+            &mut args.latency_sample_rate,
+        );
+        merge(
+            matches,
+            "histogram_bits",
+            self.histogram_bits.as_ref(),
+            &mut args.histogram_bits,
+        );
+        merge(
+            matches,
+            "range_start",
+            self.range_start.as_ref(),
+            &mut args.range_start,
+        );
+        merge(
+            matches,
+            "range_len",
+            self.range_len.map(Some).as_ref(),
+            &mut args.range_len,
+        );
+        merge(
+            matches,
+            "device_size",
+            self.device_size.map(Some).as_ref(),
+            &mut args.device_size,
+        );
+        merge(
+            matches,
+            "seed",
+            self.seed.map(Some).as_ref(),
+            &mut args.seed,
+        );
+    }
+
+    pub fn to_toml(&self) -> io::Result<String> {
+        toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+}
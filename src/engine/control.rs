@@ -0,0 +1,131 @@
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::{sum_counter, Metrics};
+
+/// Build the JSON snapshot of the current aggregate counters
+fn snapshot(label: &str, metrics: &[Arc<Metrics>], start: Instant) -> String {
+    let elapsed = start.elapsed().as_secs_f64();
+    let ops = sum_counter(metrics, |m| &m.total_ops);
+    let bytes = sum_counter(metrics, |m| &m.total_bytes);
+    let lat_samples = sum_counter(metrics, |m| &m.latency_samples) as f64;
+    let lat_sum = sum_counter(metrics, |m| &m.latency_sum_ns) as f64;
+    let avg_lat_us = if lat_samples > 0.0 {
+        lat_sum / lat_samples / 1_000.0
+    } else {
+        0.0
+    };
+
+    let mut line = serde_json::json!({
+        "test": label,
+        "elapsed_s": elapsed,
+        "ops": ops,
+        "bytes": bytes,
+        "iops": ops as f64 / elapsed,
+        "mbps": bytes as f64 / elapsed / (1024.0 * 1024.0),
+        "avg_lat_us": avg_lat_us,
+    })
+    .to_string();
+    line.push('\n');
+    line
+}
+
+/// Serve counter snapshots on a control socket until `stop` is set.
+/// Every client that connects receives one JSON line and is disconnected.
+/// On Linux `path` is a Unix-domain socket; on Windows a named pipe (`\\.\pipe\name`).
+pub fn serve(
+    path: &Path,
+    label: String,
+    metrics: Vec<Arc<Metrics>>,
+    start: Instant,
+    stop: Arc<AtomicBool>,
+) -> io::Result<JoinHandle<()>> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::UnixListener;
+
+        // A stale socket from a previous run would make bind fail; anything
+        // else at the path is left alone
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "the path exists and is not a socket; refusing to replace it",
+                ))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        let path = path.to_path_buf();
+
+        Ok(std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((mut stream, _)) => {
+                        let _ = stream.set_nonblocking(false);
+                        let _ = stream.write_all(snapshot(&label, &metrics, start).as_bytes());
+                    }
+                    Err(_) => std::thread::sleep(Duration::from_millis(50)),
+                }
+            }
+            let _ = std::fs::remove_file(&path);
+        }))
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use std::ptr;
+        use windows_sys::Win32::Foundation::*;
+        use windows_sys::Win32::Storage::FileSystem::{FlushFileBuffers, WriteFile, PIPE_ACCESS_OUTBOUND};
+        use windows_sys::Win32::System::Pipes::*;
+
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                wide.as_ptr(),
+                PIPE_ACCESS_OUTBOUND,
+                PIPE_TYPE_BYTE | PIPE_NOWAIT,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                ptr::null(),
+            )
+        };
+        if pipe == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        // HANDLE is a raw pointer; pass it to the thread as an integer
+        let pipe = pipe as usize;
+
+        Ok(std::thread::spawn(move || {
+            let pipe = pipe as HANDLE;
+            while !stop.load(Ordering::Relaxed) {
+                // In PIPE_NOWAIT mode ConnectNamedPipe polls instead of blocking
+                unsafe { ConnectNamedPipe(pipe, ptr::null_mut()) };
+                if unsafe { GetLastError() } == ERROR_PIPE_CONNECTED {
+                    let line = snapshot(&label, &metrics, start);
+                    let mut written: u32 = 0;
+                    unsafe {
+                        WriteFile(pipe, line.as_ptr(), line.len() as u32, &mut written, ptr::null_mut());
+                        FlushFileBuffers(pipe);
+                        DisconnectNamedPipe(pipe);
+                    }
+                } else {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            }
+            unsafe { CloseHandle(pipe) };
+        }))
+    }
+}
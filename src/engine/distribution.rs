@@ -0,0 +1,110 @@
+use rand::Rng;
+
+use super::OffsetDistribution;
+
+/// Picks the block index, in `[0, blocks)`, of each random offset. The skewed
+/// distributions put their hot blocks at the start of the test range, so every
+/// worker on a device hammers the same hot region.
+pub enum BlockPicker {
+    Uniform,
+    Zipf(Zipf),
+    /// Rate of the exponential decay across the whole range
+    Exponential(f64),
+}
+
+impl BlockPicker {
+    pub fn new(distribution: OffsetDistribution, skew: f64, blocks: u64) -> Self {
+        match distribution {
+            OffsetDistribution::Uniform => BlockPicker::Uniform,
+            OffsetDistribution::Zipf => BlockPicker::Zipf(Zipf::new(blocks, skew)),
+            OffsetDistribution::Exponential => BlockPicker::Exponential(skew),
+        }
+    }
+
+    pub fn pick(&self, blocks: u64, rng: &mut impl Rng) -> u64 {
+        match self {
+            BlockPicker::Uniform => rng.gen_range(0..blocks),
+            BlockPicker::Zipf(zipf) => zipf.sample(rng) - 1,
+            BlockPicker::Exponential(rate) => {
+                // Inverse CDF of the exponential truncated to [0, 1)
+                let u: f64 = rng.gen();
+                let mass = -(-rate).exp_m1(); // 1 - e^-rate
+                let x = -(-u * mass).ln_1p() / rate;
+                ((x * blocks as f64) as u64).min(blocks - 1)
+            }
+        }
+    }
+}
+
+/// Zipf distribution over ranks `1..=n`: rank k is drawn with probability
+/// proportional to 1/k^s. Sampled by rejection-inversion (Hörmann and
+/// Derflinger), which needs no table, so a draw costs the same whatever the
+/// size of the device.
+pub struct Zipf {
+    n: f64,
+    s: f64,
+    h_integral_x1: f64,
+    h_integral_n: f64,
+    threshold: f64,
+}
+
+impl Zipf {
+    pub fn new(n: u64, s: f64) -> Self {
+        let mut zipf = Self {
+            n: n.max(1) as f64,
+            s,
+            h_integral_x1: 0.0,
+            h_integral_n: 0.0,
+            threshold: 0.0,
+        };
+        zipf.h_integral_x1 = zipf.h_integral(1.5) - 1.0;
+        zipf.h_integral_n = zipf.h_integral(zipf.n + 0.5);
+        zipf.threshold = 2.0 - zipf.h_integral_inverse(zipf.h_integral(2.5) - zipf.h(2.0));
+        zipf
+    }
+
+    /// A rank in `1..=n`; 1 is the most frequent
+    pub fn sample(&self, rng: &mut impl Rng) -> u64 {
+        loop {
+            let u = self.h_integral_n + rng.gen::<f64>() * (self.h_integral_x1 - self.h_integral_n);
+            let x = self.h_integral_inverse(u);
+            let k = (x + 0.5).clamp(1.0, self.n).floor();
+            if k - x <= self.threshold || u >= self.h_integral(k + 0.5) - self.h(k) {
+                return k as u64;
+            }
+        }
+    }
+
+    fn h(&self, x: f64) -> f64 {
+        (-self.s * x.ln()).exp()
+    }
+
+    /// Integral of `h`, shifted so it is well behaved at s = 1
+    fn h_integral(&self, x: f64) -> f64 {
+        let log_x = x.ln();
+        expm1_over_x((1.0 - self.s) * log_x) * log_x
+    }
+
+    fn h_integral_inverse(&self, x: f64) -> f64 {
+        let t = (x * (1.0 - self.s)).max(-1.0);
+        (ln1p_over_x(t) * x).exp()
+    }
+}
+
+/// ln(1 + x) / x, accurate near 0
+fn ln1p_over_x(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.ln_1p() / x
+    } else {
+        1.0 - x * (0.5 - x * (1.0 / 3.0 - 0.25 * x))
+    }
+}
+
+/// (e^x - 1) / x, accurate near 0
+fn expm1_over_x(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.exp_m1() / x
+    } else {
+        1.0 + x * 0.5 * (1.0 + x / 3.0 * (1.0 + 0.25 * x))
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Each power-of-two range is split into 2^sub_bits / 2 linear sub-buckets,
+/// which bounds the relative error of a reported value to 1/2^(sub_bits-1).
+/// `--histogram-bits` picks sub_bits; memory doubles with every extra bit.
+pub const DEFAULT_SUB_BITS: u32 = 8;
+pub const MIN_SUB_BITS: u32 = 4;
+pub const MAX_SUB_BITS: u32 = 14;
+
+/// Largest trackable latency: 2^36 ns (about 68.7 s); slower I/Os land in the top bucket
+const MAX_BITS: u32 = 36;
+const MAX_NS: u64 = (1 << MAX_BITS) - 1;
+
+/// Bucket geometry shared by every histogram of one test
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    sub_bits: u32,
+}
+
+impl Layout {
+    fn new(sub_bits: u32) -> Self {
+        Self {
+            sub_bits: sub_bits.clamp(MIN_SUB_BITS, MAX_SUB_BITS),
+        }
+    }
+
+    fn sub_count(self) -> u64 {
+        1 << self.sub_bits
+    }
+
+    fn sub_half(self) -> usize {
+        1 << (self.sub_bits - 1)
+    }
+
+    fn buckets(self) -> usize {
+        (MAX_BITS - self.sub_bits + 1) as usize * self.sub_half() + self.sub_half()
+    }
+
+    fn bucket_index(self, ns: u64) -> usize {
+        let ns = ns.min(MAX_NS);
+        if ns < self.sub_count() {
+            return ns as usize;
+        }
+        let shift = (63 - ns.leading_zeros()) - (self.sub_bits - 1);
+        shift as usize * self.sub_half() + (ns >> shift) as usize
+    }
+
+    /// Midpoint of the value range covered by bucket `idx`
+    fn bucket_value(self, idx: usize) -> u64 {
+        if idx < self.sub_count() as usize {
+            return idx as u64;
+        }
+        let shift = (idx / self.sub_half() - 1) as u32;
+        let sub = (idx - shift as usize * self.sub_half()) as u64;
+        (sub << shift) + (1 << shift) / 2
+    }
+}
+
+/// Log-linear latency histogram (HdrHistogram layout) over nanoseconds.
+/// Keeps every sample regardless of run length; workers fill a
+/// `LocalHistogram` and fold it in once they stop.
+pub struct LatencyHistogram {
+    layout: Layout,
+    counts: Box<[AtomicU64]>,
+}
+
+/// Single-thread histogram with the same buckets, recorded without atomics
+pub struct LocalHistogram {
+    layout: Layout,
+    counts: Box<[u64]>,
+}
+
+impl LocalHistogram {
+    pub fn new(sub_bits: u32) -> Self {
+        let layout = Layout::new(sub_bits);
+        Self {
+            layout,
+            counts: vec![0; layout.buckets()].into_boxed_slice(),
+        }
+    }
+
+    pub fn record(&mut self, ns: u64) {
+        self.counts[self.layout.bucket_index(ns)] += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+    }
+
+    /// Latency in nanoseconds at percentile `p` (0-100), or None if nothing was recorded
+    pub fn value_at(&self, p: f64) -> Option<u64> {
+        value_at(self.layout, &self.counts, p)
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new(sub_bits: u32) -> Self {
+        let layout = Layout::new(sub_bits);
+        Self {
+            layout,
+            counts: (0..layout.buckets()).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Add every count from `other` into this histogram
+    pub fn absorb(&self, other: &Self) {
+        debug_assert!(self.layout == other.layout);
+        for (mine, theirs) in self.counts.iter().zip(other.counts.iter()) {
+            let n = theirs.load(Ordering::Relaxed);
+            if n > 0 {
+                mine.fetch_add(n, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Add a worker's local counts into this histogram
+    pub fn absorb_local(&self, local: &LocalHistogram) {
+        debug_assert!(self.layout == local.layout);
+        for (mine, &n) in self.counts.iter().zip(local.counts.iter()) {
+            if n > 0 {
+                mine.fetch_add(n, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Latency in nanoseconds at percentile `p` (0-100), or None if nothing was recorded
+    pub fn value_at(&self, p: f64) -> Option<u64> {
+        let counts: Vec<u64> = self.counts.iter().map(|c| c.load(Ordering::Relaxed)).collect();
+        value_at(self.layout, &counts, p)
+    }
+}
+
+impl LatencyHistogram {
+    /// Counts regrouped into power-of-two ranges of nanoseconds, as
+    /// `(lower_ns, count)` from the fastest to the slowest non-empty range
+    pub fn log2_counts(&self) -> Vec<(u64, u64)> {
+        let mut ranges = vec![0u64; MAX_BITS as usize + 1];
+        for (idx, count) in self.counts.iter().enumerate() {
+            let n = count.load(Ordering::Relaxed);
+            if n > 0 {
+                let ns = self.layout.bucket_value(idx).max(1);
+                ranges[(63 - ns.leading_zeros()) as usize] += n;
+            }
+        }
+        let Some(first) = ranges.iter().position(|&n| n > 0) else {
+            return Vec::new();
+        };
+        let last = ranges.iter().rposition(|&n| n > 0).unwrap();
+        (first..=last).map(|k| (1u64 << k, ranges[k])).collect()
+    }
+}
+
+fn value_at(layout: Layout, counts: &[u64], p: f64) -> Option<u64> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let rank = ((p / 100.0) * total as f64).ceil().max(1.0) as u64;
+    let mut seen = 0;
+    for (idx, &count) in counts.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return Some(layout.bucket_value(idx));
+        }
+    }
+    Some(MAX_NS)
+}
@@ -1,91 +1,1405 @@
+mod control;
+mod distribution;
+mod histogram;
+mod stream;
+mod timeseries;
+mod trace;
+mod verify;
 mod worker;
 
 #[cfg(windows)]
 mod platform_windows;
 #[cfg(target_os = "linux")]
 mod platform_linux;
+#[cfg(target_os = "macos")]
+mod platform_macos;
 
-use crate::report::TestResult;
+use crate::error::BenchError;
+use crate::report::{
+    DeviceDetails, DeviceResult, DirectionSplit, FsyncSummary, LatencyBucket, NumberFormat,
+    TestResult, ThreadSpread, TimelinePoint, VerifySummary,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::io;
-use std::io::Write;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+pub use histogram::{
+    DEFAULT_SUB_BITS as DEFAULT_HISTOGRAM_BITS, MAX_SUB_BITS as MAX_HISTOGRAM_BITS,
+    MIN_SUB_BITS as MIN_HISTOGRAM_BITS,
+};
+pub use stream::{ResultStream, StreamSender};
+
 /// Shared metrics collected by all worker threads
 pub struct Metrics {
     pub total_ops: AtomicU64,
     pub total_bytes: AtomicU64,
+    /// Subset of total_ops / total_bytes that were writes
+    pub write_ops: AtomicU64,
+    pub write_bytes: AtomicU64,
     pub latency_sum_ns: AtomicU64,
     pub latency_samples: AtomicU64,
-    /// Sorted latency samples for percentile calculation (collected post-test)
-    latency_reservoir: std::sync::Mutex<Vec<u64>>,
+    /// Sums of in-flight reads/writes sampled at each reissue (mixed workloads)
+    pub inflight_read_sum: AtomicU64,
+    pub inflight_write_sum: AtomicU64,
+    pub inflight_samples: AtomicU64,
+    /// Device flushes issued by `--fsync-every`, timed apart from the I/O latencies
+    pub flush_count: AtomicU64,
+    pub flush_sum_ns: AtomicU64,
+    pub flush_max_ns: AtomicU64,
+    /// 4 KiB chunks checked by `--verify` reads, and how many were unstamped / corrupt
+    pub verify_checked: AtomicU64,
+    pub verify_unwritten: AtomicU64,
+    pub verify_corrupt: AtomicU64,
+    /// Reissues given up because the io_uring submission queue stayed full;
+    /// each one leaves a slot idle for the rest of the test
+    pub dropped_submissions: AtomicU64,
+    /// I/Os that failed or moved fewer bytes than requested
+    pub io_errors: AtomicU64,
+    /// OS error code of the first failed I/O (0 until one fails)
+    first_io_error: AtomicI32,
+    /// Set while a warmup window runs; workers count nothing until it clears
+    pub warming_up: AtomicBool,
+    latency_min_ns: AtomicU64,
+    latency_max_ns: AtomicU64,
+    /// Sum of squared sampled latencies in ns² (f64 bits; u64 would overflow)
+    latency_sq_sum_ns: AtomicU64,
+    /// Every sampled latency, bucketed for percentile calculation
+    latency_histogram: histogram::LatencyHistogram,
+    /// Mixed workloads: the same samples split by direction
+    pub read_latency: DirectionLatency,
+    pub write_latency: DirectionLatency,
+    /// (worker id, p99 ns) of each worker that sampled anything (`--per-thread-stats`)
+    thread_p99_ns: Mutex<Vec<(u32, u64)>>,
+    /// Where workers send every sampled latency (`--latency-trace`)
+    trace: Option<trace::TraceSender>,
 }
 
 impl Metrics {
-    pub fn new() -> Self {
+    /// `histogram_bits` sets the latency histogram's precision (`--histogram-bits`)
+    pub fn new(histogram_bits: u32) -> Self {
         Self {
             total_ops: AtomicU64::new(0),
             total_bytes: AtomicU64::new(0),
+            write_ops: AtomicU64::new(0),
+            write_bytes: AtomicU64::new(0),
             latency_sum_ns: AtomicU64::new(0),
             latency_samples: AtomicU64::new(0),
-            latency_reservoir: std::sync::Mutex::new(Vec::with_capacity(100_000)),
+            inflight_read_sum: AtomicU64::new(0),
+            inflight_write_sum: AtomicU64::new(0),
+            inflight_samples: AtomicU64::new(0),
+            flush_count: AtomicU64::new(0),
+            flush_sum_ns: AtomicU64::new(0),
+            flush_max_ns: AtomicU64::new(0),
+            verify_checked: AtomicU64::new(0),
+            verify_unwritten: AtomicU64::new(0),
+            verify_corrupt: AtomicU64::new(0),
+            dropped_submissions: AtomicU64::new(0),
+            io_errors: AtomicU64::new(0),
+            first_io_error: AtomicI32::new(0),
+            warming_up: AtomicBool::new(false),
+            latency_min_ns: AtomicU64::new(u64::MAX),
+            latency_max_ns: AtomicU64::new(0),
+            latency_sq_sum_ns: AtomicU64::new(0f64.to_bits()),
+            latency_histogram: histogram::LatencyHistogram::new(histogram_bits),
+            read_latency: DirectionLatency::new(histogram_bits),
+            write_latency: DirectionLatency::new(histogram_bits),
+            thread_p99_ns: Mutex::new(Vec::new()),
+            trace: None,
+        }
+    }
+
+    /// Have the workers counting into these metrics trace each sampled latency
+    pub fn with_trace(mut self, trace: Option<trace::TraceSender>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Sampled latencies counted per power-of-two range, for charting
+    pub fn latency_distribution(&self) -> Vec<LatencyBucket> {
+        self.latency_histogram
+            .log2_counts()
+            .into_iter()
+            .map(|(lower_ns, count)| LatencyBucket {
+                from_us: lower_ns as f64 / 1_000.0,
+                to_us: (lower_ns * 2) as f64 / 1_000.0,
+                count,
+            })
+            .collect()
+    }
+
+    pub fn percentile(&self, p: f64) -> f64 {
+        match self.latency_histogram.value_at(p) {
+            // Bucket midpoints can stray past the observed extremes; pin them back
+            Some(ns) => {
+                let min = self.latency_min_ns.load(Ordering::Relaxed);
+                let max = self.latency_max_ns.load(Ordering::Relaxed);
+                ns.clamp(min, max) as f64 / 1_000.0 // ns -> us
+            }
+            None => 0.0,
         }
     }
 
-    pub fn record_latency(&self, ns: u64) {
-        self.latency_sum_ns.fetch_add(ns, Ordering::Relaxed);
-        self.latency_samples.fetch_add(1, Ordering::Relaxed);
-        // Reservoir sampling: keep up to 100k samples
-        let mut reservoir = self.latency_reservoir.lock().unwrap();
-        if reservoir.len() < 100_000 {
-            reservoir.push(ns);
+    /// Fold another set of counters into this one (used to combine per-device metrics)
+    pub fn absorb(&self, other: &Metrics) {
+        let counters = [
+            (&self.total_ops, &other.total_ops),
+            (&self.total_bytes, &other.total_bytes),
+            (&self.write_ops, &other.write_ops),
+            (&self.write_bytes, &other.write_bytes),
+            (&self.latency_sum_ns, &other.latency_sum_ns),
+            (&self.latency_samples, &other.latency_samples),
+            (&self.inflight_read_sum, &other.inflight_read_sum),
+            (&self.inflight_write_sum, &other.inflight_write_sum),
+            (&self.inflight_samples, &other.inflight_samples),
+            (&self.flush_count, &other.flush_count),
+            (&self.flush_sum_ns, &other.flush_sum_ns),
+            (&self.verify_checked, &other.verify_checked),
+            (&self.verify_unwritten, &other.verify_unwritten),
+            (&self.verify_corrupt, &other.verify_corrupt),
+            (&self.dropped_submissions, &other.dropped_submissions),
+            (&self.io_errors, &other.io_errors),
+        ];
+        for (mine, theirs) in counters {
+            mine.fetch_add(theirs.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.latency_min_ns
+            .fetch_min(other.latency_min_ns.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.latency_max_ns
+            .fetch_max(other.latency_max_ns.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.latency_histogram.absorb(&other.latency_histogram);
+        self.read_latency.absorb(&other.read_latency);
+        self.write_latency.absorb(&other.write_latency);
+        add_f64(&self.latency_sq_sum_ns, f64::from_bits(other.latency_sq_sum_ns.load(Ordering::Relaxed)));
+        self.flush_max_ns
+            .fetch_max(other.flush_max_ns.load(Ordering::Relaxed), Ordering::Relaxed);
+        let _ = self.first_io_error.compare_exchange(
+            0,
+            other.first_io_error.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+        let theirs = other.thread_p99_ns.lock().unwrap().clone();
+        self.thread_p99_ns.lock().unwrap().extend(theirs);
+    }
+
+    /// Spread of per-worker p99 latencies, or None unless workers reported them
+    pub fn thread_spread(&self) -> Option<ThreadSpread> {
+        let mut p99s = self.thread_p99_ns.lock().unwrap().clone();
+        if p99s.is_empty() {
+            return None;
+        }
+        p99s.sort_by_key(|&(_, ns)| ns);
+        let (slowest_thread, max_ns) = p99s[p99s.len() - 1];
+        Some(ThreadSpread {
+            threads: p99s.len(),
+            p99_min_us: p99s[0].1 as f64 / 1_000.0,
+            p99_median_us: p99s[p99s.len() / 2].1 as f64 / 1_000.0,
+            p99_max_us: max_ns as f64 / 1_000.0,
+            slowest_thread,
+        })
+    }
+
+    /// Count an I/O that failed with OS error `code`, or (None) came back short
+    pub fn record_io_error(&self, code: Option<i32>) {
+        self.io_errors.fetch_add(1, Ordering::Relaxed);
+        if let Some(code) = code {
+            let _ = self
+                .first_io_error
+                .compare_exchange(0, code, Ordering::Relaxed, Ordering::Relaxed);
+        }
+    }
+
+    /// What the first failed I/O reported, or None if every error was a short transfer
+    pub fn first_io_error(&self) -> Option<io::Error> {
+        match self.first_io_error.load(Ordering::Relaxed) {
+            0 => None,
+            code => Some(io::Error::from_raw_os_error(code)),
+        }
+    }
+
+    /// Average sampled latency in microseconds
+    pub fn avg_latency_us(&self) -> f64 {
+        let samples = self.latency_samples.load(Ordering::Relaxed) as f64;
+        if samples > 0.0 {
+            self.latency_sum_ns.load(Ordering::Relaxed) as f64 / samples / 1_000.0
         } else {
-            // Random replacement
-            let idx = rand::random::<usize>() % reservoir.len();
-            reservoir[idx] = ns;
+            0.0
         }
     }
 
-    pub fn percentile(&self, p: f64) -> f64 {
-        let mut reservoir = self.latency_reservoir.lock().unwrap();
-        if reservoir.is_empty() {
+    /// Standard deviation of the sampled latencies in microseconds
+    pub fn latency_stddev_us(&self) -> f64 {
+        let samples = self.latency_samples.load(Ordering::Relaxed) as f64;
+        if samples < 2.0 {
             return 0.0;
         }
-        reservoir.sort_unstable();
-        let idx = ((p / 100.0) * (reservoir.len() as f64 - 1.0)) as usize;
-        reservoir[idx.min(reservoir.len() - 1)] as f64 / 1_000.0 // ns -> us
+        let mean_ns = self.latency_sum_ns.load(Ordering::Relaxed) as f64 / samples;
+        let sq_sum = f64::from_bits(self.latency_sq_sum_ns.load(Ordering::Relaxed));
+        // E[x²] - E[x]², which rounding can push slightly below zero
+        (sq_sum / samples - mean_ns * mean_ns).max(0.0).sqrt() / 1_000.0
+    }
+
+    /// Smallest and largest sampled latency in microseconds
+    pub fn latency_range_us(&self) -> (f64, f64) {
+        let min = self.latency_min_ns.load(Ordering::Relaxed);
+        let max = self.latency_max_ns.load(Ordering::Relaxed);
+        if min > max {
+            return (0.0, 0.0);
+        }
+        (min as f64 / 1_000.0, max as f64 / 1_000.0)
+    }
+}
+
+/// Sampled latencies of one direction of a mixed workload. Reads and writes
+/// usually have very different service times, so one blended average hides both.
+pub struct DirectionLatency {
+    histogram: histogram::LatencyHistogram,
+    sum_ns: AtomicU64,
+    samples: AtomicU64,
+    min_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+impl DirectionLatency {
+    fn new(histogram_bits: u32) -> Self {
+        Self {
+            histogram: histogram::LatencyHistogram::new(histogram_bits),
+            sum_ns: AtomicU64::new(0),
+            samples: AtomicU64::new(0),
+            min_ns: AtomicU64::new(u64::MAX),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// Fold in a worker's samples for this direction
+    fn absorb_local(&self, local: &LatencySampler) {
+        self.sum_ns.fetch_add(local.sum_ns, Ordering::Relaxed);
+        self.samples.fetch_add(local.samples, Ordering::Relaxed);
+        self.min_ns.fetch_min(local.min_ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(local.max_ns, Ordering::Relaxed);
+        self.histogram.absorb_local(&local.histogram);
+    }
+
+    fn absorb(&self, other: &DirectionLatency) {
+        self.sum_ns.fetch_add(other.sum_ns.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.samples.fetch_add(other.samples.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.min_ns.fetch_min(other.min_ns.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max_ns.fetch_max(other.max_ns.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.histogram.absorb(&other.histogram);
+    }
+
+    /// Average, p50, p99, p99.9 and max in microseconds; all 0 with no samples
+    pub fn stats_us(&self) -> [f64; 5] {
+        let samples = self.samples.load(Ordering::Relaxed);
+        if samples == 0 {
+            return [0.0; 5];
+        }
+        let min = self.min_ns.load(Ordering::Relaxed);
+        let max = self.max_ns.load(Ordering::Relaxed);
+        let at = |p: f64| self.histogram.value_at(p).map_or(0, |ns| ns.clamp(min, max)) as f64 / 1_000.0;
+        [
+            self.sum_ns.load(Ordering::Relaxed) as f64 / samples as f64 / 1_000.0,
+            at(50.0),
+            at(99.0),
+            at(99.9),
+            max as f64 / 1_000.0,
+        ]
+    }
+}
+
+/// Atomically add to an f64 stored as bits in an AtomicU64
+fn add_f64(cell: &AtomicU64, v: f64) {
+    let _ = cell.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        Some((f64::from_bits(bits) + v).to_bits())
+    });
+}
+
+/// Set by the Ctrl-C handler: the running test stops early and keeps its
+/// results, and no further tests start
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the user asked the run to stop (Ctrl-C)
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Name of this machine, for reports and result streams
+#[cfg(unix)]
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let name = String::from_utf8_lossy(&buf[..len]).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Name of this machine, for reports and result streams
+#[cfg(windows)]
+pub fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok().filter(|h| !h.is_empty())
+}
+
+/// Live total of one counter across the per-device metrics
+fn sum_counter(metrics: &[Arc<Metrics>], counter: fn(&Metrics) -> &AtomicU64) -> u64 {
+    metrics.iter().map(|m| counter(m).load(Ordering::Relaxed)).sum()
+}
+
+/// Per-worker latency samples. Recording touches only thread-local state, so
+/// workers share nothing while the test runs: the running sum is published
+/// with each batched counter update (for live averages) and the histogram and
+/// min/max once the worker stops.
+pub struct LatencySampler {
+    histogram: histogram::LocalHistogram,
+    /// Worker id, when its own p99 is reported (`--per-thread-stats`)
+    thread: Option<u32>,
+    sum_ns: u64,
+    samples: u64,
+    sq_sum_ns: f64,
+    min_ns: u64,
+    max_ns: u64,
+    /// Mixed workloads: the samples again, split into reads and writes
+    directions: Option<Box<[LatencySampler; 2]>>,
+    /// Samples on their way to the `--latency-trace` file
+    trace: Option<trace::TraceBuffer>,
+}
+
+impl LatencySampler {
+    pub fn new(config: &TestConfig, thread_id: u32, metrics: &Metrics) -> Self {
+        let mut sampler = Self::empty(config.histogram_bits, config.per_thread_stats.then_some(thread_id));
+        if config.rw_mix.is_some() {
+            let bits = config.histogram_bits;
+            sampler.directions = Some(Box::new([Self::empty(bits, None), Self::empty(bits, None)]));
+        }
+        let warming = metrics.warming_up.load(Ordering::Relaxed);
+        sampler.trace = metrics.trace.clone().map(|sender| trace::TraceBuffer::new(sender, thread_id, !warming));
+        sampler
+    }
+
+    fn empty(histogram_bits: u32, thread: Option<u32>) -> Self {
+        Self {
+            histogram: histogram::LocalHistogram::new(histogram_bits),
+            thread,
+            sum_ns: 0,
+            samples: 0,
+            sq_sum_ns: 0.0,
+            min_ns: u64::MAX,
+            max_ns: 0,
+            directions: None,
+            trace: None,
+        }
+    }
+
+    /// Record the latency of one completed read or write
+    pub fn record(&mut self, ns: u64, write: bool) {
+        self.histogram.record(ns);
+        self.sum_ns += ns;
+        self.samples += 1;
+        self.sq_sum_ns += (ns as f64) * (ns as f64);
+        self.min_ns = self.min_ns.min(ns);
+        self.max_ns = self.max_ns.max(ns);
+        if let Some(directions) = &mut self.directions {
+            directions[write as usize].record(ns, write);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.push(ns, write);
+        }
+    }
+
+    /// Drop everything recorded so far (the end of a warmup window)
+    pub fn clear(&mut self) {
+        self.histogram.clear();
+        self.sum_ns = 0;
+        self.samples = 0;
+        self.sq_sum_ns = 0.0;
+        self.min_ns = u64::MAX;
+        self.max_ns = 0;
+        if let Some(directions) = &mut self.directions {
+            directions.iter_mut().for_each(LatencySampler::clear);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.start();
+        }
+    }
+
+    /// Publish the samples taken since the last flush to the live average
+    pub fn flush_average(&mut self, metrics: &Metrics) {
+        metrics.latency_sum_ns.fetch_add(self.sum_ns, Ordering::Relaxed);
+        metrics.latency_samples.fetch_add(self.samples, Ordering::Relaxed);
+        self.sum_ns = 0;
+        self.samples = 0;
+    }
+
+    /// Publish everything; called once when the worker stops
+    pub fn finish(mut self, metrics: &Metrics) {
+        self.flush_average(metrics);
+        metrics.latency_min_ns.fetch_min(self.min_ns, Ordering::Relaxed);
+        metrics.latency_max_ns.fetch_max(self.max_ns, Ordering::Relaxed);
+        add_f64(&metrics.latency_sq_sum_ns, self.sq_sum_ns);
+        metrics.latency_histogram.absorb_local(&self.histogram);
+        if let (Some(thread), Some(p99)) = (self.thread, self.histogram.value_at(99.0)) {
+            metrics.thread_p99_ns.lock().unwrap().push((thread, p99));
+        }
+        if let Some(directions) = &self.directions {
+            metrics.read_latency.absorb_local(&directions[0]);
+            metrics.write_latency.absorb_local(&directions[1]);
+        }
+        if let Some(trace) = self.trace {
+            trace.finish();
+        }
+    }
+}
+
+/// Per-worker count of in-flight reads and writes, sampled to show the live mix
+#[derive(Default)]
+pub struct InflightMix {
+    reads: u64,
+    writes: u64,
+    read_sum: u64,
+    write_sum: u64,
+    samples: u64,
+}
+
+impl InflightMix {
+    pub fn issue(&mut self, write: bool) {
+        if write {
+            self.writes += 1;
+        } else {
+            self.reads += 1;
+        }
+    }
+
+    pub fn complete(&mut self, write: bool) {
+        if write {
+            self.writes -= 1;
+        } else {
+            self.reads -= 1;
+        }
+    }
+
+    /// I/Os issued and not yet completed (only the async workers ask; the
+    /// synchronous worker's lanes each have at most one)
+    #[cfg_attr(not(any(windows, target_os = "linux")), allow(dead_code))]
+    pub fn in_flight(&self) -> u64 {
+        self.reads + self.writes
+    }
+
+    pub fn sample(&mut self) {
+        self.read_sum += self.reads;
+        self.write_sum += self.writes;
+        self.samples += 1;
+    }
+
+    /// Forget the samples so far; the live in-flight counts are kept
+    pub fn clear(&mut self) {
+        self.read_sum = 0;
+        self.write_sum = 0;
+        self.samples = 0;
+    }
+
+    pub fn flush(&self, metrics: &Metrics) {
+        metrics.inflight_read_sum.fetch_add(self.read_sum, Ordering::Relaxed);
+        metrics.inflight_write_sum.fetch_add(self.write_sum, Ordering::Relaxed);
+        metrics.inflight_samples.fetch_add(self.samples, Ordering::Relaxed);
+    }
+}
+
+/// Per-worker `--fsync-every` state: flushes the device after every `every`
+/// completed writes and times each flush. A failed flush stops further
+/// flushes; the worker checks `failed` to wind down and reports it from `finish`.
+pub struct FsyncSchedule {
+    every: u64,
+    pending: u64,
+    count: u64,
+    sum_ns: u64,
+    max_ns: u64,
+    error: Option<io::Error>,
+}
+
+impl FsyncSchedule {
+    pub fn new(config: &TestConfig) -> Self {
+        Self {
+            every: if config.has_writes() { config.fsync_every as u64 } else { 0 },
+            pending: 0,
+            count: 0,
+            sum_ns: 0,
+            max_ns: 0,
+            error: None,
+        }
+    }
+
+    /// Note one completed write, calling `flush` when the interval is reached
+    pub fn write_done(&mut self, flush: impl FnOnce() -> io::Result<()>) {
+        if self.every == 0 {
+            return;
+        }
+        self.pending += 1;
+        if self.pending < self.every {
+            return;
+        }
+        self.pending = 0;
+
+        let start = Instant::now();
+        if let Err(e) = flush() {
+            self.every = 0;
+            self.error = Some(e);
+            return;
+        }
+        let ns = start.elapsed().as_nanos() as u64;
+        self.count += 1;
+        self.sum_ns += ns;
+        self.max_ns = self.max_ns.max(ns);
+    }
+
+    pub fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Forget the flushes timed so far (the end of a warmup window)
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.sum_ns = 0;
+        self.max_ns = 0;
+    }
+
+    /// Publish the flush timings and surface any flush error
+    pub fn finish(self, metrics: &Metrics) -> io::Result<()> {
+        metrics.flush_count.fetch_add(self.count, Ordering::Relaxed);
+        metrics.flush_sum_ns.fetch_add(self.sum_ns, Ordering::Relaxed);
+        metrics.flush_max_ns.fetch_max(self.max_ns, Ordering::Relaxed);
+        match self.error {
+            Some(e) => Err(io::Error::new(e.kind(), format!("flush failed: {}", e))),
+            None => Ok(()),
+        }
     }
 }
 
+/// How far a paced worker may fall behind its schedule and still catch up;
+/// beyond that the schedule moves on rather than firing a burst of I/Os
+const PACER_MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Per-worker `--target-iops` schedule. Each worker gets an equal share of the
+/// target and issues its I/Os at fixed intervals, instead of reissuing a slot
+/// the moment it completes. Due times are absolute, so a late wakeup is made
+/// up on the next I/O rather than lowering the rate.
+pub struct Pacer {
+    interval: Duration,
+    next: Instant,
+}
+
+impl Pacer {
+    /// None unless `--target-iops` is set
+    pub fn new(config: &TestConfig, thread_id: u32) -> Option<Self> {
+        let target = config.target_iops?;
+        let workers = config.threads.max(1) as u64 * config.device_paths.len().max(1) as u64;
+        let interval = Duration::from_secs_f64(workers as f64 / target as f64);
+        // Stagger the workers across one interval so they don't issue in lockstep
+        let phase = interval.mul_f64((thread_id as u64 % workers) as f64 / workers as f64);
+        Some(Self {
+            interval,
+            next: Instant::now() + phase,
+        })
+    }
+
+    /// When the next I/O may be issued, for the async workers' completion wait
+    /// and the synchronous worker's lanes
+    pub fn due(&self) -> Instant {
+        self.next
+    }
+
+    /// Book the next I/O if it is due; false means wait until `due`
+    pub fn take(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next {
+            return false;
+        }
+        if let Some(floor) = now.checked_sub(PACER_MAX_LAG) {
+            self.next = self.next.max(floor);
+        }
+        self.next += self.interval;
+        true
+    }
+
+    /// Sleep until the next I/O is due, waking early if the test stops. Only
+    /// called with nothing in flight, so no completion waits behind the sleep.
+    #[cfg_attr(not(any(windows, target_os = "linux")), allow(dead_code))]
+    pub fn sleep(&self, stop: &AtomicBool) {
+        Self::sleep_until(self.next, stop)
+    }
+
+    /// Sleep until `due`, waking early if the test stops
+    pub fn sleep_until(due: Instant, stop: &AtomicBool) {
+        loop {
+            let now = Instant::now();
+            if now >= due || stop.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep((due - now).min(Duration::from_millis(100)));
+        }
+    }
+}
+
+/// Sector sizes of a block device. Direct I/O offsets and lengths must be
+/// multiples of the logical size; the physical size is what the media writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectorSize {
+    pub logical: u32,
+    pub physical: u32,
+}
+
+/// Alignment for I/O buffers on `path`: the logical sector size, but at least a page
+pub(crate) fn buffer_alignment(path: &str) -> usize {
+    get_sector_size(path).map_or(4096, |s| (s.logical as usize).max(4096))
+}
+
+/// Check that direct I/O of `io_size` bytes at offsets aligned to `offset_align`
+/// suits the device's logical sector size. Misaligned direct I/O opens fine and
+/// then fails every request (EINVAL on Linux, ERROR_INVALID_PARAMETER on
+/// Windows), so catch it up front with a clear message. macOS F_NOCACHE I/O has
+/// no alignment requirement, so it is left alone there.
+fn check_alignment(path: &str, io_size: u64, offset_align: u64) -> io::Result<()> {
+    if cfg!(target_os = "macos") {
+        return Ok(());
+    }
+    let logical = match get_sector_size(path) {
+        Some(sector) => sector.logical as u64,
+        None => return Ok(()),
+    };
+
+    let misaligned = [("block size", io_size), ("offset alignment", offset_align)]
+        .into_iter()
+        .find(|&(_, value)| !value.is_multiple_of(logical));
+    match misaligned {
+        Some((what, value)) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} requires {}-byte alignment for direct I/O, got {} of {} bytes",
+                path, logical, what, value
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// What kind of target a device path refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    /// A regular file on a filesystem
+    File,
+    /// A raw block device, physical drive or volume
+    BlockDevice,
+    /// Anything else (character device, pipe, missing path, ...)
+    Other,
+}
+
+impl DeviceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceKind::File => "file",
+            DeviceKind::BlockDevice => "block device",
+            DeviceKind::Other => "other",
+        }
+    }
+}
+
+/// Order in which a test walks the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessPattern {
+    /// Uniformly random block-aligned offsets
+    Random,
+    /// Each I/O slot streams through its own region of the device
+    Sequential,
+}
+
+/// How random offsets are spread over the test range
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OffsetDistribution {
+    /// Every block equally likely
+    #[default]
+    Uniform,
+    /// Block k of the range drawn in proportion to 1/k^skew: a few blocks take most I/Os
+    Zipf,
+    /// Access falls off as e^(-skew * position) across the range
+    Exponential,
+}
+
+impl OffsetDistribution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OffsetDistribution::Uniform => "uniform",
+            OffsetDistribution::Zipf => "zipf",
+            OffsetDistribution::Exponential => "exponential",
+        }
+    }
+
+    /// Skew used when `--skew` is not given
+    pub fn default_skew(&self) -> f64 {
+        match self {
+            OffsetDistribution::Uniform => 0.0,
+            OffsetDistribution::Zipf => 1.2,
+            OffsetDistribution::Exponential => 10.0,
+        }
+    }
+}
+
+/// Weighted I/O sizes (`--bs-mix`): each I/O draws its size, the chance of
+/// each being its percentage. Written as `4k:70,64k:30`, sizes in KB.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BlockSizeMix {
+    /// (size in KB, percent of I/Os), percents adding up to 100
+    shares: Vec<(u32, u8)>,
+}
+
+impl BlockSizeMix {
+    /// The largest size in KB; buffers and offsets are laid out for it
+    pub fn largest_kb(&self) -> u32 {
+        self.shares.iter().map(|&(kb, _)| kb).max().unwrap_or(0)
+    }
+
+    pub fn sizes_kb(&self) -> impl Iterator<Item = u32> + '_ {
+        self.shares.iter().map(|&(kb, _)| kb)
+    }
+
+    /// Draw the size in bytes of the next I/O
+    pub fn pick(&self, rng: &mut StdRng) -> u64 {
+        let mut roll = rng.gen_range(0..100u32);
+        for &(kb, percent) in &self.shares {
+            if roll < percent as u32 {
+                return kb as u64 * 1024;
+            }
+            roll -= percent as u32;
+        }
+        self.largest_kb() as u64 * 1024
+    }
+}
+
+impl std::str::FromStr for BlockSizeMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut shares: Vec<(u32, u8)> = Vec::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (size, percent) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("expected SIZE:PERCENT (e.g. 4k:70), got '{}'", entry))?;
+            let size = size.trim();
+            let (digits, scale) = match size.char_indices().last() {
+                Some((i, 'k' | 'K')) => (&size[..i], 1),
+                Some((i, 'm' | 'M')) => (&size[..i], 1024),
+                _ => (size, 1),
+            };
+            let kb = match digits.parse::<u32>().ok().and_then(|n| n.checked_mul(scale)) {
+                Some(kb) if kb > 0 => kb,
+                _ => return Err(format!("invalid block size '{}' (KB, or with a k/m suffix)", size)),
+            };
+            let percent = match percent.trim().parse::<u8>() {
+                Ok(p @ 1..=100) => p,
+                _ => return Err(format!("percent for {} must be 1-100, got '{}'", size, percent.trim())),
+            };
+            if shares.iter().any(|&(k, _)| k == kb) {
+                return Err(format!("block size {} KB is listed twice", kb));
+            }
+            shares.push((kb, percent));
+        }
+        let total: u32 = shares.iter().map(|&(_, p)| p as u32).sum();
+        if total != 100 {
+            return Err(format!("percentages must add up to 100, got {}", total));
+        }
+        Ok(Self { shares })
+    }
+}
+
+impl std::fmt::Display for BlockSizeMix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let entries: Vec<String> = self.shares.iter().map(|(kb, percent)| format!("{}k:{}", kb, percent)).collect();
+        f.write_str(&entries.join(","))
+    }
+}
+
+impl TryFrom<String> for BlockSizeMix {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<BlockSizeMix> for String {
+    fn from(mix: BlockSizeMix) -> String {
+        mix.to_string()
+    }
+}
+
+/// How a test's queue depth is counted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum QdSemantics {
+    /// Every worker thread keeps `queue_depth` I/Os in flight
+    PerThread,
+    /// `queue_depth` is the total in flight per device, split across its threads (fio style)
+    Total,
+}
+
+/// How `--create-file` sets aside the file's space
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FileAlloc {
+    /// Write every byte with the write pattern: slow, but nothing is sparse or unwritten
+    #[default]
+    Fill,
+    /// Allocate the space without writing it (fallocate, F_PREALLOCATE, or SetEndOfFile + SetFileValidData)
+    Preallocate,
+}
+
+/// What written blocks contain. Drives and arrays that compress or
+/// deduplicate inline write far less than was sent for anything but random data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum WritePattern {
+    /// All zero bytes: trivially compressible and deduplicable
+    Zero,
+    /// Random bytes: neither compresses nor deduplicates
+    #[default]
+    Random,
+    /// One random 4 KiB block repeated: incompressible, but every block is a duplicate
+    Repeating,
+}
+
+impl WritePattern {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WritePattern::Zero => "zero",
+            WritePattern::Random => "random",
+            WritePattern::Repeating => "repeating",
+        }
+    }
+
+    /// Fill a buffer about to be written with this pattern, drawing any random
+    /// bytes from `rng`
+    pub fn fill(self, buf: &mut [u8], rng: &mut impl Rng) {
+        match self {
+            WritePattern::Zero => buf.fill(0),
+            WritePattern::Random => rng.fill(buf),
+            WritePattern::Repeating => {
+                let mut block = [0u8; 4096];
+                rng.fill(&mut block[..]);
+                for chunk in buf.chunks_mut(block.len()) {
+                    chunk.copy_from_slice(&block[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
+
+/// What ended the measured window of a test
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// `--duration` elapsed
+    #[default]
+    Duration,
+    /// `--size-limit` bytes were transferred
+    SizeLimit,
+    /// `--ops-limit` I/Os completed
+    OpsLimit,
+    /// Ctrl+C
+    Interrupted,
+    /// `--max-runtime` ran out
+    MaxRuntime,
+    /// Every worker exited early on an error
+    WorkersFailed,
+}
+
+impl StopReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::Duration => "duration",
+            StopReason::SizeLimit => "size limit",
+            StopReason::OpsLimit => "ops limit",
+            StopReason::Interrupted => "interrupted",
+            StopReason::MaxRuntime => "max runtime",
+            StopReason::WorkersFailed => "workers failed",
+        }
+    }
+}
+
+impl AccessPattern {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessPattern::Random => "random",
+            AccessPattern::Sequential => "sequential",
+        }
+    }
+}
+
+/// Source of block-aligned I/O offsets for one worker.
+///
+/// Random: by default every offset is drawn fresh from the worker's PRNG, so
+/// the access pattern never repeats. With a pool size set, offsets are
+/// pre-generated once and replayed cyclically: marginally cheaper per op,
+/// but a long run repeats the same sequence every `pool` ops, which a
+/// controller cache can learn.
+///
+/// Sequential: every slot of every worker on a device gets its own cursor,
+/// staggered evenly across the device, that advances one block per I/O and
+/// wraps at the end of the test range.
+pub struct OffsetGenerator {
+    blocks: u64,
+    picker: distribution::BlockPicker,
+    io_size: u64,
+    shift: u64,
+    pool: Vec<u64>,
+    idx: usize,
+    cursors: Vec<u64>,
+}
+
+impl OffsetGenerator {
+    pub fn new(config: &TestConfig, thread_id: u32, rng: &mut StdRng, test_range: u64) -> Self {
+        let blocks = (test_range - config.offset_shift) / config.io_size;
+        let (dist, skew) = config.distribution().unwrap_or((OffsetDistribution::Uniform, 0.0));
+        let mut offsets = Self {
+            blocks,
+            picker: distribution::BlockPicker::new(dist, skew, blocks),
+            io_size: config.io_size,
+            shift: config.range_start + config.offset_shift,
+            pool: Vec::new(),
+            idx: 0,
+            cursors: Vec::new(),
+        };
+        match config.access_pattern {
+            AccessPattern::Sequential => {
+                let qd = config.queue_depth as u64;
+                let streams = config.threads as u64 * qd;
+                let first = (thread_id % config.threads) as u64 * qd;
+                offsets.cursors = (first..first + qd)
+                    .map(|stream| stream * offsets.blocks / streams)
+                    .collect();
+            }
+            AccessPattern::Random if config.offset_pool > 0 => {
+                offsets.pool = (0..config.offset_pool).map(|_| offsets.draw(rng)).collect();
+                // Start each worker at its own point in the pool
+                offsets.idx = rng.gen_range(0..offsets.pool.len());
+            }
+            AccessPattern::Random => {}
+        }
+        offsets
+    }
+
+    fn draw(&self, rng: &mut StdRng) -> u64 {
+        self.picker.pick(self.blocks, rng) * self.io_size + self.shift
+    }
+
+    /// Offset for the next I/O issued on `slot`
+    pub fn next(&mut self, slot: usize, rng: &mut StdRng) -> u64 {
+        if let Some(cursor) = self.cursors.get_mut(slot) {
+            let block = *cursor;
+            *cursor = (block + 1) % self.blocks;
+            return block * self.io_size + self.shift;
+        }
+        if self.pool.is_empty() {
+            return self.draw(rng);
+        }
+        let off = self.pool[self.idx];
+        self.idx = (self.idx + 1) % self.pool.len();
+        off
+    }
+}
+
+/// Options controlling how devices are opened
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenFlags {
+    /// Retry without direct I/O if the device rejects unbuffered access
+    pub buffered_fallback: bool,
+    /// Skip direct I/O entirely and go through the OS cache (`--buffered`)
+    pub buffered: bool,
+    /// Open for writing with O_DSYNC so writes complete only once durable
+    /// (`--write-through`; Linux, where Windows always writes through)
+    pub write_through: bool,
+}
+
+/// Turn a permission error from opening a device into an actionable message.
+/// Raw device access is the most common first-run failure.
+pub(crate) fn privilege_error(err: io::Error) -> io::Error {
+    if err.kind() != io::ErrorKind::PermissionDenied {
+        return err;
+    }
+    #[cfg(windows)]
+    let hint = "run from an elevated (Run as administrator) prompt";
+    #[cfg(target_os = "macos")]
+    let hint = "run with sudo";
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let hint = "run with sudo, or grant access to the device (e.g. the disk group or CAP_SYS_ADMIN)";
+    io::Error::new(err.kind(), format!("{} - raw device access needs elevated privileges: {}", err, hint))
+}
+
+/// Name the size query that failed (the OS error code stays in the message)
+/// and point at the manual override
+pub(crate) fn size_query_error(query: &str, err: io::Error) -> io::Error {
+    io::Error::new(
+        err.kind(),
+        format!("{} failed: {}; give the size with --device-size", query, err),
+    )
+}
+
+/// Size of a device or file: `size_override` when given (`--device-size`),
+/// otherwise the OS's answer, which must not be 0
+pub fn device_size(path: &str, size_override: Option<u64>) -> io::Result<u64> {
+    if let Some(size) = size_override {
+        return Ok(size);
+    }
+    match get_device_size(path)? {
+        0 => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} reports a size of 0 bytes; give the size with --device-size", path),
+        )),
+        size => Ok(size),
+    }
+}
+
+/// Warn (once per run) that a device fell back to buffered I/O
+pub(crate) fn warn_buffered(path: &str) {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        warn!(
+            "  Warning: {} does not support direct I/O, opened buffered - results include OS cache effects",
+            path
+        );
+    });
+}
+
 /// Configuration for a benchmark test (single or multiple devices)
+#[derive(Clone)]
 pub struct TestConfig {
     pub device_paths: Vec<String>,
     pub io_size: u64,
     pub threads: u32,  // per device
     pub queue_depth: u32,
+    /// Whether `queue_depth` is per thread or the total per device
+    pub qd_semantics: QdSemantics,
     pub duration_secs: u32,
+    /// End the test early once this many bytes / I/Os have completed
+    pub size_limit: Option<u64>,
+    pub ops_limit: Option<u64>,
+    /// Hold each test to this many IOPS in total, for latency at a fixed load
+    pub target_iops: Option<u64>,
+    /// Seconds of I/O run before the measured window and excluded from results
+    pub warmup_secs: u32,
+    /// `--max-runtime`: the test stops early at this moment, warmup included
+    pub deadline: Option<Instant>,
     pub is_write: bool,
+    /// Bytes added to every I/O offset; non-zero deliberately misaligns I/O
+    pub offset_shift: u64,
+    /// Restrict I/O to `[range_start, range_start + range_len)` of each device
+    /// (`range_len` None = to the end of the device)
+    pub range_start: u64,
+    pub range_len: Option<u64>,
+    /// Size to assume for every device instead of querying it (`--device-size`)
+    pub device_size: Option<u64>,
+    /// Test only the start of the window that `--fill-percent` wrote
+    pub fill_percent: Option<u8>,
+    pub open_flags: OpenFlags,
+    /// Bind all worker threads and their buffers to this NUMA node
+    pub numa_node: Option<u32>,
+    /// Pin each worker thread to its own CPU core, round-robin
+    pub cpu_affinity: bool,
+    /// Allocate each worker's buffers on the NUMA node of the core it runs on (Linux)
+    pub numa_local: bool,
+    /// Serve live counter snapshots as JSON on this socket / named pipe
+    pub control_socket: Option<PathBuf>,
+    /// Append per-interval throughput and latency to this CSV file
+    pub timeseries: Option<PathBuf>,
+    /// Append every sampled latency to this CSV file
+    pub latency_trace: Option<PathBuf>,
+    /// Flush OS caches for each device before the test starts
+    pub flush_caches: bool,
+    /// After a write test, flush device write caches and report the drain time
+    pub drain_flush: bool,
+    /// Writes stamp self-describing blocks and reads check them (`--verify`)
+    pub verify: bool,
+    /// Flush the device after every N completed writes (0 = never)
+    pub fsync_every: u32,
+    /// NDJSON feed receiving progress intervals and the final result
+    pub stream: Option<StreamSender>,
+    /// Record the latency of every Nth completed I/O
+    pub latency_sample_rate: u32,
+    /// Sub-bucket bits of the latency histogram: percentile precision vs memory
+    pub histogram_bits: u32,
+    /// Work out each worker's own p99 and report their spread
+    pub per_thread_stats: bool,
+    /// Emit progress intervals as JSON lines on stderr instead of the console line
+    pub progress_json: bool,
+    /// Decimal places / grouping for console output
+    pub number_format: NumberFormat,
+    /// Mixed workload: percentage of ops that are reads (overrides `is_write`)
+    pub rw_mix: Option<u8>,
+    /// Draw each I/O's size from this mix; `io_size` is then its largest size
+    pub bs_mix: Option<BlockSizeMix>,
+    /// Base seed for the per-worker offset RNGs
+    pub seed: Option<u64>,
+    /// Issue every slot of a write-only test from one shared buffer
+    pub shared_write_buffer: bool,
+    /// Contents of the write buffers (ignored by `--verify`, which stamps its own)
+    pub write_pattern: WritePattern,
+    /// io_uring setup flags (Linux): poll for completions / a kernel thread
+    /// polling the submission queue
+    pub io_poll: bool,
+    pub sq_poll: bool,
+    /// Register buffers and the device fd with io_uring (Linux)
+    pub fixed_buffers: bool,
+    /// Replay a pre-generated pool of this many offsets (0 = draw every offset fresh)
+    pub offset_pool: usize,
+    pub access_pattern: AccessPattern,
+    /// Spread of random offsets (`--distribution`) and its `--skew` (None = the default)
+    pub offset_distribution: OffsetDistribution,
+    pub skew: Option<f64>,
+}
+
+impl TestConfig {
+    /// Whether any op in this test can be a write
+    pub fn has_writes(&self) -> bool {
+        match self.rw_mix {
+            Some(read_pct) => read_pct < 100,
+            None => self.is_write,
+        }
+    }
+
+    /// Whether slots share one write buffer: asked for, and safe because no
+    /// op reads into the buffer and `--verify` doesn't stamp it per I/O
+    pub fn shares_write_buffer(&self) -> bool {
+        let write_only = match self.rw_mix {
+            Some(read_pct) => read_pct == 0,
+            None => self.is_write,
+        };
+        self.shared_write_buffer && write_only && !self.verify
+    }
+
+    /// Names of the io_uring options in effect, for the test header
+    fn ring_modes(&self) -> Vec<&'static str> {
+        [
+            (self.io_poll, "IOPOLL"),
+            (self.sq_poll, "SQPOLL"),
+            (self.fixed_buffers, "fixed buffers"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect()
+    }
+
+    /// Decide whether the next op is a write. Mixed workloads draw per op,
+    /// so the in-flight mix tracks the ratio rather than a fixed slot split.
+    pub fn next_is_write(&self, rng: &mut StdRng) -> bool {
+        match self.rw_mix {
+            Some(read_pct) => rng.gen_range(0..100) >= read_pct as u32,
+            None => self.is_write,
+        }
+    }
+
+    /// Bytes to move in the next I/O: `io_size`, or a draw from `--bs-mix`
+    pub fn next_io_len(&self, rng: &mut StdRng) -> u64 {
+        match &self.bs_mix {
+            Some(mix) => mix.pick(rng),
+            None => self.io_size,
+        }
+    }
+
+    /// The skewed distribution random offsets follow and its skew; None when
+    /// they are uniform or the test is sequential
+    pub fn distribution(&self) -> Option<(OffsetDistribution, f64)> {
+        if self.access_pattern != AccessPattern::Random || self.offset_distribution == OffsetDistribution::Uniform {
+            return None;
+        }
+        let dist = self.offset_distribution;
+        Some((dist, self.skew.unwrap_or_else(|| dist.default_skew())))
+    }
+
+    /// Whether `--max-runtime` has run out for this test
+    pub fn deadline_passed(&self) -> bool {
+        self.deadline.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// RNG for one worker. With `seed` set the stream is derived from the seed
+    /// and the global thread id, so runs are reproducible but no two workers
+    /// walk the same offset sequence; otherwise each worker seeds from the OS.
+    pub fn worker_rng(&self, thread_id: u32) -> StdRng {
+        match self.seed {
+            Some(seed) => {
+                StdRng::seed_from_u64(seed ^ (thread_id as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            }
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// RNG for one worker's write buffer contents: a separate stream derived
+    /// from the worker's, so filling buffers doesn't shift its offsets
+    pub fn fill_rng(&self, thread_id: u32) -> StdRng {
+        StdRng::seed_from_u64(self.worker_rng(thread_id).gen())
+    }
+
+    fn label(&self) -> String {
+        match self.rw_mix {
+            Some(read_pct) => format!("Mixed {}/{}", read_pct, 100 - read_pct),
+            None if self.is_write => "Write".to_string(),
+            None => "Read".to_string(),
+        }
+    }
 }
 
 /// Run a benchmark test on one or more devices and return the result
-pub fn run_test(config: &TestConfig) -> io::Result<TestResult> {
-    let test_type = if config.is_write { "Write" } else { "Read" };
+pub fn run_test(config: &TestConfig) -> Result<TestResult, BenchError> {
+    // Workers always keep a per-thread depth in flight; resolve a total to that
+    let requested_qd = config.queue_depth;
+    let resolved;
+    let config = match config.qd_semantics {
+        QdSemantics::PerThread => config,
+        QdSemantics::Total => {
+            resolved = TestConfig {
+                queue_depth: (requested_qd / config.threads.max(1)).max(1),
+                ..config.clone()
+            };
+            &resolved
+        }
+    };
+    let test_type = config.label();
     let io_kb = config.io_size / 1024;
 
     if config.device_paths.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             "No devices specified",
-        ));
+        )
+        .into());
+    }
+
+    info!(
+        "  {} test: {}KB {} blocks, {} threads per device, QD={} per thread ({} total per device), {} seconds",
+        test_type,
+        io_kb,
+        config.access_pattern.as_str(),
+        config.threads,
+        config.queue_depth,
+        config.threads * config.queue_depth,
+        config.duration_secs
+    );
+    if let Some((dist, skew)) = config.distribution() {
+        info!("  Offsets: {} distribution, skew {} (hot blocks at the start of the range)", dist.as_str(), skew);
+    }
+    if let Some(mix) = &config.bs_mix {
+        info!("  Block sizes: {} (KB:percent of I/Os), offsets aligned to {}KB", mix, io_kb);
+    }
+    if config.qd_semantics == QdSemantics::Total && config.threads * config.queue_depth != requested_qd {
+        info!(
+            "  Note: total QD {} does not split evenly across {} threads; running {} in total",
+            requested_qd,
+            config.threads,
+            config.threads * config.queue_depth
+        );
+    }
+    if config.offset_shift > 0 {
+        info!("  Offsets shifted by {} bytes (misaligned)", config.offset_shift);
+    }
+    if let Some(target) = config.target_iops {
+        info!(
+            "  Paced to {} IOPS in total ({} per worker)",
+            config.number_format.count(target as f64),
+            config.number_format.fixed(target as f64 / (config.threads as f64 * config.device_paths.len() as f64), 1)
+        );
     }
+    if config.open_flags.buffered {
+        info!("  Buffered I/O: results include OS cache effects");
+    }
+    if config.open_flags.write_through && config.has_writes() {
+        if cfg!(target_os = "macos") {
+            info!("  Note: --write-through is not supported on macOS; ignored (--fsync-every flushes with F_FULLFSYNC)");
+        } else if cfg!(target_os = "linux") {
+            info!("  Write-through: O_DSYNC, each write completes once on stable media");
+        }
+    }
+    if config.io_poll && config.open_flags.buffered {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--io-poll needs direct I/O and can't be combined with --buffered",
+        )
+        .into());
+    }
+    let ring_modes = config.ring_modes();
+    if !ring_modes.is_empty() {
+        if cfg!(target_os = "linux") {
+            info!("  io_uring: {}", ring_modes.join(" + "));
+        } else {
+            info!("  Note: --io-poll, --sq-poll and --fixed-buffers need io_uring (Linux); ignored");
+        }
+    }
+    if config.verify && !config.io_size.is_multiple_of(verify::CHUNK as u64) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("--verify needs a block size that is a multiple of {}KB", verify::CHUNK / 1024),
+        )
+        .into());
+    }
+
+    if config.flush_caches {
+        for path in &config.device_paths {
+            if let Err(e) = flush_caches(path) {
+                info!("  Note: cache flush skipped for {}: {}", path, e);
+            }
+        }
+    }
+
+    let node_affinity = match config.numa_node {
+        Some(node) => {
+            let affinity = CpuAffinity::for_node(node)?;
+            info!("  Bound to NUMA node {}", node);
+            Some(affinity)
+        }
+        None => None,
+    };
+    // With --cpu-affinity each worker gets one core of its own (round-robin),
+    // drawn from the NUMA node if one was given
+    let core_affinity = if config.cpu_affinity {
+        let cores = match node_affinity {
+            Some(node) => node.cores(),
+            None => CpuAffinity::current()?.cores(),
+        };
+        info!(
+            "  Workers pinned round-robin to {} CPU core{}",
+            cores.len(),
+            if cores.len() == 1 { "" } else { "s" }
+        );
+        if config.numa_local && config.numa_node.is_none() && cfg!(target_os = "linux") {
+            info!("  I/O buffers allocated on each worker's NUMA node");
+        }
+        cores
+    } else {
+        Vec::new()
+    };
 
-    println!(
-        "  {} test: {}KB blocks, {} threads per device, QD={}, {} seconds",
-        test_type, io_kb, config.threads, config.queue_depth, config.duration_secs
-    );
+    let trace = match &config.latency_trace {
+        Some(path) => match trace::LatencyTrace::open(path, &test_type, io_kb, config.queue_depth) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                warn!("  Latency trace {} unavailable: {}", path.display(), e);
+                None
+            }
+        },
+        None => None,
+    };
 
-    let metrics = Arc::new(Metrics::new());
+    let shared_config = Arc::new(config.clone());
+    // One set of counters per device so a slow member of a multi-device run stands out
+    let device_metrics: Vec<Arc<Metrics>> = config
+        .device_paths
+        .iter()
+        .map(|_| Arc::new(Metrics::new(config.histogram_bits).with_trace(trace.as_ref().and_then(trace::LatencyTrace::sender))))
+        .collect();
     let stop = Arc::new(AtomicBool::new(false));
     let duration = Duration::from_secs(config.duration_secs as u64);
 
@@ -94,138 +1408,684 @@ pub fn run_test(config: &TestConfig) -> io::Result<TestResult> {
     let mut total_size: u64 = 0;
 
     for device_path in &config.device_paths {
-        let device_size = get_device_size(device_path)?;
-        if device_size == 0 {
+        let device_size =
+            device_size(device_path, config.device_size).map_err(|e| BenchError::device(device_path, e))?;
+
+        // Direct I/O on a block device must match its logical block size
+        if !config.open_flags.buffered {
+            let offset_align = config.range_start + config.offset_shift;
+            check_alignment(device_path, config.io_size, offset_align)
+                .map_err(|e| BenchError::device(device_path, e))?;
+            for kb in config.bs_mix.iter().flat_map(BlockSizeMix::sizes_kb) {
+                check_alignment(device_path, kb as u64 * 1024, offset_align)
+                    .map_err(|e| BenchError::device(device_path, e))?;
+            }
+            // A file has no sector size to check against; hold its range to
+            // the 4 KB every filesystem accepts
+            if get_sector_size(device_path).is_none() && !config.range_start.is_multiple_of(4096) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("--range-start must be a multiple of 4096 bytes for direct I/O on {}", device_path),
+                )
+                .into());
+            }
+        }
+
+        // The window of the device the workers address: all of it unless a range was given
+        let window = match config.range_len {
+            Some(len) => len,
+            None => device_size.saturating_sub(config.range_start),
+        };
+        if config.range_start.saturating_add(window) > device_size {
             return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Device {} size is 0", device_path),
-            ));
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Range {}..{} is past the end of device {} ({} bytes)",
+                    config.range_start,
+                    config.range_start.saturating_add(window),
+                    device_path,
+                    device_size
+                ),
+            )
+            .into());
         }
-        device_info.push((device_path.clone(), device_size));
-        total_size += device_size;
+        // --fill-percent wrote only the start of the window; stay inside it
+        let window = match config.fill_percent {
+            Some(percent) => fill_len(window, percent),
+            None => window,
+        };
+        // Workers pick offsets in [0, window / io_size); a window smaller than one
+        // block would leave them nothing to address
+        if window < config.offset_shift + config.io_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} {} ({} bytes) is smaller than one {}KB block{}",
+                    if window < device_size { "Test range on device" } else { "Device" },
+                    device_path,
+                    window,
+                    io_kb,
+                    if config.offset_shift > 0 { " plus the offset shift" } else { "" }
+                ),
+            )
+            .into());
+        }
+        device_info.push((device_path.clone(), window));
+        total_size += window;
     }
 
-    println!(
-        "  Total device size: {:.2} GB ({} device{})",
-        total_size as f64 / (1024.0 * 1024.0 * 1024.0),
+    let nf = config.number_format;
+    let restricted = config.range_start > 0 || config.range_len.is_some() || config.fill_percent.is_some();
+    info!(
+        "  Total {} size: {} {} ({} device{})",
+        if restricted { "test range" } else { "device" },
+        nf.size(total_size as f64, 2),
+        nf.size_unit(),
         config.device_paths.len(),
         if config.device_paths.len() == 1 { "" } else { "s" }
     );
+    if restricted {
+        info!(
+            "  Range: {} {} starting at byte {} of each device",
+            nf.size((total_size / config.device_paths.len() as u64) as f64, 2),
+            nf.size_unit(),
+            config.range_start
+        );
+    }
 
-    let start = Instant::now();
+    if config.warmup_secs > 0 {
+        for m in &device_metrics {
+            m.warming_up.store(true, Ordering::Relaxed);
+        }
+    }
 
     // Spawn worker threads for all devices
     let mut handles = Vec::new();
     let mut global_thread_id = 0u32;
 
-    for (device_path, device_size) in device_info {
+    for ((device_path, device_size), device_metrics) in device_info.into_iter().zip(&device_metrics) {
         for _thread_id in 0..config.threads {
-            let metrics = Arc::clone(&metrics);
+            let metrics = Arc::clone(device_metrics);
             let stop = Arc::clone(&stop);
+            let config = Arc::clone(&shared_config);
             let dev_path = device_path.clone();
-            let io_size = config.io_size;
-            let queue_depth = config.queue_depth;
-            let is_write = config.is_write;
             let local_global_id = global_thread_id;
+            let affinity = match core_affinity.len() {
+                0 => node_affinity,
+                n => Some(core_affinity[local_global_id as usize % n]),
+            };
 
             let handle = std::thread::spawn(move || {
-                if let Err(e) = worker::run_worker(
-                    local_global_id,
-                    &dev_path,
-                    io_size,
-                    queue_depth,
-                    is_write,
-                    device_size,
-                    &stop,
-                    &metrics,
-                ) {
-                    eprintln!("  Worker {} error: {}", local_global_id, e);
+                if let Some(affinity) = affinity {
+                    if let Err(e) = affinity.pin_current_thread() {
+                        warn!("  Worker {} affinity error: {}", local_global_id, e);
+                    }
                 }
+                worker::run_worker(local_global_id, &dev_path, device_size, &config, &stop, &metrics).map_err(|e| {
+                    warn!("  Worker {} error: {}", local_global_id, e);
+                    BenchError::device(&dev_path, e)
+                })
             });
             handles.push(handle);
             global_thread_id += 1;
         }
     }
 
+    if config.warmup_secs > 0 {
+        info!("  Warming up for {}s (not measured)...", config.warmup_secs);
+        let warmup_end = Instant::now() + Duration::from_secs(config.warmup_secs as u64);
+        while Instant::now() < warmup_end && !interrupted() && !config.deadline_passed() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        for m in &device_metrics {
+            m.warming_up.store(false, Ordering::Relaxed);
+        }
+    }
+
+    let start = Instant::now();
+
+    let control = match &config.control_socket {
+        Some(path) => {
+            let label = format!("{} {}KB", test_type, io_kb);
+            match control::serve(path, label, device_metrics.clone(), start, Arc::clone(&stop)) {
+                Ok(handle) => Some(handle),
+                Err(e) => {
+                    warn!("  Control socket {} unavailable: {}", path.display(), e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut timeseries = match &config.timeseries {
+        Some(path) => match timeseries::TimeSeriesLog::open(path, &test_type, io_kb) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                warn!("  Time series {} unavailable: {}", path.display(), e);
+                None
+            }
+        },
+        None => None,
+    };
+
     // Progress reporting
     let report_interval = Duration::from_secs(5);
     let mut next_report = start + report_interval;
 
-    while start.elapsed() < duration {
-        std::thread::sleep(Duration::from_millis(100));
+    // 1-second windows: the timeline in the report, and used to find when the
+    // pipeline reached steady state
+    let window_interval = Duration::from_secs(1);
+    let mut next_window = start + window_interval;
+    let mut window_ops = 0u64;
+    let mut window_bytes = 0u64;
+    let mut windows: Vec<TimelinePoint> = Vec::new();
+
+    // Limits are checked against the published counters, which workers update
+    // in batches; poll them more often so a test overshoots as little as possible
+    let poll_interval = if config.size_limit.is_some() || config.ops_limit.is_some() {
+        Duration::from_millis(10)
+    } else {
+        Duration::from_millis(100)
+    };
+
+    let stop_reason = loop {
+        if interrupted() {
+            break StopReason::Interrupted;
+        }
+        if config.deadline_passed() {
+            break StopReason::MaxRuntime;
+        }
+        if start.elapsed() >= duration {
+            break StopReason::Duration;
+        }
+        // Workers only return before the stop flag when they hit an error
+        if handles.iter().all(|h| h.is_finished()) {
+            break StopReason::WorkersFailed;
+        }
+        if config
+            .size_limit
+            .is_some_and(|limit| sum_counter(&device_metrics, |m| &m.total_bytes) >= limit)
+        {
+            break StopReason::SizeLimit;
+        }
+        if config
+            .ops_limit
+            .is_some_and(|limit| sum_counter(&device_metrics, |m| &m.total_ops) >= limit)
+        {
+            break StopReason::OpsLimit;
+        }
+        std::thread::sleep(poll_interval);
+
+        if Instant::now() >= next_window {
+            let ops = sum_counter(&device_metrics, |m| &m.total_ops);
+            let bytes = sum_counter(&device_metrics, |m| &m.total_bytes);
+            let window_secs = window_interval.as_secs_f64();
+            windows.push(TimelinePoint {
+                elapsed_secs: start.elapsed().as_secs_f64(),
+                mbps: (bytes - window_bytes) as f64 / window_secs / (1024.0 * 1024.0),
+                iops: (ops - window_ops) as f64 / window_secs,
+            });
+            window_ops = ops;
+            window_bytes = bytes;
+            next_window += window_interval;
+        }
 
         if Instant::now() >= next_report {
-            let elapsed = start.elapsed().as_secs_f64();
-            let ops = metrics.total_ops.load(Ordering::Relaxed) as f64;
-            let bytes = metrics.total_bytes.load(Ordering::Relaxed) as f64;
-            let mbps = bytes / elapsed / (1024.0 * 1024.0);
-            let iops = ops / elapsed;
-
-            let lat_samples = metrics.latency_samples.load(Ordering::Relaxed) as f64;
-            let lat_sum = metrics.latency_sum_ns.load(Ordering::Relaxed) as f64;
-            let avg_lat_us = if lat_samples > 0.0 {
-                lat_sum / lat_samples / 1_000.0
+            let totals = timeseries::Totals {
+                elapsed: start.elapsed().as_secs_f64(),
+                ops: sum_counter(&device_metrics, |m| &m.total_ops),
+                bytes: sum_counter(&device_metrics, |m| &m.total_bytes),
+                lat_sum_ns: sum_counter(&device_metrics, |m| &m.latency_sum_ns),
+                lat_samples: sum_counter(&device_metrics, |m| &m.latency_samples),
+            };
+            if let Some(log) = &mut timeseries {
+                log.record(totals);
+            }
+
+            let elapsed = totals.elapsed;
+            let mbps = totals.bytes as f64 / elapsed / (1024.0 * 1024.0);
+            let iops = totals.ops as f64 / elapsed;
+            let avg_lat_us = if totals.lat_samples > 0 {
+                totals.lat_sum_ns as f64 / totals.lat_samples as f64 / 1_000.0
             } else {
                 0.0
             };
 
-            println!(
-                "  {:>3.0}s: {:>8.2} MB/s | {:>10.0} IOPS | {:>8.1} us avg lat",
-                elapsed, mbps, iops, avg_lat_us
-            );
+            if config.progress_json {
+                warn!(
+                    "{}",
+                    serde_json::json!({
+                        "test": test_type,
+                        "block_size_kb": config.io_size / 1024,
+                        "elapsed_s": elapsed,
+                        "mbps": mbps,
+                        "iops": iops,
+                        "avg_lat_us": avg_lat_us,
+                    })
+                );
+            } else {
+                info!(
+                    "  {:>3.0}s: {:>8} {} | {:>10} IOPS | {:>8} us avg lat",
+                    elapsed,
+                    nf.throughput(mbps, 2),
+                    nf.throughput_unit(),
+                    nf.count(iops),
+                    nf.fixed(avg_lat_us, 1)
+                );
+            }
+            if let Some(stream) = &config.stream {
+                stream.send(
+                    "progress",
+                    &test_type,
+                    serde_json::json!({
+                        "elapsed_s": elapsed,
+                        "block_size_kb": config.io_size / 1024,
+                        "mbps": mbps,
+                        "iops": iops,
+                        "avg_lat_us": avg_lat_us,
+                    }),
+                );
+            }
             next_report += report_interval;
         }
+    };
+
+    match stop_reason {
+        StopReason::Interrupted => {
+            info!("  Interrupted after {:.1}s; keeping the results so far", start.elapsed().as_secs_f64())
+        }
+        StopReason::SizeLimit | StopReason::OpsLimit | StopReason::MaxRuntime => {
+            info!("  Stopped after {:.1}s: {} reached", start.elapsed().as_secs_f64(), stop_reason.as_str())
+        }
+        StopReason::WorkersFailed => {
+            warn!("  All workers stopped after {:.1}s", start.elapsed().as_secs_f64())
+        }
+        StopReason::Duration => {}
     }
 
     // Signal stop
     stop.store(true, Ordering::Release);
 
-    // Wait for workers
+    // Wait for workers, keeping the first error in case none of them got any I/O done
+    let mut worker_error = None;
     for h in handles {
+        if let Ok(Err(e)) = h.join() {
+            worker_error.get_or_insert(e);
+        }
+    }
+    if let Some(h) = control {
         let _ = h.join();
     }
+    if let (Some(trace), Some(path)) = (trace, &config.latency_trace) {
+        match trace.finish() {
+            Ok((written, 0)) => info!("  Latency trace: {} samples appended to {}", written, path.display()),
+            Ok((written, dropped)) => warn!(
+                "  Warning: latency trace {} dropped {} of {} samples (the writer fell behind); raise --latency-sample-rate",
+                path.display(),
+                dropped,
+                written + dropped
+            ),
+            Err(e) => warn!("  Warning: latency trace {} incomplete: {}", path.display(), e),
+        }
+    }
 
     let elapsed = start.elapsed().as_secs_f64();
+    let metrics = Metrics::new(config.histogram_bits);
+    for m in &device_metrics {
+        metrics.absorb(m);
+    }
     let total_ops = metrics.total_ops.load(Ordering::Relaxed) as f64;
     let total_bytes = metrics.total_bytes.load(Ordering::Relaxed) as f64;
-    let lat_samples = metrics.latency_samples.load(Ordering::Relaxed) as f64;
-    let lat_sum = metrics.latency_sum_ns.load(Ordering::Relaxed) as f64;
+    if total_ops == 0.0 {
+        if let Some(e) = worker_error {
+            return Err(e);
+        }
+    }
 
-    let throughput_mbps = total_bytes / elapsed / (1024.0 * 1024.0);
-    let iops = total_ops / elapsed;
-    let avg_lat_us = if lat_samples > 0.0 {
-        lat_sum / lat_samples / 1_000.0
+    // Flush the devices' volatile write caches and time how long that takes
+    let drain_secs = if config.drain_flush && config.has_writes() {
+        Some(drain_devices(&config.device_paths))
     } else {
-        0.0
+        None
     };
+
+    let throughput_mbps = total_bytes / elapsed / (1024.0 * 1024.0);
+    let iops = total_ops / elapsed;
+    let avg_lat_us = metrics.avg_latency_us();
     let p50_us = metrics.percentile(50.0);
     let p99_us = metrics.percentile(99.0);
+    let p999_us = metrics.percentile(99.9);
+    let p9999_us = metrics.percentile(99.99);
+    let (min_us, max_us) = metrics.latency_range_us();
+    let stddev_us = metrics.latency_stddev_us();
+    let time_to_steady_secs = time_to_steady(&windows);
+
+    // Mixed workloads: split throughput and latency by direction
+    let split = config.rw_mix.map(|_| {
+        let write_ops = metrics.write_ops.load(Ordering::Relaxed) as f64;
+        let write_bytes = metrics.write_bytes.load(Ordering::Relaxed) as f64;
+        let [read_avg, read_p50, read_p99, read_p999, read_max] = metrics.read_latency.stats_us();
+        let [write_avg, write_p50, write_p99, write_p999, write_max] = metrics.write_latency.stats_us();
+        DirectionSplit {
+            read_mbps: (total_bytes - write_bytes) / elapsed / (1024.0 * 1024.0),
+            read_iops: (total_ops - write_ops) / elapsed,
+            write_mbps: write_bytes / elapsed / (1024.0 * 1024.0),
+            write_iops: write_ops / elapsed,
+            read_latency_avg_us: read_avg,
+            read_latency_p50_us: read_p50,
+            read_latency_p99_us: read_p99,
+            read_latency_p999_us: read_p999,
+            read_latency_max_us: read_max,
+            write_latency_avg_us: write_avg,
+            write_latency_p50_us: write_p50,
+            write_latency_p99_us: write_p99,
+            write_latency_p999_us: write_p999,
+            write_latency_max_us: write_max,
+        }
+    });
 
-    println!(
-        "  RESULT: {:.2} MB/s | {:.0} IOPS | avg {:.1} us | p50 {:.1} us | p99 {:.1} us",
-        throughput_mbps, iops, avg_lat_us, p50_us, p99_us
+    let inflight_samples = metrics.inflight_samples.load(Ordering::Relaxed) as f64;
+    let (avg_inflight_reads, avg_inflight_writes) = if config.rw_mix.is_some() && inflight_samples > 0.0 {
+        (
+            Some(metrics.inflight_read_sum.load(Ordering::Relaxed) as f64 / inflight_samples),
+            Some(metrics.inflight_write_sum.load(Ordering::Relaxed) as f64 / inflight_samples),
+        )
+    } else {
+        (None, None)
+    };
+    let achieved_queue_depth = (inflight_samples > 0.0).then(|| {
+        (metrics.inflight_read_sum.load(Ordering::Relaxed) + metrics.inflight_write_sum.load(Ordering::Relaxed)) as f64
+            / inflight_samples
+    });
+
+    let fsync = (config.fsync_every > 0 && config.has_writes()).then(|| {
+        let count = metrics.flush_count.load(Ordering::Relaxed);
+        FsyncSummary {
+            every: config.fsync_every,
+            count,
+            latency_avg_us: if count > 0 {
+                metrics.flush_sum_ns.load(Ordering::Relaxed) as f64 / count as f64 / 1_000.0
+            } else {
+                0.0
+            },
+            latency_max_us: metrics.flush_max_ns.load(Ordering::Relaxed) as f64 / 1_000.0,
+        }
+    });
+
+    let thread_spread = metrics.thread_spread();
+
+    // Only read passes check data; a verifying write test just stamps it
+    let verify = (config.verify && !config.has_writes()).then(|| VerifySummary {
+        checked: metrics.verify_checked.load(Ordering::Relaxed),
+        unwritten: metrics.verify_unwritten.load(Ordering::Relaxed),
+        corrupt: metrics.verify_corrupt.load(Ordering::Relaxed),
+    });
+
+    info!(
+        "  RESULT: {} {} | {} IOPS | avg {} us | p50 {} us | p99 {} us",
+        nf.throughput(throughput_mbps, 2),
+        nf.throughput_unit(),
+        nf.count(iops),
+        nf.fixed(avg_lat_us, 1),
+        nf.fixed(p50_us, 1),
+        nf.fixed(p99_us, 1)
     );
+    if let Some(t) = time_to_steady_secs {
+        info!("  Time to steady state: {:.0}s", t);
+    }
+
+    // Per-device breakdown only adds information when several devices ran together
+    let per_device: Vec<DeviceResult> = if device_metrics.len() > 1 {
+        config
+            .device_paths
+            .iter()
+            .zip(&device_metrics)
+            .map(|(path, m)| device_result(path, m, elapsed))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for d in &per_device {
+        info!(
+            "    {}: {} {} | {} IOPS | avg {} us | p99 {} us",
+            d.path,
+            nf.throughput(d.throughput_mbps, 2),
+            nf.throughput_unit(),
+            nf.count(d.iops),
+            nf.fixed(d.latency_avg_us, 1),
+            nf.fixed(d.latency_p99_us, 1)
+        );
+    }
+    if let Some(d) = &split {
+        info!(
+            "  Reads: {} {} | {} IOPS    Writes: {} {} | {} IOPS",
+            nf.throughput(d.read_mbps, 2),
+            nf.throughput_unit(),
+            nf.count(d.read_iops),
+            nf.throughput(d.write_mbps, 2),
+            nf.throughput_unit(),
+            nf.count(d.write_iops)
+        );
+    }
+    if let (Some(reads), Some(writes)) = (avg_inflight_reads, avg_inflight_writes) {
+        info!("  In flight: {:.1} reads / {:.1} writes per thread", reads, writes);
+    }
+    if let Some(t) = &thread_spread {
+        info!("  Per-thread p99: {}", t.describe(nf));
+    }
+    if let Some(v) = &verify {
+        info!("  Verify: {}", v.describe(nf));
+    }
+    if let Some(f) = &fsync {
+        info!(
+            "  Fsync every {} writes: {} flushes | avg {} us | max {} us",
+            f.every,
+            nf.count(f.count as f64),
+            nf.fixed(f.latency_avg_us, 1),
+            nf.fixed(f.latency_max_us, 1)
+        );
+    }
+    let dropped_submissions = metrics.dropped_submissions.load(Ordering::Relaxed);
+    if dropped_submissions > 0 {
+        warn!(
+            "  Warning: {} submissions dropped (io_uring queue full); the effective queue depth shrank",
+            dropped_submissions
+        );
+    }
+    let io_errors = metrics.io_errors.load(Ordering::Relaxed);
+    let first_io_error = metrics.first_io_error().map(|e| e.to_string());
+    if io_errors > 0 {
+        warn!(
+            "  Warning: {} I/Os failed or came back short (first error: {})",
+            io_errors,
+            first_io_error.as_deref().unwrap_or("short transfer")
+        );
+    }
+    // Paced runs measure latency at a known load; a shortfall means the queue
+    // was saturated after all
+    if let Some(target) = config.target_iops {
+        if iops < target as f64 * 0.95 && !matches!(stop_reason, StopReason::Interrupted | StopReason::MaxRuntime) {
+            warn!(
+                "  Warning: reached {} of the {} IOPS target; raise the threads or queue depth, or the device can't sustain it",
+                nf.count(iops),
+                nf.count(target as f64)
+            );
+        }
+    }
+    if let Some(d) = drain_secs {
+        info!(
+            "  Drain time: {:.2}s (durable throughput {} {})",
+            d,
+            nf.throughput(total_bytes / (elapsed + d) / (1024.0 * 1024.0), 2),
+            nf.throughput_unit()
+        );
+    }
 
-    Ok(TestResult {
+    let result = TestResult {
         throughput_mbps,
         iops,
         latency_avg_us: avg_lat_us,
         latency_p50_us: p50_us,
         latency_p99_us: p99_us,
+        latency_p999_us: p999_us,
+        latency_p9999_us: p9999_us,
+        latency_min_us: min_us,
+        latency_max_us: max_us,
+        latency_stddev_us: stddev_us,
+        total_bytes: total_bytes as u64,
         threads: config.threads,
         queue_depth: config.queue_depth,
         block_size_kb: (config.io_size / 1024) as u32,
+        block_size_mix: config.bs_mix.clone(),
+        access_pattern: config.access_pattern,
+        distribution: config.distribution().map(|(dist, _)| dist),
+        skew: config.distribution().map(|(_, skew)| skew),
+        write_pattern: (config.has_writes() && !config.verify).then_some(config.write_pattern),
+        buffered: config.open_flags.buffered,
+        write_through: config.has_writes()
+            && (cfg!(windows) || (config.open_flags.write_through && cfg!(target_os = "linux"))),
+        io_poll: config.io_poll && cfg!(target_os = "linux"),
+        sq_poll: config.sq_poll && cfg!(target_os = "linux"),
+        fixed_buffers: config.fixed_buffers && cfg!(target_os = "linux"),
+        dropped_submissions,
+        io_errors,
+        first_io_error,
         duration_secs: config.duration_secs,
-    })
+        target_iops: config.target_iops,
+        stop_reason,
+        elapsed_secs: elapsed,
+        warmup_secs: config.warmup_secs,
+        numa_node: config.numa_node,
+        numa_local: config.numa_local && cfg!(target_os = "linux"),
+        pinned_cores: (!core_affinity.is_empty()).then_some(core_affinity.len()),
+        time_to_steady_secs,
+        read_pct: config.rw_mix,
+        split,
+        avg_inflight_reads,
+        avg_inflight_writes,
+        achieved_queue_depth,
+        drain_secs,
+        verify,
+        fsync,
+        thread_spread,
+        timeline: windows,
+        latency_distribution: metrics.latency_distribution(),
+        per_device,
+        repeat: None,
+    };
+    if let Some(qd) = result.achieved_queue_depth {
+        info!(
+            "  Achieved QD: {:.1} of {} per thread{}",
+            qd,
+            config.queue_depth,
+            if result.queue_depth_starved() { " (the device is not the bottleneck: CPU or submission rate is)" } else { "" }
+        );
+    }
+    if let Some(stream) = &config.stream {
+        if let Ok(record) = serde_json::to_value(&result) {
+            stream.send("result", &test_type, record);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Throughput and latency for one device of a multi-device test
+fn device_result(path: &str, metrics: &Metrics, elapsed: f64) -> DeviceResult {
+    let total_bytes = metrics.total_bytes.load(Ordering::Relaxed);
+    let (min_us, max_us) = metrics.latency_range_us();
+    DeviceResult {
+        path: path.to_string(),
+        throughput_mbps: total_bytes as f64 / elapsed / (1024.0 * 1024.0),
+        iops: metrics.total_ops.load(Ordering::Relaxed) as f64 / elapsed,
+        latency_avg_us: metrics.avg_latency_us(),
+        latency_p50_us: metrics.percentile(50.0),
+        latency_p99_us: metrics.percentile(99.0),
+        latency_p999_us: metrics.percentile(99.9),
+        latency_p9999_us: metrics.percentile(99.99),
+        latency_min_us: min_us,
+        latency_max_us: max_us,
+        total_bytes,
+    }
+}
+
+/// Flush every device's write cache in parallel and return the wall time
+/// until all flushes completed. Failures are reported but still timed.
+fn drain_devices(paths: &[String]) -> f64 {
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        for path in paths {
+            scope.spawn(move || {
+                if let Err(e) = sync_device(path) {
+                    warn!("  Drain flush failed for {}: {}", path, e);
+                }
+            });
+        }
+    });
+    start.elapsed().as_secs_f64()
+}
+
+/// Time at which windowed IOPS first reached 90% of the steady-state rate,
+/// where steady state is the mean of the second half of the windows
+fn time_to_steady(windows: &[TimelinePoint]) -> Option<f64> {
+    if windows.len() < 2 {
+        return None;
+    }
+    let tail = &windows[windows.len() / 2..];
+    let steady = tail.iter().map(|w| w.iops).sum::<f64>() / tail.len() as f64;
+    if steady <= 0.0 {
+        return None;
+    }
+    windows
+        .iter()
+        .find(|w| w.iops >= steady * 0.9)
+        .map(|w| w.elapsed_secs)
+}
+
+/// Stop a long preparation step on Ctrl-C, or once the `--max-runtime`
+/// `deadline` has passed
+fn check_prep_stop(deadline: Option<Instant>) -> io::Result<()> {
+    if interrupted() {
+        info!();
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+    }
+    if deadline.is_some_and(|at| Instant::now() >= at) {
+        info!();
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "max runtime reached"));
+    }
+    Ok(())
 }
 
-/// Create a file device of the specified size
-pub fn create_file_device(path: &str, size_gb: u64) -> io::Result<()> {
+/// RNG for the data `--create-file`, `--prep` and `--fill-percent` write on
+/// device number `device`. With `seed` set the stream is derived from it, so
+/// a `--seed` run writes the same bytes again; otherwise it seeds from the OS.
+pub fn prep_rng(seed: Option<u64>, device: usize) -> StdRng {
+    // Kept apart from the worker streams, which mix the thread id the same way
+    const PREP_STREAM: u64 = 0x7072_6570_6461_7461;
+    match seed {
+        Some(seed) => {
+            StdRng::seed_from_u64(seed ^ PREP_STREAM ^ (device as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        }
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Create a file device of the specified size, written out in full or
+/// only allocated
+pub fn create_file_device(
+    path: &str,
+    size_gb: u64,
+    pattern: WritePattern,
+    rng: &mut StdRng,
+    alloc: FileAlloc,
+    deadline: Option<Instant>,
+) -> io::Result<()> {
     use std::fs::OpenOptions;
     use std::io::Write;
 
     let size_bytes = size_gb * 1024 * 1024 * 1024;
-    println!("Creating file device: {} ({} GB)", path, size_gb);
+    info!("Creating file device: {} ({} GiB)", path, size_gb);
 
     let mut file = OpenOptions::new()
         .write(true)
@@ -233,88 +2093,280 @@ pub fn create_file_device(path: &str, size_gb: u64) -> io::Result<()> {
         .truncate(true)
         .open(path)?;
 
+    if alloc == FileAlloc::Preallocate {
+        if !preallocate(&file, size_bytes)? {
+            info!("  Note: the space is allocated but unwritten; until written it reads back as zeros from the filesystem, not the device (--prep writes it)");
+        }
+        info!("  Preallocated - Done!");
+        return Ok(());
+    }
+
     let chunk_size: usize = 1024 * 1024; // 1 MB chunks
     let mut buf = vec![0u8; chunk_size];
-    // Fill with random data
-    for chunk in buf.chunks_mut(8) {
-        let val = rand::random::<u64>();
-        let bytes = val.to_le_bytes();
-        let len = chunk.len().min(8);
-        chunk[..len].copy_from_slice(&bytes[..len]);
-    }
+    pattern.fill(&mut buf, rng);
 
     let total_chunks = size_bytes / chunk_size as u64;
     for i in 0..total_chunks {
+        check_prep_stop(deadline)?;
+        // Fresh random data for every chunk, or dedup would collapse the file
+        if i > 0 && pattern == WritePattern::Random {
+            pattern.fill(&mut buf, rng);
+        }
         file.write_all(&buf)?;
         if i % 1024 == 0 {
             let pct = (i as f64 / total_chunks as f64) * 100.0;
-            print!("\r  Progress: {:.1}%", pct);
+            progress!("\r  Progress: {:.1}%", pct);
         }
     }
 
     // Write remaining bytes
     let remainder = (size_bytes % chunk_size as u64) as usize;
     if remainder > 0 {
+        if pattern == WritePattern::Random {
+            pattern.fill(&mut buf, rng);
+        }
         file.write_all(&buf[..remainder])?;
     }
 
-    println!("\r  Progress: 100.0% - Done!");
+    info!("\r  Progress: 100.0% - Done!");
     file.flush()?;
     Ok(())
 }
 
-/// Prep device by writing random data
-pub fn prep_device(path: &str) -> io::Result<()> {
-    let size = get_device_size(path)?;
-    println!(
-        "Preparing device: {} ({:.2} GB)",
-        path,
-        size as f64 / (1024.0 * 1024.0 * 1024.0)
-    );
+/// Bytes per write of `--prep` and `--fill-percent` (4MB for better throughput)
+const PREP_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// Prep device by writing the whole of it with `pattern`
+pub fn prep_device(
+    path: &str,
+    size_override: Option<u64>,
+    flags: OpenFlags,
+    pattern: WritePattern,
+    rng: &mut StdRng,
+    deadline: Option<Instant>,
+    nf: NumberFormat,
+) -> io::Result<()> {
+    let size = device_size(path, size_override)?;
+    info!("Preparing device: {} ({} {})", path, nf.size(size as f64, 2), nf.size_unit());
+    write_sequential(path, flags, 0, size, pattern, rng, deadline, nf)
+}
 
-    let file = open_device_write(path)?;
+/// Bytes `--fill-percent` writes at the start of a `window`-byte test window,
+/// in whole prep chunks. The tests then stay inside them.
+fn fill_len(window: u64, percent: u8) -> u64 {
+    (window as u128 * percent as u128 / 100) as u64 / PREP_CHUNK * PREP_CHUNK
+}
 
-    let chunk_size: u64 = 4 * 1024 * 1024; // 4MB for better throughput
-    let aligned_buf = alloc_aligned(chunk_size as usize, 4096);
-    // Fill with random data
-    for chunk in unsafe {
-        std::slice::from_raw_parts_mut(aligned_buf.ptr, aligned_buf.len)
-    }
-    .chunks_mut(8)
-    {
-        let val = rand::random::<u64>();
-        let bytes = val.to_le_bytes();
-        let len = chunk.len().min(8);
-        chunk[..len].copy_from_slice(&bytes[..len]);
+/// The part of the test window (the whole device unless a range was given)
+/// that `--fill-percent` writes before the tests, as (offset, length)
+pub fn fill_region(
+    path: &str,
+    range_start: u64,
+    range_len: Option<u64>,
+    size_override: Option<u64>,
+    percent: u8,
+) -> io::Result<(u64, u64)> {
+    let size = device_size(path, size_override)?;
+    let available = size.saturating_sub(range_start);
+    let window = range_len.map_or(available, |len| len.min(available));
+    let len = fill_len(window, percent);
+    if len == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}% of the test range on {} is smaller than one {} MB fill chunk", percent, path, PREP_CHUNK >> 20),
+        ));
     }
+    Ok((range_start, len))
+}
 
-    let total_chunks = size / chunk_size;
+/// Write `len` bytes of `pattern` from `offset` in whole chunks, with progress
+#[allow(clippy::too_many_arguments)]
+pub fn write_sequential(
+    path: &str,
+    flags: OpenFlags,
+    offset: u64,
+    len: u64,
+    pattern: WritePattern,
+    rng: &mut StdRng,
+    deadline: Option<Instant>,
+    nf: NumberFormat,
+) -> io::Result<()> {
+    let file = open_device_write(path, flags)?;
+
+    let chunk_size = PREP_CHUNK;
+    let mut aligned_buf = alloc_aligned(chunk_size as usize, buffer_alignment(path));
+    pattern.fill(aligned_buf.as_mut_slice(), rng);
+
+    let total_chunks = len / chunk_size;
     let start = Instant::now();
 
-    print!("  Progress:   0.0%");
-    let _ = std::io::stdout().flush();
+    progress!("  Progress:   0.0%");
 
     for i in 0..total_chunks {
-        let offset = i * chunk_size;
-        write_at_raw(&file, &aligned_buf, offset)?;
+        check_prep_stop(deadline)?;
+        // Fresh random data for every chunk, or dedup would collapse the device
+        if i > 0 && pattern == WritePattern::Random {
+            pattern.fill(aligned_buf.as_mut_slice(), rng);
+        }
+        write_at_raw(&file, &aligned_buf, offset + i * chunk_size)?;
         // Report every 256MB (64 x 4MB chunks)
         if i % 64 == 0 {
             let pct = (i as f64 / total_chunks as f64) * 100.0;
             let elapsed = start.elapsed().as_secs_f64();
             let written_mb = (i * chunk_size) as f64 / (1024.0 * 1024.0);
             let mbps = if elapsed > 0.0 { written_mb / elapsed } else { 0.0 };
-            print!("\r  Progress: {:>5.1}%  ({:.0} MB/s)", pct, mbps);
-            let _ = std::io::stdout().flush();
+            progress!("\r  Progress: {:>5.1}%  ({} {})", pct, nf.throughput(mbps, 0), nf.throughput_unit());
         }
     }
 
     let elapsed = start.elapsed().as_secs_f64();
-    let total_mb = size as f64 / (1024.0 * 1024.0);
+    let total_mb = (total_chunks * chunk_size) as f64 / (1024.0 * 1024.0);
     let mbps = if elapsed > 0.0 { total_mb / elapsed } else { 0.0 };
-    println!("\r  Progress: 100.0%  ({:.0} MB/s avg) - Done!    ", mbps);
+    info!(
+        "\r  Progress: 100.0%  ({} {} avg) - Done!    ",
+        nf.throughput(mbps, 0),
+        nf.throughput_unit()
+    );
     Ok(())
 }
 
+/// Discard every block of the test window (the whole device unless a range
+/// was given) so an SSD starts the tests from its erased state. Returns the
+/// seconds it took.
+pub fn trim_device(
+    path: &str,
+    range_start: u64,
+    range_len: Option<u64>,
+    size_override: Option<u64>,
+    deadline: Option<Instant>,
+    nf: NumberFormat,
+) -> io::Result<f64> {
+    let size = device_size(path, size_override)?;
+    let end = match range_len {
+        Some(len) => range_start.saturating_add(len).min(size),
+        None => size,
+    };
+    // Discards cover whole logical blocks
+    let align = get_sector_size(path).map_or(4096, |s| s.logical as u64);
+    let first = range_start.div_ceil(align) * align;
+    let last = end / align * align;
+    if last <= first {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("nothing to trim on {}: the range holds no whole {}-byte block", path, align),
+        ));
+    }
+    info!("Trimming device: {} ({} {})", path, nf.size((last - first) as f64, 2), nf.size_unit());
+
+    // 1 GiB per request, so Ctrl-C and the progress line get a look in
+    let chunk: u64 = 1 << 30;
+    let start = Instant::now();
+    let mut offset = first;
+    while offset < last {
+        check_prep_stop(deadline)?;
+        let len = chunk.min(last - offset);
+        discard_range(path, offset, len)
+            .map_err(|e| io::Error::new(e.kind(), format!("discard failed: {}", e)))?;
+        offset += len;
+        progress!("\r  Progress: {:>5.1}%", (offset - first) as f64 / (last - first) as f64 * 100.0);
+    }
+
+    let secs = start.elapsed().as_secs_f64();
+    info!("\r  Progress: 100.0%  ({:.2}s) - Done!    ", secs);
+    Ok(secs)
+}
+
+/// Pre-flight check of a device: open it with the real direct I/O flags and
+/// read a single aligned block from offset 0. Returns the device size.
+/// With `check_write`, also confirms the device can be opened for writing (no data is written).
+pub fn check_device(
+    path: &str,
+    size_override: Option<u64>,
+    flags: OpenFlags,
+    io_size: u64,
+    check_write: bool,
+) -> io::Result<u64> {
+    let size = device_size(path, size_override)?;
+    if size < io_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("size {} bytes is smaller than one {}KB block", size, io_size / 1024),
+        ));
+    }
+
+    if !flags.buffered {
+        check_alignment(path, io_size, 0)?;
+    }
+
+    let dev = open_device_read(path, flags)?;
+    let buf = alloc_aligned(io_size as usize, buffer_alignment(path));
+    let read = read_at_raw(&dev, &buf, 0)?;
+    if (read as u64) < io_size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("short read: {} of {} bytes", read, io_size),
+        ));
+    }
+
+    if check_write {
+        open_device_write(path, flags)?;
+    }
+    Ok(size)
+}
+
+/// Collect the per-device settings recorded in the report
+pub fn device_details(path: &str) -> DeviceDetails {
+    #[allow(unused_mut)]
+    let mut details = DeviceDetails::new(path, classify_device(path));
+    details.sector_size = get_sector_size(path);
+    #[cfg(target_os = "linux")]
+    platform_linux::fill_sysfs_details(path, &mut details);
+    #[cfg(windows)]
+    platform_windows::fill_device_identity(path, &mut details);
+    details
+}
+
+/// Mount points of the filesystems mounted on `path` (every volume of a
+/// physical drive, or the volume itself). Only Windows checks; elsewhere the
+/// list is always empty.
+pub fn mounted_volumes(path: &str) -> io::Result<Vec<String>> {
+    #[cfg(windows)]
+    {
+        platform_windows::mounted_volumes(path)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        Ok(Vec::new())
+    }
+}
+
+/// A disk found by device enumeration
+#[derive(Debug, Clone)]
+pub struct DiskInfo {
+    pub path: String,
+    pub size_bytes: u64,
+    pub model: Option<String>,
+}
+
+/// Enumerate the block devices / physical drives on this machine
+pub fn list_devices() -> Vec<DiskInfo> {
+    #[cfg(any(windows, target_os = "linux", target_os = "macos"))]
+    {
+        #[cfg(windows)]
+        use platform_windows as platform;
+        #[cfg(target_os = "linux")]
+        use platform_linux as platform;
+        #[cfg(target_os = "macos")]
+        use platform_macos as platform;
+        platform::list_devices()
+    }
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+    {
+        Vec::new()
+    }
+}
+
 /// Aligned buffer for direct I/O
 pub struct AlignedBuf {
     pub ptr: *mut u8,
@@ -341,6 +2393,97 @@ impl AlignedBuf {
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
         unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
     }
+
+    /// Use only the first `len` bytes for I/O (`--bs-mix`), at most the
+    /// size the buffer was allocated with
+    #[cfg_attr(any(windows, target_os = "linux"), allow(dead_code))]
+    pub fn set_io_len(&mut self, len: usize) {
+        assert!(len <= self.layout.size());
+        self.len = len;
+    }
+}
+
+/// A worker's I/O buffers, indexed by slot. Normally every slot owns one; with
+/// `--shared-write-buffer` a write-only test issues all slots from a single
+/// buffer, since the bytes written don't matter to the measurement.
+pub struct SlotBuffers {
+    bufs: Vec<AlignedBuf>,
+    shared: bool,
+}
+
+impl SlotBuffers {
+    /// Allocate worker `thread_id`'s buffers for `slots` slots, calling `prepare`
+    /// on each new buffer (e.g. to bind it to a NUMA node) before it is first touched
+    pub fn new(
+        config: &TestConfig,
+        thread_id: u32,
+        slots: usize,
+        align: usize,
+        mut prepare: impl FnMut(&AlignedBuf),
+    ) -> Self {
+        let shared = config.shares_write_buffer();
+        let count = if shared { 1 } else { slots };
+        let mut rng = config.fill_rng(thread_id);
+        let bufs = (0..count)
+            .map(|_| {
+                let mut buf = alloc_aligned(config.io_size as usize, align);
+                prepare(&buf);
+                // Verifying writes stamp each buffer just before it is issued instead
+                if config.has_writes() && !config.verify {
+                    config.write_pattern.fill(buf.as_mut_slice(), &mut rng);
+                }
+                buf
+            })
+            .collect();
+        Self { bufs, shared }
+    }
+
+    /// The distinct buffers, in allocation order (for io_uring registration)
+    #[cfg(target_os = "linux")]
+    pub fn all(&self) -> &[AlignedBuf] {
+        &self.bufs
+    }
+
+    /// One owned buffer per slot, for a worker that issues each slot from its
+    /// own thread. A shared write buffer is copied so every slot has one.
+    #[cfg(not(any(windows, target_os = "linux")))]
+    pub fn into_slots(self, slots: usize) -> Vec<AlignedBuf> {
+        if !self.shared {
+            return self.bufs;
+        }
+        let src = &self.bufs[0];
+        (0..slots)
+            .map(|_| {
+                let mut buf = alloc_aligned(src.len, src.layout.align());
+                buf.as_mut_slice().copy_from_slice(src.as_slice());
+                buf
+            })
+            .collect()
+    }
+
+    /// Position in `all()` of the buffer `slot` uses
+    pub fn index_of(&self, slot: usize) -> usize {
+        if self.shared {
+            0
+        } else {
+            slot
+        }
+    }
+}
+
+impl std::ops::Index<usize> for SlotBuffers {
+    type Output = AlignedBuf;
+
+    fn index(&self, slot: usize) -> &AlignedBuf {
+        &self.bufs[self.index_of(slot)]
+    }
+}
+
+impl std::ops::IndexMut<usize> for SlotBuffers {
+    fn index_mut(&mut self, slot: usize) -> &mut AlignedBuf {
+        let idx = self.index_of(slot);
+        &mut self.bufs[idx]
+    }
 }
 
 /// Allocate a buffer aligned to the specified alignment
@@ -353,10 +2496,273 @@ pub fn alloc_aligned(size: usize, align: usize) -> AlignedBuf {
     AlignedBuf { ptr, len: size, layout }
 }
 
-// Platform-specific functions - implemented in platform_windows.rs / platform_linux.rs
+// Platform-specific functions - implemented in platform_windows.rs / platform_linux.rs / platform_macos.rs
 
 #[cfg(windows)]
-pub use platform_windows::{get_device_size, open_device_write, DeviceHandle, write_at_raw, normalize_device_path};
+pub use platform_windows::{
+    classify_device, discard_range, flush_caches, get_device_size, normalize_device_path, open_device_read, open_device_write, preallocate, read_at_raw,
+    sync_device, write_at_raw, get_sector_size, install_interrupt_handler, CpuAffinity,
+};
 
 #[cfg(target_os = "linux")]
-pub use platform_linux::{get_device_size, open_device_read, open_device_write, DeviceHandle, read_at_raw, write_at_raw};
+pub use platform_linux::{
+    classify_device, discard_range, flush_caches, get_device_size, open_device_read, open_device_write, preallocate, read_at_raw, sync_device,
+    write_at_raw, get_sector_size, install_interrupt_handler, CpuAffinity,
+};
+
+#[cfg(target_os = "macos")]
+pub use platform_macos::{
+    classify_device, discard_range, flush_caches, flush_handle, get_device_size, open_device_read, open_device_write, preallocate, read_at_raw, sync_device,
+    write_at_raw, get_sector_size, install_interrupt_handler, CpuAffinity, DeviceHandle,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::BenchmarkConfig;
+
+    /// A scratch file of `len` bytes in the temp directory, removed on drop
+    struct TempFile(String);
+
+    impl TempFile {
+        fn new(name: &str, len: u64) -> Self {
+            let path = std::env::temp_dir().join(format!("4c-test-{}-{}", std::process::id(), name));
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_len(len).unwrap();
+            TempFile(path.to_string_lossy().into_owned())
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// A one-second read test of `path` as the CLI defaults would set it up
+    fn read_config(path: &str, block_size_kb: u32) -> TestConfig {
+        let args = BenchmarkConfig::default();
+        let mut config = crate::suite::test_config(&args, &[path.to_string()], block_size_kb, 1, 1, false);
+        config.duration_secs = 1;
+        config
+    }
+
+    #[test]
+    fn run_test_rejects_a_device_smaller_than_one_block() {
+        let file = TempFile::new("small-run", 4096);
+        let err = run_test(&read_config(&file.0, 128)).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains(&file.0), "error does not name the device: {}", message);
+        assert!(message.contains("smaller than one 128KB block"), "{}", message);
+    }
+
+    #[test]
+    fn buffered_io_takes_sizes_that_are_not_sector_multiples() {
+        let file = TempFile::new("buffered", 1 << 20);
+        for is_write in [false, true] {
+            let mut config = read_config(&file.0, 4);
+            config.io_size = 1000;
+            config.open_flags.buffered = true;
+            config.is_write = is_write;
+            config.ops_limit = Some(1000);
+            let result = run_test(&config).unwrap();
+            assert_eq!(result.io_errors, 0);
+            assert_eq!(result.stop_reason, StopReason::OpsLimit);
+            assert!(result.total_bytes > 0 && result.total_bytes.is_multiple_of(1000), "{}", result.total_bytes);
+        }
+    }
+
+    #[test]
+    fn check_device_rejects_a_device_smaller_than_one_block() {
+        let file = TempFile::new("small-check", 4096);
+        let err = check_device(&file.0, None, OpenFlags::default(), 128 * 1024, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("size 4096 bytes is smaller than one 128KB block"), "{}", err);
+    }
+
+    #[test]
+    fn mixed_ops_are_drawn_per_reissue() {
+        let mut config = read_config("unused", 4);
+        config.rw_mix = Some(70);
+        config.seed = Some(42);
+        let mut rng = config.worker_rng(0);
+        let mut inflight = InflightMix::default();
+
+        // 32 slots kept busy: each completion reissues its slot with a fresh draw
+        let mut slots: Vec<bool> = (0..32)
+            .map(|_| {
+                let write = config.next_is_write(&mut rng);
+                inflight.issue(write);
+                write
+            })
+            .collect();
+        let mut switched = vec![false; slots.len()];
+        let (ops, mut writes) = (100_000, 0);
+        for n in 0..ops {
+            let slot = n % slots.len();
+            inflight.complete(slots[slot]);
+            let write = config.next_is_write(&mut rng);
+            switched[slot] |= write != slots[slot];
+            slots[slot] = write;
+            inflight.issue(write);
+            inflight.sample();
+            writes += write as usize;
+        }
+
+        assert!(switched.iter().all(|&s| s), "a slot kept the same op kind throughout");
+        let write_fraction = writes as f64 / ops as f64;
+        assert!((write_fraction - 0.3).abs() < 0.01, "write fraction {}", write_fraction);
+
+        // The sampled in-flight mix tracks the ratio too
+        let metrics = Metrics::new(config.histogram_bits);
+        inflight.flush(&metrics);
+        let samples = metrics.inflight_samples.load(Ordering::Relaxed) as f64;
+        let avg_reads = metrics.inflight_read_sum.load(Ordering::Relaxed) as f64 / samples;
+        let avg_writes = metrics.inflight_write_sum.load(Ordering::Relaxed) as f64 / samples;
+        assert_eq!(avg_reads + avg_writes, slots.len() as f64);
+        assert!((avg_writes / slots.len() as f64 - 0.3).abs() < 0.02, "in-flight writes {}", avg_writes);
+
+        for write in slots {
+            inflight.complete(write);
+        }
+        assert_eq!(inflight.in_flight(), 0);
+    }
+
+    #[test]
+    fn unmixed_ops_follow_is_write() {
+        let mut config = read_config("unused", 4);
+        config.seed = Some(42);
+        let mut rng = config.worker_rng(0);
+        assert!((0..1000).all(|_| !config.next_is_write(&mut rng)));
+        config.is_write = true;
+        assert!((0..1000).all(|_| config.next_is_write(&mut rng)));
+        config.rw_mix = Some(100);
+        assert!((0..1000).all(|_| !config.next_is_write(&mut rng)));
+        config.rw_mix = Some(0);
+        assert!((0..1000).all(|_| config.next_is_write(&mut rng)));
+    }
+
+    /// Record `per_thread` latencies from each of `threads` workers at once,
+    /// each folding its sampler into the shared metrics as it stops, and
+    /// check the merged totals lost nothing
+    fn merge_samplers(threads: u32, per_thread: u64) {
+        let mut config = read_config("unused", 4);
+        config.rw_mix = Some(50);
+        config.per_thread_stats = true;
+        let metrics = Metrics::new(config.histogram_bits);
+
+        std::thread::scope(|scope| {
+            for thread_id in 0..threads {
+                let (config, metrics) = (&config, &metrics);
+                scope.spawn(move || {
+                    let mut sampler = LatencySampler::new(config, thread_id, metrics);
+                    for i in 0..per_thread {
+                        sampler.record(1_000 + thread_id as u64 * per_thread + i, i % 2 == 1);
+                        if i % 1024 == 0 {
+                            sampler.flush_average(metrics);
+                        }
+                    }
+                    sampler.finish(metrics);
+                });
+            }
+        });
+
+        let total = threads as u64 * per_thread;
+        let counted = |histogram: &histogram::LatencyHistogram| -> u64 {
+            histogram.log2_counts().iter().map(|&(_, n)| n).sum()
+        };
+        assert_eq!(metrics.latency_samples.load(Ordering::Relaxed), total);
+        assert_eq!(counted(&metrics.latency_histogram), total);
+        assert_eq!(metrics.latency_min_ns.load(Ordering::Relaxed), 1_000);
+        assert_eq!(metrics.latency_max_ns.load(Ordering::Relaxed), 1_000 + total - 1);
+        for direction in [&metrics.read_latency, &metrics.write_latency] {
+            assert_eq!(direction.samples.load(Ordering::Relaxed), total / 2);
+            assert_eq!(counted(&direction.histogram), total / 2);
+        }
+        assert_eq!(metrics.thread_p99_ns.lock().unwrap().len(), threads as usize);
+    }
+
+    #[test]
+    fn bs_mix_parses_sizes_and_shares() {
+        let mix: BlockSizeMix = "4k:70, 64K:20,1m:10".parse().unwrap();
+        assert_eq!(mix.sizes_kb().collect::<Vec<_>>(), vec![4, 64, 1024]);
+        assert_eq!(mix.largest_kb(), 1024);
+        assert_eq!(mix.to_string(), "4k:70,64k:20,1024k:10");
+        assert_eq!(mix.to_string().parse::<BlockSizeMix>().unwrap(), mix);
+    }
+
+    #[test]
+    fn bs_mix_rejects_bad_specs() {
+        let err = |spec: &str| spec.parse::<BlockSizeMix>().unwrap_err();
+        assert_eq!(err("4k:70,64k:20"), "percentages must add up to 100, got 90");
+        assert_eq!(err("4k:70,64k:40"), "percentages must add up to 100, got 110");
+        assert_eq!(err(""), "percentages must add up to 100, got 0");
+        assert_eq!(err("4k:50,4:50"), "block size 4 KB is listed twice");
+        assert_eq!(err("1m:50,1024k:50"), "block size 1024 KB is listed twice");
+        assert_eq!(err("0k:50,4k:50"), "invalid block size '0k' (KB, or with a k/m suffix)");
+        assert_eq!(err("4k:0,64k:100"), "percent for 4k must be 1-100, got '0'");
+        assert_eq!(err("4k"), "expected SIZE:PERCENT (e.g. 4k:70), got '4k'");
+    }
+
+    #[test]
+    fn bs_mix_draws_sizes_by_share() {
+        let mix: BlockSizeMix = "4k:70,64k:20,1m:10".parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(42);
+        let draws = 100_000;
+        let mut counts = [0usize; 3];
+        for _ in 0..draws {
+            match mix.pick(&mut rng) {
+                4096 => counts[0] += 1,
+                65536 => counts[1] += 1,
+                1048576 => counts[2] += 1,
+                other => panic!("drew a size not in the mix: {}", other),
+            }
+        }
+        for (count, share) in counts.iter().zip([0.7, 0.2, 0.1]) {
+            let fraction = *count as f64 / draws as f64;
+            assert!((fraction - share).abs() < 0.01, "fraction {} for share {}", fraction, share);
+        }
+
+        let single: BlockSizeMix = "8k:100".parse().unwrap();
+        assert!((0..1000).all(|_| single.pick(&mut rng) == 8192));
+    }
+
+    /// One-second windows with the given IOPS, MB/s unused
+    fn timeline(iops: &[f64]) -> Vec<TimelinePoint> {
+        iops.iter()
+            .enumerate()
+            .map(|(i, &iops)| TimelinePoint { elapsed_secs: (i + 1) as f64, mbps: 0.0, iops })
+            .collect()
+    }
+
+    #[test]
+    fn time_to_steady_finds_the_end_of_a_ramp() {
+        // Steady state is the mean of the last four windows: 1000
+        let ramp = timeline(&[100.0, 400.0, 700.0, 950.0, 1000.0, 1020.0, 980.0, 1000.0]);
+        assert_eq!(time_to_steady(&ramp), Some(4.0));
+    }
+
+    #[test]
+    fn time_to_steady_is_the_first_window_when_flat() {
+        let flat = timeline(&[500.0; 10]);
+        assert_eq!(time_to_steady(&flat), Some(1.0));
+    }
+
+    #[test]
+    fn time_to_steady_needs_two_windows_and_some_io() {
+        assert_eq!(time_to_steady(&[]), None);
+        assert_eq!(time_to_steady(&timeline(&[500.0])), None);
+        assert_eq!(time_to_steady(&timeline(&[0.0; 4])), None);
+    }
+
+    #[test]
+    fn samplers_from_many_threads_merge_every_sample() {
+        merge_samplers(8, 10_000);
+    }
+
+    #[test]
+    #[ignore = "stress test; run with --ignored"]
+    fn samplers_from_many_threads_merge_every_sample_under_load() {
+        merge_samplers(128, 1_000_000);
+    }
+}
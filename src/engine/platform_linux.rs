@@ -22,64 +22,454 @@ impl AsRawFd for DeviceHandle {
 }
 
 /// Open device for reading with O_DIRECT
-pub fn open_device_read(path: &str) -> io::Result<DeviceHandle> {
-    open_device(path, false)
+pub fn open_device_read(path: &str, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    open_device(path, false, flags)
 }
 
 /// Open device for writing with O_DIRECT
-pub fn open_device_write(path: &str) -> io::Result<DeviceHandle> {
-    open_device(path, true)
+pub fn open_device_write(path: &str, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    open_device(path, true, flags)
 }
 
-fn open_device(path: &str, write: bool) -> io::Result<DeviceHandle> {
+fn open_device(path: &str, write: bool, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    // O_DSYNC makes each direct write reach stable media (FUA, or a cache
+    // flush on drives without it), like FILE_FLAG_WRITE_THROUGH on Windows
+    let sync = if write && flags.write_through { libc::O_DSYNC } else { 0 };
+    if flags.buffered {
+        return open_fd(path, write, sync);
+    }
+    match open_fd(path, write, libc::O_DIRECT | sync) {
+        // Filesystems without direct I/O support (tmpfs, some network mounts)
+        // reject O_DIRECT with EINVAL. File targets always fall back; block
+        // devices only when explicitly requested.
+        Err(e)
+            if e.raw_os_error() == Some(libc::EINVAL)
+                && (flags.buffered_fallback || classify_device(path) == super::DeviceKind::File) =>
+        {
+            let dev = open_fd(path, write, sync)?;
+            super::warn_buffered(path);
+            Ok(dev)
+        }
+        result => result,
+    }
+}
+
+/// Allocate `len` bytes for a new file without writing them. The extents
+/// are marked unwritten, so until written they read back as zeros from the
+/// filesystem rather than the device. Returns whether reads reach the device.
+pub fn preallocate(file: &std::fs::File, len: u64) -> io::Result<bool> {
+    if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len as libc::off_t) } < 0 {
+        let err = io::Error::last_os_error();
+        return Err(io::Error::new(err.kind(), format!("fallocate failed: {}", err)));
+    }
+    Ok(false)
+}
+
+/// Write back dirty pages and drop the page cache for a device or file so
+/// a read test is not served from memory left behind by an earlier write test
+pub fn flush_caches(path: &str) -> io::Result<()> {
+    let dev = open_fd(path, false, 0)?;
+    unsafe { libc::sync() };
+    let rc = unsafe { libc::posix_fadvise(dev.fd, 0, 0, libc::POSIX_FADV_DONTNEED) };
+    if rc != 0 {
+        return Err(io::Error::from_raw_os_error(rc));
+    }
+    Ok(())
+}
+
+/// Flush buffered data and the device's volatile write cache to stable media.
+/// On a block device `fsync` makes the kernel issue a cache flush to the drive.
+pub fn sync_device(path: &str) -> io::Result<()> {
+    let dev = open_fd(path, true, 0)?;
+    flush_handle(&dev)
+}
+
+/// fsync an open device; used by `--fsync-every` inside the write workers
+pub fn flush_handle(dev: &DeviceHandle) -> io::Result<()> {
+    if unsafe { libc::fsync(dev.fd) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Classify a target by `stat`: regular file, block device, or other
+pub fn classify_device(path: &str) -> super::DeviceKind {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_file() => super::DeviceKind::File,
+        Ok(meta) if meta.file_type().is_block_device() => super::DeviceKind::BlockDevice,
+        _ => super::DeviceKind::Other,
+    }
+}
+
+fn open_fd(path: &str, write: bool, extra: libc::c_int) -> io::Result<DeviceHandle> {
     let c_path = std::ffi::CString::new(path).unwrap();
     let flags = if write {
-        libc::O_RDWR | libc::O_DIRECT
+        libc::O_RDWR | extra
     } else {
-        libc::O_RDONLY | libc::O_DIRECT
+        libc::O_RDONLY | extra
     };
 
     let fd = unsafe { libc::open(c_path.as_ptr(), flags) };
     if fd < 0 {
-        return Err(io::Error::last_os_error());
+        return Err(super::privilege_error(io::Error::last_os_error()));
     }
 
     Ok(DeviceHandle { fd })
 }
 
-/// Get device or file size
-pub fn get_device_size(path: &str) -> io::Result<u64> {
-    // Try as regular file first
-    if let Ok(meta) = std::fs::metadata(path) {
-        if meta.len() > 0 {
-            return Ok(meta.len());
-        }
+/// ioctl request argument type: c_ulong on glibc, c_int on musl
+#[cfg(target_env = "musl")]
+type IoctlRequest = libc::c_int;
+#[cfg(not(target_env = "musl"))]
+type IoctlRequest = libc::c_ulong;
+
+/// Encode a Linux `_IOR(ty, nr, size)` ioctl request number.
+///
+/// The `_IOC` layout differs between architectures: most use a 2-bit direction
+/// field at bit 30 with a 14-bit size, while powerpc, mips and sparc use a 3-bit
+/// direction field at bit 29 with a 13-bit size. `_IOC_READ` is 2 on all of them.
+const fn ior(ty: u32, nr: u32, size: usize) -> u32 {
+    #[cfg(any(
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "mips32r6",
+        target_arch = "mips64r6",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    ))]
+    const DIR_SHIFT: u32 = 29;
+    #[cfg(not(any(
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "mips32r6",
+        target_arch = "mips64r6",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    )))]
+    const DIR_SHIFT: u32 = 30;
+    const IOC_READ: u32 = 2;
+
+    (IOC_READ << DIR_SHIFT) | ((size as u32) << 16) | (ty << 8) | nr
+}
+
+/// BLKGETSIZE64 = _IOR(0x12, 114, size_t). The size field follows the width of
+/// size_t, so the value differs per architecture:
+///   x86_64, aarch64, riscv64, s390x   0x80081272
+///   x86, arm                          0x80041272
+///   powerpc64, mips64, sparc64        0x40081272
+///   powerpc, mips                     0x40041272
+const BLKGETSIZE64: u32 = ior(0x12, 114, std::mem::size_of::<usize>());
+
+// Pin the well-known values so a change to `ior` can't silently break them
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const _: () = assert!(BLKGETSIZE64 == 0x80081272);
+#[cfg(any(target_arch = "x86", target_arch = "arm"))]
+const _: () = assert!(BLKGETSIZE64 == 0x80041272);
+#[cfg(target_arch = "powerpc64")]
+const _: () = assert!(BLKGETSIZE64 == 0x40081272);
+
+/// BLKSSZGET = _IO(0x12, 104) / BLKPBSZGET = _IO(0x12, 123): logical and
+/// physical block size. They carry no size field, so they are the same on
+/// every architecture.
+const BLKSSZGET: u32 = (0x12 << 8) | 104;
+const BLKPBSZGET: u32 = (0x12 << 8) | 123;
+
+/// BLKDISCARD = _IO(0x12, 119): discard a byte range given as `[start, len]`
+const BLKDISCARD: u32 = (0x12 << 8) | 119;
+
+/// How long the `--sq-poll` kernel thread spins without work before it sleeps
+const SQPOLL_IDLE_MS: u32 = 2000;
+
+/// Logical and physical sector size of a block device; None for files or
+/// when the device can't be queried
+pub fn get_sector_size(path: &str) -> Option<super::SectorSize> {
+    if classify_device(path) != super::DeviceKind::BlockDevice {
+        return None;
     }
 
-    // Try as block device using ioctl
-    let c_path = std::ffi::CString::new(path).unwrap();
-    let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY) };
-    if fd < 0 {
+    let dev = open_fd(path, false, 0).ok()?;
+    let mut logical: libc::c_int = 0;
+    let mut physical: libc::c_uint = 0;
+    if unsafe { libc::ioctl(dev.fd, BLKSSZGET as IoctlRequest, &mut logical) } < 0 {
+        return None;
+    }
+    if unsafe { libc::ioctl(dev.fd, BLKPBSZGET as IoctlRequest, &mut physical) } < 0 {
+        physical = logical as u32;
+    }
+    Some(super::SectorSize {
+        logical: logical.max(1) as u32,
+        physical: physical.max(1),
+    })
+}
+
+/// Tell the device `len` bytes at `offset` no longer hold data: BLKDISCARD on
+/// a block device, a punched hole in a regular file
+pub fn discard_range(path: &str, offset: u64, len: u64) -> io::Result<()> {
+    let dev = open_fd(path, true, 0)?;
+    let result = if classify_device(path) == super::DeviceKind::BlockDevice {
+        let range: [u64; 2] = [offset, len];
+        unsafe { libc::ioctl(dev.fd, BLKDISCARD as IoctlRequest, range.as_ptr()) }
+    } else {
+        unsafe {
+            libc::fallocate(
+                dev.fd,
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                offset as libc::off_t,
+                len as libc::off_t,
+            )
+        }
+    };
+    if result < 0 {
         return Err(io::Error::last_os_error());
     }
+    Ok(())
+}
 
+/// Get device or file size
+pub fn get_device_size(path: &str) -> io::Result<u64> {
+    if classify_device(path) == super::DeviceKind::File {
+        return Ok(std::fs::metadata(path)?.len());
+    }
+
+    // Block device: ask the kernel via ioctl
+    let dev = open_fd(path, false, 0)?;
     let mut size: u64 = 0;
-    // BLKGETSIZE64 = 0x80081272 on x86_64
-    // Use c_ulong on glibc, c_int on musl for ioctl compatibility
-    #[cfg(target_env = "musl")]
-    const BLKGETSIZE64: libc::c_int = 0x80081272u32 as libc::c_int;
-    #[cfg(not(target_env = "musl"))]
-    const BLKGETSIZE64: libc::c_ulong = 0x80081272;
-    let result = unsafe { libc::ioctl(fd, BLKGETSIZE64, &mut size) };
-    unsafe { libc::close(fd) };
+    let result = unsafe { libc::ioctl(dev.fd, BLKGETSIZE64 as IoctlRequest, &mut size) };
 
     if result < 0 {
-        return Err(io::Error::last_os_error());
+        return Err(super::size_query_error("BLKGETSIZE64", io::Error::last_os_error()));
     }
 
     Ok(size)
 }
 
+/// Resolve the sysfs directory of the whole disk backing a block device path.
+/// Partitions resolve to their parent disk, since that is where `queue/` lives.
+fn sysfs_block_dir(path: &str) -> Option<std::path::PathBuf> {
+    if classify_device(path) != super::DeviceKind::BlockDevice {
+        return None;
+    }
+    let dev = std::fs::canonicalize(path).ok()?;
+    let name = dev.file_name()?;
+    let dir = std::fs::canonicalize(std::path::Path::new("/sys/class/block").join(name)).ok()?;
+    if dir.join("partition").exists() {
+        dir.parent().map(|p| p.to_path_buf())
+    } else {
+        Some(dir)
+    }
+}
+
+fn read_sysfs(path: std::path::PathBuf) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Fill in the drive identity and block-layer queue settings (scheduler,
+/// nr_requests, ...) for a device. Files and paths without a sysfs entry are
+/// left untouched.
+pub fn fill_sysfs_details(path: &str, details: &mut crate::report::DeviceDetails) {
+    let dir = match sysfs_block_dir(path) {
+        Some(dir) => dir,
+        None => return,
+    };
+    let queue = dir.join("queue");
+
+    // NVMe exposes firmware_rev and serial, SCSI/SATA rev and the serial in
+    // VPD page 0x80 (a 4-byte header, then ASCII), virtio-blk a bare serial
+    details.model = read_sysfs(dir.join("device/model")).filter(|m| !m.is_empty());
+    details.firmware = read_sysfs(dir.join("device/firmware_rev"))
+        .or_else(|| read_sysfs(dir.join("device/rev")))
+        .filter(|f| !f.is_empty());
+    details.serial = read_sysfs(dir.join("device/serial"))
+        .or_else(|| read_sysfs(dir.join("serial")))
+        .or_else(|| {
+            let page = std::fs::read(dir.join("device/vpd_pg80")).ok()?;
+            let serial = String::from_utf8_lossy(page.get(4..)?);
+            Some(serial.trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string())
+        })
+        .filter(|s| !s.is_empty());
+
+    // The active scheduler is the bracketed entry, e.g. "mq-deadline kyber [bfq] none"
+    details.scheduler = read_sysfs(queue.join("scheduler")).map(|s| {
+        match (s.find('['), s.find(']')) {
+            (Some(start), Some(end)) if start < end => s[start + 1..end].to_string(),
+            _ => s,
+        }
+    });
+    details.nr_requests = read_sysfs(queue.join("nr_requests")).and_then(|s| s.parse().ok());
+    details.read_ahead_kb = read_sysfs(queue.join("read_ahead_kb")).and_then(|s| s.parse().ok());
+    details.rotational = read_sysfs(queue.join("rotational")).map(|s| s == "1");
+}
+
+/// Enumerate whole-disk block devices from /sys/block, skipping empty and
+/// RAM-backed entries (ram*, zram*, loop devices without a backing file)
+pub fn list_devices() -> Vec<super::DiskInfo> {
+    let entries = match std::fs::read_dir("/sys/block") {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut disks: Vec<super::DiskInfo> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if name.starts_with("ram") || name.starts_with("zram") {
+                return None;
+            }
+            let dir = entry.path();
+            // sysfs reports size in 512-byte sectors regardless of the logical block size
+            let sectors: u64 = read_sysfs(dir.join("size"))?.parse().ok()?;
+            if sectors == 0 {
+                return None;
+            }
+            let model = read_sysfs(dir.join("device/model")).filter(|m| !m.is_empty());
+            Some(super::DiskInfo {
+                path: format!("/dev/{}", name),
+                size_bytes: sectors * 512,
+                model,
+            })
+        })
+        .collect();
+
+    disks.sort_by(|a, b| a.path.cmp(&b.path));
+    disks
+}
+
+/// First Ctrl-C: ask the run to wind down. The default action is restored so
+/// a second Ctrl-C kills the process as usual.
+extern "C" fn on_sigint(_: libc::c_int) {
+    super::INTERRUPTED.store(true, std::sync::atomic::Ordering::Relaxed);
+    unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+}
+
+/// Route SIGINT to `super::interrupted()` instead of killing the process
+pub fn install_interrupt_handler() {
+    let handler = on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe { libc::signal(libc::SIGINT, handler) };
+}
+
+/// Set of CPUs a worker thread can be restricted to
+#[derive(Clone, Copy)]
+pub struct CpuAffinity {
+    set: libc::cpu_set_t,
+}
+
+impl CpuAffinity {
+    /// All CPUs belonging to a NUMA node, from /sys/devices/system/node/nodeN/cpulist
+    pub fn for_node(node: u32) -> io::Result<Self> {
+        let path = format!("/sys/devices/system/node/node{}/cpulist", node);
+        let list = std::fs::read_to_string(&path).map_err(|e| {
+            io::Error::new(e.kind(), format!("NUMA node {} not found ({})", node, path))
+        })?;
+
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        let mut count = 0;
+        // cpulist format: "0-3,8-11"
+        for part in list.trim().split(',').filter(|p| !p.is_empty()) {
+            let (first, last) = match part.split_once('-') {
+                Some((a, b)) => (a.parse::<usize>(), b.parse::<usize>()),
+                None => (part.parse::<usize>(), part.parse::<usize>()),
+            };
+            let (first, last) = match (first, last) {
+                (Ok(a), Ok(b)) => (a, b),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unexpected cpulist '{}' in {}", list.trim(), path),
+                    ))
+                }
+            };
+            for cpu in first..=last {
+                unsafe { libc::CPU_SET(cpu, &mut set) };
+                count += 1;
+            }
+        }
+
+        if count == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("NUMA node {} has no CPUs", node),
+            ));
+        }
+        Ok(Self { set })
+    }
+
+    /// The CPUs the process may currently run on
+    pub fn current() -> io::Result<Self> {
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { set })
+    }
+
+    /// Split this set into one single-CPU set per member, in CPU order
+    pub fn cores(&self) -> Vec<Self> {
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &self.set) })
+            .map(|cpu| {
+                let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+                unsafe { libc::CPU_SET(cpu, &mut set) };
+                Self { set }
+            })
+            .collect()
+    }
+
+    /// Restrict the calling thread to this CPU set
+    pub fn pin_current_thread(&self) -> io::Result<()> {
+        let result = unsafe {
+            libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &self.set)
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// NUMA node of the CPU the calling thread is running on
+pub fn current_numa_node() -> Option<u32> {
+    let mut cpu: libc::c_uint = 0;
+    let mut node: libc::c_uint = 0;
+    let result = unsafe {
+        libc::syscall(libc::SYS_getcpu, &mut cpu, &mut node, std::ptr::null_mut::<libc::c_void>())
+    };
+    (result == 0).then_some(node)
+}
+
+/// Bind a buffer's pages to a NUMA node (best effort, must run before the buffer is touched)
+pub fn bind_buffer_to_node(buf: &super::AlignedBuf, node: u32) {
+    const MPOL_BIND: libc::c_long = 2;
+    const MPOL_MF_MOVE: libc::c_ulong = 2;
+
+    let mut nodemask = [0 as libc::c_ulong; 16];
+    let bits = libc::c_ulong::BITS as usize;
+    let node = node as usize;
+    if node >= nodemask.len() * bits {
+        return;
+    }
+    nodemask[node / bits] |= 1 << (node % bits);
+
+    // mbind needs a page-aligned address; unaligned buffers simply keep first-touch placement
+    unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            buf.ptr as *mut libc::c_void,
+            buf.len as libc::c_ulong,
+            MPOL_BIND,
+            nodemask.as_ptr(),
+            (nodemask.len() * bits) as libc::c_ulong,
+            MPOL_MF_MOVE,
+        );
+    }
+}
+
 /// Synchronous read at offset (for prep/simple operations)
 pub fn read_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) -> io::Result<u32> {
     let result = unsafe {
@@ -102,163 +492,406 @@ pub fn write_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) ->
     Ok(result as u32)
 }
 
+/// How SQEs name the device and buffer: a plain fd and pointer, or the
+/// registered file and buffer index (`--fixed-buffers`)
+#[derive(Clone, Copy)]
+enum RingTarget {
+    Fd(RawFd),
+    /// The device is registered file 0; each buffer its index in the registration
+    Fixed,
+}
+
+/// Build a read or write SQE for one slot
+fn rw_entry(
+    target: RingTarget,
+    buf: *mut u8,
+    buf_index: usize,
+    len: u32,
+    offset: u64,
+    write: bool,
+    slot: usize,
+) -> io_uring::squeue::Entry {
+    use io_uring::{opcode, types};
+
+    let entry = match (target, write) {
+        (RingTarget::Fd(fd), true) => opcode::Write::new(types::Fd(fd), buf, len).offset(offset).build(),
+        (RingTarget::Fd(fd), false) => opcode::Read::new(types::Fd(fd), buf, len).offset(offset).build(),
+        (RingTarget::Fixed, true) => opcode::WriteFixed::new(types::Fixed(0), buf, len, buf_index as u16)
+            .offset(offset)
+            .build(),
+        (RingTarget::Fixed, false) => opcode::ReadFixed::new(types::Fixed(0), buf, len, buf_index as u16)
+            .offset(offset)
+            .build(),
+    };
+    entry.user_data(slot as u64)
+}
+
+/// Queue an SQE. If the submission queue is full, submit what is queued (and
+/// with SQPOLL wait for the kernel thread to take it) before trying again.
+/// Returns false if there was still no room; that slot then stays idle.
+fn push_sqe(ring: &mut io_uring::IoUring, entry: &io_uring::squeue::Entry) -> io::Result<bool> {
+    for _ in 0..3 {
+        // Safety: the entry's buffer stays allocated until its completion is reaped
+        if unsafe { ring.submission().push(entry) }.is_ok() {
+            return Ok(true);
+        }
+        ring.submit()?;
+        if ring.params().is_setup_sqpoll() {
+            ring.submitter().squeue_wait()?;
+        }
+    }
+    Ok(false)
+}
+
+/// Register the slot buffers and the device fd with the ring, so the kernel
+/// maps them once instead of on every I/O
+fn register_fixed(ring: &io_uring::IoUring, fd: RawFd, buffers: &super::SlotBuffers) -> io::Result<()> {
+    let iovecs: Vec<libc::iovec> = buffers
+        .all()
+        .iter()
+        .map(|buf| libc::iovec {
+            iov_base: buf.ptr as *mut libc::c_void,
+            iov_len: buf.len,
+        })
+        .collect();
+    let submitter = ring.submitter();
+    // Safety: the buffers outlive the ring's use of them; the worker drains
+    // every in-flight I/O before `buffers` is dropped
+    unsafe { submitter.register_buffers(&iovecs) }
+        .and_then(|_| submitter.register_files(&[fd]))
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("registering fixed buffers failed (check RLIMIT_MEMLOCK): {}", e),
+            )
+        })
+}
+
+/// Wait for a completion, or until `deadline` if none arrives by then
+fn wait_until(ring: &io_uring::IoUring, deadline: std::time::Instant) -> io::Result<()> {
+    let timeout = io_uring::types::Timespec::from(deadline.saturating_duration_since(std::time::Instant::now()));
+    let args = io_uring::types::SubmitArgs::new().timespec(&timeout);
+    match ring.submitter().submit_with_args(1, &args) {
+        Err(e) if e.raw_os_error() != Some(libc::ETIME) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// user_data of the drain's cancel request, apart from every slot index
+const CANCEL_USER_DATA: u64 = u64::MAX;
+
+/// Reap the completions of the `outstanding` I/Os still on the ring, first
+/// asking the kernel to cancel them (IORING_ASYNC_CANCEL_ANY, 5.19+) so a
+/// slow device doesn't hold up shutdown. I/O already on the device runs to
+/// completion either way. IOPOLL rings take only reads and writes, so they
+/// skip the cancel.
+fn drain_ring(ring: &mut io_uring::IoUring, mut outstanding: usize, cancel: bool) -> io::Result<()> {
+    use io_uring::{opcode, types};
+
+    if outstanding == 0 {
+        return Ok(());
+    }
+    if cancel {
+        let entry = opcode::AsyncCancel2::new(types::CancelBuilder::any())
+            .build()
+            .user_data(CANCEL_USER_DATA);
+        // Best effort: older kernels fail the cancel, and a full queue can't
+        // take it; either way the I/Os still finish and are reaped below
+        let _ = push_sqe(ring, &entry);
+    }
+    while outstanding > 0 {
+        let waited = ring.submit_and_wait(1);
+        let reaped = ring.completion().filter(|cqe| cqe.user_data() != CANCEL_USER_DATA).count();
+        outstanding = outstanding.saturating_sub(reaped);
+        // A failed wait that still freed completions (a full completion queue
+        // reports EBUSY) makes progress; give up only when nothing comes back
+        match waited {
+            Err(e) if reaped == 0 && e.kind() != io::ErrorKind::Interrupted => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 /// io_uring-based async I/O worker for maximum IOPS
 pub fn worker_io_uring(
+    thread_id: u32,
     device_path: &str,
-    io_size: u64,
-    queue_depth: u32,
-    is_write: bool,
     test_range: u64,
+    config: &super::TestConfig,
     stop: &std::sync::atomic::AtomicBool,
     metrics: &super::Metrics,
 ) -> io::Result<()> {
-    use io_uring::{opcode, types, IoUring};
+    use io_uring::IoUring;
     use std::sync::atomic::Ordering;
 
-    let dev = if is_write {
-        open_device_write(device_path)?
+    let io_size = config.io_size;
+    let queue_depth = config.queue_depth;
+    let has_writes = config.has_writes();
+
+    let dev = if has_writes {
+        open_device_write(device_path, config.open_flags)?
     } else {
-        open_device_read(device_path)?
+        open_device_read(device_path, config.open_flags)?
     };
 
     let qd = queue_depth as usize;
-    let sector_size: usize = 4096;
-    let max_offset = test_range / io_size;
-
-    // Create io_uring instance
-    let mut ring = IoUring::new(queue_depth)?;
-
-    // Allocate aligned buffers per slot
-    let mut buffers: Vec<super::AlignedBuf> = Vec::with_capacity(qd);
-    for _ in 0..qd {
-        let mut buf = super::alloc_aligned(io_size as usize, sector_size);
-        if is_write {
-            for chunk in buf.as_mut_slice().chunks_mut(8) {
-                let val = rand::random::<u64>();
-                let bytes = val.to_le_bytes();
-                let len = chunk.len().min(8);
-                chunk[..len].copy_from_slice(&bytes[..len]);
-            }
-        }
-        buffers.push(buf);
+    let sector_size = super::buffer_alignment(device_path);
+    let mut rng = config.worker_rng(thread_id);
+
+    // Create io_uring instance. With IOPOLL, submit_and_wait below busy-polls
+    // the device for completions instead of sleeping until an interrupt.
+    let mut builder = IoUring::builder();
+    if config.io_poll {
+        builder.setup_iopoll();
     }
-
-    // Pre-generate random offsets
-    let mut offsets: Vec<u64> = Vec::with_capacity(16384);
-    for _ in 0..16384 {
-        let rand_val = rand::random::<u64>();
-        let block_num = rand_val % max_offset;
-        offsets.push(block_num * io_size);
+    if config.sq_poll {
+        builder.setup_sqpoll(SQPOLL_IDLE_MS);
     }
-    let mut offset_idx: usize = 0;
-
-    // Track start times
-    let mut start_times: Vec<std::time::Instant> = vec![std::time::Instant::now(); qd];
+    let mut ring: IoUring = builder.build(queue_depth).map_err(|e| {
+        if config.io_poll || config.sq_poll {
+            io::Error::new(e.kind(), format!("io_uring polling setup failed: {}", e))
+        } else {
+            e
+        }
+    })?;
+
+    // Allocate aligned buffers per slot; with --numa, on the node of the core
+    // this thread was pinned to before it started
+    let buffer_node = config
+        .numa_node
+        .or_else(|| config.numa_local.then(current_numa_node).flatten());
+    let mut buffers = super::SlotBuffers::new(config, thread_id, qd, sector_size, |buf| {
+        if let Some(node) = buffer_node {
+            bind_buffer_to_node(buf, node);
+        }
+    });
 
-    // Submit initial batch
-    for slot in 0..qd {
-        let off = offsets[offset_idx];
-        offset_idx = (offset_idx + 1) % offsets.len();
-        start_times[slot] = std::time::Instant::now();
+    let target = if config.fixed_buffers {
+        register_fixed(&ring, dev.fd, &buffers)?;
+        RingTarget::Fixed
+    } else {
+        RingTarget::Fd(dev.fd)
+    };
 
-        let entry = if is_write {
-            opcode::Write::new(
-                types::Fd(dev.fd),
-                buffers[slot].ptr,
-                io_size as u32,
-            )
-            .offset(off)
-            .build()
-            .user_data(slot as u64)
-        } else {
-            opcode::Read::new(
-                types::Fd(dev.fd),
-                buffers[slot].ptr,
-                io_size as u32,
-            )
-            .offset(off)
-            .build()
-            .user_data(slot as u64)
-        };
+    let mut offsets = super::OffsetGenerator::new(config, thread_id, &mut rng, test_range);
 
-        unsafe { ring.submission().push(&entry).ok() };
-    }
-    ring.submit()?;
+    // Track start times, offsets and whether each slot currently holds a write
+    let mut start_times: Vec<std::time::Instant> = vec![std::time::Instant::now(); qd];
+    let mut slot_offsets: Vec<u64> = vec![0; qd];
+    let mut slot_writes: Vec<bool> = vec![false; qd];
+    let mut slot_lens: Vec<u64> = vec![io_size; qd];
+    let mut inflight = super::InflightMix::default();
+    let mut latency = super::LatencySampler::new(config, thread_id, metrics);
+    let mut fsync = super::FsyncSchedule::new(config);
+    let mut verifier = super::verify::Verifier::new(config, thread_id);
+    let mut warming = metrics.warming_up.load(Ordering::Relaxed);
+    let mut pacer = super::Pacer::new(config, thread_id);
+    // With --target-iops, slots that completed wait here for their turn
+    let mut idle: Vec<usize> = Vec::new();
+    let wait_timeout = ring.params().is_feature_ext_arg();
 
     let mut local_ops: u64 = 0;
     let mut local_bytes: u64 = 0;
+    let mut local_write_ops: u64 = 0;
+    let mut local_write_bytes: u64 = 0;
     let batch_size: u64 = 256;
+    let sample_rate = config.latency_sample_rate.max(1) as u64;
     let mut op_count: u64 = 0;
+    let mut poll_unsupported = false;
+    // I/Os pushed to the ring whose completion has not been reaped yet
+    let mut in_ring: usize = 0;
+
+    // Everything that submits runs in here, so however it ends (the stop
+    // flag or an error) the drain below still runs before the buffers go
+    let run = (|| -> io::Result<()> {
+        // Submit initial batch; a paced worker starts with every slot idle
+        for slot in 0..qd {
+            if pacer.is_some() {
+                idle.push(slot);
+                continue;
+            }
+            let off = offsets.next(slot, &mut rng);
+            start_times[slot] = std::time::Instant::now();
+            slot_offsets[slot] = off;
+            slot_writes[slot] = config.next_is_write(&mut rng);
+            slot_lens[slot] = config.next_io_len(&mut rng);
+            inflight.issue(slot_writes[slot]);
+            if slot_writes[slot] {
+                verifier.before_write(buffers[slot].as_mut_slice(), off);
+            }
 
-    while !stop.load(Ordering::Relaxed) {
-        // Wait for at least 1 completion
-        ring.submit_and_wait(1)?;
-
-        // Collect completions first
-        let mut completions = Vec::new();
-        {
-            let cq = ring.completion();
-            for cqe in cq {
-                completions.push((cqe.user_data() as usize, cqe.result()));
+            let entry = rw_entry(
+                target,
+                buffers[slot].ptr,
+                buffers.index_of(slot),
+                slot_lens[slot] as u32,
+                off,
+                slot_writes[slot],
+                slot,
+            );
+            if push_sqe(&mut ring, &entry)? {
+                in_ring += 1;
+            } else {
+                inflight.complete(slot_writes[slot]);
+                metrics.dropped_submissions.fetch_add(1, Ordering::Relaxed);
             }
         }
+        ring.submit()?;
+
+        while !stop.load(Ordering::Relaxed) && !fsync.failed() {
+            if warming && !metrics.warming_up.load(Ordering::Relaxed) {
+                // Warmup is over: discard what it measured and start counting
+                warming = false;
+                local_ops = 0;
+                local_bytes = 0;
+                local_write_ops = 0;
+                local_write_bytes = 0;
+                latency.clear();
+                inflight.clear();
+                fsync.clear();
+                verifier.clear();
+            }
 
-        // Process completions and reissue
-        for (slot, result) in completions {
-            if result > 0 {
-                op_count += 1;
-                if op_count % 64 == 0 {
-                    let lat_ns = start_times[slot].elapsed().as_nanos() as u64;
-                    metrics.record_latency(lat_ns);
+            // Wait for at least 1 completion. A paced worker wakes for its next
+            // due I/O too, and with nothing in flight just sleeps until then.
+            match &pacer {
+                Some(p) if inflight.in_flight() == 0 => p.sleep(stop),
+                Some(p) if !idle.is_empty() && wait_timeout => wait_until(&ring, p.due())?,
+                _ => {
+                    ring.submit_and_wait(1)?;
                 }
+            }
 
-                local_ops += 1;
-                local_bytes += result as u64;
+            // Collect completions first
+            let mut completions = Vec::new();
+            {
+                let cq = ring.completion();
+                for cqe in cq {
+                    completions.push((cqe.user_data() as usize, cqe.result()));
+                }
             }
+            in_ring -= completions.len();
 
-            // Reissue I/O on this slot
-            let off = offsets[offset_idx];
-            offset_idx = (offset_idx + 1) % offsets.len();
-            start_times[slot] = std::time::Instant::now();
+            // Process completions and reissue
+            if config.io_poll && completions.iter().any(|&(_, result)| result == -libc::EOPNOTSUPP) {
+                // The device has no poll queues: every I/O would fail the same way
+                poll_unsupported = true;
+                break;
+            }
+            let mut ready = Vec::with_capacity(completions.len());
+            for (slot, result) in completions {
+                inflight.complete(slot_writes[slot]);
+                if result < 0 {
+                    metrics.record_io_error(Some(-result));
+                } else if (result as u64) < slot_lens[slot] {
+                    metrics.record_io_error(None);
+                }
+                if result > 0 {
+                    op_count += 1;
+                    if op_count.is_multiple_of(sample_rate) {
+                        let lat_ns = start_times[slot].elapsed().as_nanos() as u64;
+                        latency.record(lat_ns, slot_writes[slot]);
+                    }
+
+                    local_ops += 1;
+                    local_bytes += result as u64;
+                    if slot_writes[slot] {
+                        local_write_ops += 1;
+                        local_write_bytes += result as u64;
+                        fsync.write_done(|| flush_handle(&dev));
+                    } else {
+                        verifier.after_read(&buffers[slot].as_slice()[..result as usize], slot_offsets[slot]);
+                    }
+                }
+                ready.push(slot);
+            }
 
-            let entry = if is_write {
-                opcode::Write::new(
-                    types::Fd(dev.fd),
-                    buffers[slot].ptr,
-                    io_size as u32,
-                )
-                .offset(off)
-                .build()
-                .user_data(slot as u64)
-            } else {
-                opcode::Read::new(
-                    types::Fd(dev.fd),
-                    buffers[slot].ptr,
-                    io_size as u32,
-                )
-                .offset(off)
-                .build()
-                .user_data(slot as u64)
-            };
+            // Paced: completed slots queue up and only the I/Os now due go out
+            if let Some(p) = &mut pacer {
+                idle.append(&mut ready);
+                while !idle.is_empty() && p.take() {
+                    ready.extend(idle.pop());
+                }
+            }
 
-            unsafe { ring.submission().push(&entry).ok() };
-        }
-        ring.submit()?;
+            // Reissue I/O on each slot, drawing read vs write afresh for every op
+            for slot in ready {
+                let off = offsets.next(slot, &mut rng);
+                start_times[slot] = std::time::Instant::now();
+                slot_offsets[slot] = off;
+                slot_writes[slot] = config.next_is_write(&mut rng);
+                slot_lens[slot] = config.next_io_len(&mut rng);
+                inflight.issue(slot_writes[slot]);
+                inflight.sample();
+                if slot_writes[slot] {
+                    verifier.before_write(buffers[slot].as_mut_slice(), off);
+                }
 
-        // Batch update metrics
-        if local_ops >= batch_size {
-            metrics.total_ops.fetch_add(local_ops, Ordering::Relaxed);
-            metrics.total_bytes.fetch_add(local_bytes, Ordering::Relaxed);
-            local_ops = 0;
-            local_bytes = 0;
+                let entry = rw_entry(
+                    target,
+                    buffers[slot].ptr,
+                    buffers.index_of(slot),
+                    slot_lens[slot] as u32,
+                    off,
+                    slot_writes[slot],
+                    slot,
+                );
+                if push_sqe(&mut ring, &entry)? {
+                    in_ring += 1;
+                } else {
+                    inflight.complete(slot_writes[slot]);
+                    metrics.dropped_submissions.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            ring.submit()?;
+
+            // Batch update metrics
+            if !warming && local_ops >= batch_size {
+                metrics.total_ops.fetch_add(local_ops, Ordering::Relaxed);
+                metrics.total_bytes.fetch_add(local_bytes, Ordering::Relaxed);
+                metrics.write_ops.fetch_add(local_write_ops, Ordering::Relaxed);
+                metrics.write_bytes.fetch_add(local_write_bytes, Ordering::Relaxed);
+                latency.flush_average(metrics);
+                local_ops = 0;
+                local_bytes = 0;
+                local_write_ops = 0;
+                local_write_bytes = 0;
+            }
         }
-    }
+        Ok(())
+    })();
 
     // Flush remaining
     if local_ops > 0 {
         metrics.total_ops.fetch_add(local_ops, Ordering::Relaxed);
         metrics.total_bytes.fetch_add(local_bytes, Ordering::Relaxed);
+        metrics.write_ops.fetch_add(local_write_ops, Ordering::Relaxed);
+        metrics.write_bytes.fetch_add(local_write_bytes, Ordering::Relaxed);
+    }
+    inflight.flush(metrics);
+    verifier.flush(metrics);
+    latency.finish(metrics);
+
+    // Slots may still have I/Os in flight that DMA straight into their
+    // buffers. Cancel and reap them all before the buffers are freed; if the
+    // ring can't be drained, leak the buffers rather than free them under
+    // the kernel.
+    if let Err(e) = drain_ring(&mut ring, in_ring, !config.io_poll) {
+        std::mem::forget(buffers);
+        return Err(e);
+    }
+    run?;
+
+    if poll_unsupported {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{} does not support polled I/O (NVMe needs poll queues, e.g. nvme.poll_queues=N)",
+                device_path
+            ),
+        ));
     }
 
-    Ok(())
+    fsync.finish(metrics)
 }
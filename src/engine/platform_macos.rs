@@ -0,0 +1,260 @@
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+/// Wrapper around a raw macOS file descriptor opened with F_NOCACHE
+pub struct DeviceHandle {
+    fd: RawFd,
+}
+
+unsafe impl Send for DeviceHandle {}
+unsafe impl Sync for DeviceHandle {}
+
+impl Drop for DeviceHandle {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+impl AsRawFd for DeviceHandle {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+/// Open device for reading with the unified buffer cache disabled
+pub fn open_device_read(path: &str, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    open_device(path, false, flags)
+}
+
+/// Open device for writing with the unified buffer cache disabled
+pub fn open_device_write(path: &str, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    open_device(path, true, flags)
+}
+
+/// macOS has no O_DIRECT; F_NOCACHE on the open descriptor is the equivalent
+fn open_device(path: &str, write: bool, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    let dev = open_fd(path, write)?;
+    if flags.buffered {
+        return Ok(dev);
+    }
+    if unsafe { libc::fcntl(dev.fd, libc::F_NOCACHE, 1) } < 0 {
+        let err = io::Error::last_os_error();
+        if !flags.buffered_fallback {
+            return Err(err);
+        }
+        super::warn_buffered(path);
+    }
+    Ok(dev)
+}
+
+fn open_fd(path: &str, write: bool) -> io::Result<DeviceHandle> {
+    let c_path = std::ffi::CString::new(path).unwrap();
+    let flags = if write { libc::O_RDWR } else { libc::O_RDONLY };
+
+    let fd = unsafe { libc::open(c_path.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(super::privilege_error(io::Error::last_os_error()));
+    }
+
+    Ok(DeviceHandle { fd })
+}
+
+/// Allocate `len` bytes for a new file without writing them, then extend it
+/// to that length. APFS and HFS+ read the unwritten space back as zeros
+/// without touching the device. Returns whether reads reach the device.
+pub fn preallocate(file: &std::fs::File, len: u64) -> io::Result<bool> {
+    let mut store = libc::fstore_t {
+        fst_flags: libc::F_ALLOCATEALL,
+        fst_posmode: libc::F_PEOFPOSMODE,
+        fst_offset: 0,
+        fst_length: len as libc::off_t,
+        fst_bytesalloc: 0,
+    };
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PREALLOCATE, &mut store) } < 0 {
+        let err = io::Error::last_os_error();
+        return Err(io::Error::new(err.kind(), format!("F_PREALLOCATE failed: {}", err)));
+    }
+    file.set_len(len)?;
+    Ok(false)
+}
+
+/// Write back dirty buffers before a test. macOS offers no unprivileged way to
+/// evict a file's cached pages, but tests open with F_NOCACHE so reads bypass them.
+pub fn flush_caches(_path: &str) -> io::Result<()> {
+    unsafe { libc::sync() };
+    Ok(())
+}
+
+/// Flush buffered data and the drive's volatile write cache to stable media.
+/// Plain `fsync` does not reach the drive on macOS; F_FULLFSYNC does.
+pub fn sync_device(path: &str) -> io::Result<()> {
+    let dev = open_fd(path, true)?;
+    flush_handle(&dev)
+}
+
+/// F_FULLFSYNC an open device; used by `--fsync-every` inside the write worker
+pub fn flush_handle(dev: &DeviceHandle) -> io::Result<()> {
+    if unsafe { libc::fcntl(dev.fd, libc::F_FULLFSYNC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Classify a target by `stat`. Raw disks (`/dev/rdiskN`) are character
+/// devices on macOS, so both block and character devices count as devices.
+pub fn classify_device(path: &str) -> super::DeviceKind {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_file() => super::DeviceKind::File,
+        Ok(meta) if meta.file_type().is_block_device() || meta.file_type().is_char_device() => {
+            super::DeviceKind::BlockDevice
+        }
+        _ => super::DeviceKind::Other,
+    }
+}
+
+/// `_IOR('d', 24, u32)` / `_IOR('d', 25, u64)` / `_IOR('d', 77, u32)` from <sys/disk.h>
+const DKIOCGETBLOCKSIZE: libc::c_ulong = 0x4004_6418;
+const DKIOCGETBLOCKCOUNT: libc::c_ulong = 0x4008_6419;
+const DKIOCGETPHYSICALBLOCKSIZE: libc::c_ulong = 0x4004_644D;
+
+/// Logical and physical sector size of a disk; None for files or when the
+/// disk can't be queried
+pub fn get_sector_size(path: &str) -> Option<super::SectorSize> {
+    if classify_device(path) != super::DeviceKind::BlockDevice {
+        return None;
+    }
+
+    let dev = open_fd(path, false).ok()?;
+    let mut logical: u32 = 0;
+    let mut physical: u32 = 0;
+    if unsafe { libc::ioctl(dev.fd, DKIOCGETBLOCKSIZE, &mut logical) } < 0 {
+        return None;
+    }
+    if unsafe { libc::ioctl(dev.fd, DKIOCGETPHYSICALBLOCKSIZE, &mut physical) } < 0 {
+        physical = logical;
+    }
+    Some(super::SectorSize {
+        logical: logical.max(1),
+        physical: physical.max(1),
+    })
+}
+
+/// Get device or file size
+pub fn get_device_size(path: &str) -> io::Result<u64> {
+    if classify_device(path) == super::DeviceKind::File {
+        return Ok(std::fs::metadata(path)?.len());
+    }
+
+    // Disk: block count x block size
+    let dev = open_fd(path, false)?;
+    let mut block_size: u32 = 0;
+    let mut block_count: u64 = 0;
+    if unsafe { libc::ioctl(dev.fd, DKIOCGETBLOCKSIZE, &mut block_size) } < 0 {
+        return Err(super::size_query_error("DKIOCGETBLOCKSIZE", io::Error::last_os_error()));
+    }
+    if unsafe { libc::ioctl(dev.fd, DKIOCGETBLOCKCOUNT, &mut block_count) } < 0 {
+        return Err(super::size_query_error("DKIOCGETBLOCKCOUNT", io::Error::last_os_error()));
+    }
+
+    Ok(block_count * block_size as u64)
+}
+
+/// Discard is not wired up on macOS (DKIOCUNMAP is private to the kernel's
+/// storage drivers)
+pub fn discard_range(_path: &str, _offset: u64, _len: u64) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--trim is not supported on macOS"))
+}
+
+/// Enumerate whole disks (`/dev/diskN`, not slices like `/dev/disk0s1`).
+/// Disks that cannot be opened (usually without sudo) are skipped.
+pub fn list_devices() -> Vec<super::DiskInfo> {
+    let entries = match std::fs::read_dir("/dev") {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut disks: Vec<super::DiskInfo> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let index = name.strip_prefix("disk")?;
+            if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let path = format!("/dev/{}", name);
+            let size_bytes = get_device_size(&path).ok()?;
+            Some(super::DiskInfo {
+                path,
+                size_bytes,
+                model: None,
+            })
+        })
+        .collect();
+
+    disks.sort_by(|a, b| a.path.cmp(&b.path));
+    disks
+}
+
+/// First Ctrl-C: ask the run to wind down. The default action is restored so
+/// a second Ctrl-C kills the process as usual.
+extern "C" fn on_sigint(_: libc::c_int) {
+    super::INTERRUPTED.store(true, std::sync::atomic::Ordering::Relaxed);
+    unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+}
+
+/// Route SIGINT to `super::interrupted()` instead of killing the process
+pub fn install_interrupt_handler() {
+    let handler = on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe { libc::signal(libc::SIGINT, handler) };
+}
+
+/// macOS exposes no thread-to-CPU binding, so NUMA placement is unavailable
+#[derive(Clone, Copy)]
+pub struct CpuAffinity;
+
+impl CpuAffinity {
+    pub fn for_node(_node: u32) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "NUMA node binding is not supported on macOS",
+        ))
+    }
+
+    pub fn current() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "CPU pinning is not supported on macOS",
+        ))
+    }
+
+    pub fn cores(&self) -> Vec<Self> {
+        Vec::new()
+    }
+
+    pub fn pin_current_thread(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Synchronous read at offset
+pub fn read_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) -> io::Result<u32> {
+    let result = unsafe {
+        libc::pread(dev.fd, buf.ptr as *mut libc::c_void, buf.len, offset as i64)
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result as u32)
+}
+
+/// Synchronous write at offset
+pub fn write_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) -> io::Result<u32> {
+    let result = unsafe {
+        libc::pwrite(dev.fd, buf.ptr as *const libc::c_void, buf.len, offset as i64)
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result as u32)
+}
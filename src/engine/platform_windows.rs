@@ -5,6 +5,7 @@ use std::ptr;
 use windows_sys::Win32::Foundation::*;
 use windows_sys::Win32::Storage::FileSystem::*;
 use windows_sys::Win32::System::IO::*;
+use windows_sys::Win32::System::SystemInformation::GROUP_AFFINITY;
 use windows_sys::Win32::System::Threading::*;
 
 /// Wrapper around a raw Windows HANDLE
@@ -28,7 +29,8 @@ fn to_wide(s: &str) -> Vec<u16> {
 }
 
 /// Normalize device path on Windows
-/// Accepts either \\.\PhysicalDrive4 or just 4 and returns the full path
+/// Accepts either \\.\PhysicalDrive4 or just 4 and returns the full path;
+/// a drive letter (`C:` or `C:\`) becomes the volume path `\\.\C:`
 pub fn normalize_device_path(path: &str) -> String {
     let trimmed = path.trim();
 
@@ -42,21 +44,145 @@ pub fn normalize_device_path(path: &str) -> String {
         return format!(r"\\.\PhysicalDrive{}", trimmed);
     }
 
+    // A bare drive letter means the volume, not its root directory
+    if let [letter, b':'] | [letter, b':', b'\\'] = trimmed.as_bytes() {
+        if letter.is_ascii_alphabetic() {
+            return format!(r"\\.\{}:", letter.to_ascii_uppercase() as char);
+        }
+    }
+
     // Otherwise assume it's a file path and return as-is
     trimmed.to_string()
 }
 
+/// Enumerate physical drives by probing \\.\PhysicalDrive0..63, with the
+/// model from the drive's STORAGE_DEVICE_DESCRIPTOR.
+/// Drives that cannot be opened (missing or access denied) are skipped.
+pub fn list_devices() -> Vec<super::DiskInfo> {
+    (0..64)
+        .filter_map(|n| {
+            let path = format!(r"\\.\PhysicalDrive{}", n);
+            let size_bytes = get_device_size(&path).ok()?;
+            let mut details = crate::report::DeviceDetails::new(&path, super::DeviceKind::BlockDevice);
+            fill_device_identity(&path, &mut details);
+            Some(super::DiskInfo {
+                path,
+                size_bytes,
+                model: details.model,
+            })
+        })
+        .collect()
+}
+
+/// Allocate `len` bytes for a new file without writing them. SetEndOfFile
+/// reserves the clusters; SetFileValidData then moves the valid data length
+/// to the end, so reads go to the disk instead of NTFS returning zeros. That
+/// needs SeManageVolumePrivilege, which administrators hold but must enable
+/// first; the result says whether it took.
+pub fn preallocate(file: &std::fs::File, len: u64) -> io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+
+    file.set_len(len)?;
+    enable_manage_volume_privilege();
+    Ok(unsafe { SetFileValidData(file.as_raw_handle() as HANDLE, len as i64) } != 0)
+}
+
+/// Best effort: turn on SeManageVolumePrivilege in this process's token
+fn enable_manage_volume_privilege() {
+    use windows_sys::Win32::Security::{
+        AdjustTokenPrivileges, LookupPrivilegeValueW, LUID_AND_ATTRIBUTES, SE_MANAGE_VOLUME_NAME,
+        SE_PRIVILEGE_ENABLED, TOKEN_ADJUST_PRIVILEGES, TOKEN_PRIVILEGES,
+    };
+
+    unsafe {
+        let mut token: HANDLE = ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_ADJUST_PRIVILEGES, &mut token) == 0 {
+            return;
+        }
+        let mut privileges = TOKEN_PRIVILEGES {
+            PrivilegeCount: 1,
+            Privileges: [LUID_AND_ATTRIBUTES {
+                Luid: std::mem::zeroed(),
+                Attributes: SE_PRIVILEGE_ENABLED,
+            }],
+        };
+        if LookupPrivilegeValueW(ptr::null(), SE_MANAGE_VOLUME_NAME, &mut privileges.Privileges[0].Luid) != 0 {
+            AdjustTokenPrivileges(token, 0, &privileges, 0, ptr::null_mut(), ptr::null_mut());
+        }
+        CloseHandle(token);
+    }
+}
+
+/// Write back dirty data the system cache holds for a device, volume or file,
+/// so an earlier write test's data is on the device before a read test starts.
+/// Clean pages stay cached: this does not stop a buffered read from being served
+/// from memory. Needs write access, which for raw volumes and physical drives
+/// means running as administrator.
+pub fn flush_caches(path: &str) -> io::Result<()> {
+    sync_device(path)
+}
+
+/// Flush buffered data and the device's volatile write cache to stable media
+pub fn sync_device(path: &str) -> io::Result<()> {
+    let wide_path = to_wide(path);
+    let handle = unsafe {
+        CreateFileW(
+            wide_path.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            ptr::null(),
+            OPEN_EXISTING,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    let dev = DeviceHandle { handle };
+    flush_handle(&dev)
+}
+
+/// FlushFileBuffers on an open device; used by `--fsync-every` inside the write workers
+pub fn flush_handle(dev: &DeviceHandle) -> io::Result<()> {
+    if unsafe { FlushFileBuffers(dev.handle) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 /// Open device for reading with direct I/O + overlapped
-pub fn open_device_read(path: &str) -> io::Result<DeviceHandle> {
-    open_device(path, false)
+pub fn open_device_read(path: &str, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    open_device(path, false, flags)
 }
 
 /// Open device for writing with direct I/O + overlapped
-pub fn open_device_write(path: &str) -> io::Result<DeviceHandle> {
-    open_device(path, true)
+pub fn open_device_write(path: &str, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    open_device(path, true, flags)
 }
 
-fn open_device(path: &str, write: bool) -> io::Result<DeviceHandle> {
+fn open_device(path: &str, write: bool, flags: super::OpenFlags) -> io::Result<DeviceHandle> {
+    if flags.buffered {
+        return create_device_handle(path, write, 0);
+    }
+    match create_device_handle(path, write, FILE_FLAG_NO_BUFFERING) {
+        // Some volumes and file paths reject unbuffered access outright
+        Err(e)
+            if flags.buffered_fallback
+                && matches!(
+                    e.raw_os_error().map(|c| c as u32),
+                    Some(ERROR_INVALID_PARAMETER) | Some(ERROR_NOT_SUPPORTED)
+                ) =>
+        {
+            let dev = create_device_handle(path, write, 0)?;
+            super::warn_buffered(path);
+            Ok(dev)
+        }
+        result => result,
+    }
+}
+
+fn create_device_handle(path: &str, write: bool, buffering: u32) -> io::Result<DeviceHandle> {
     let wide_path = to_wide(path);
     let access = if write {
         GENERIC_READ | GENERIC_WRITE
@@ -64,7 +190,7 @@ fn open_device(path: &str, write: bool) -> io::Result<DeviceHandle> {
         GENERIC_READ
     };
 
-    let flags = FILE_FLAG_NO_BUFFERING | FILE_FLAG_WRITE_THROUGH | FILE_FLAG_OVERLAPPED;
+    let flags = buffering | FILE_FLAG_WRITE_THROUGH | FILE_FLAG_OVERLAPPED;
 
     let handle = unsafe {
         CreateFileW(
@@ -79,22 +205,30 @@ fn open_device(path: &str, write: bool) -> io::Result<DeviceHandle> {
     };
 
     if handle == INVALID_HANDLE_VALUE {
-        return Err(io::Error::last_os_error());
+        return Err(super::privilege_error(io::Error::last_os_error()));
     }
 
     Ok(DeviceHandle { handle })
 }
 
+/// Classify a target: `\\.\` device namespace paths (physical drives and
+/// volumes) are block devices, otherwise ask the filesystem
+pub fn classify_device(path: &str) -> super::DeviceKind {
+    if path.trim().starts_with(r"\\.\") {
+        return super::DeviceKind::BlockDevice;
+    }
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_file() => super::DeviceKind::File,
+        _ => super::DeviceKind::Other,
+    }
+}
+
 /// Get device or file size
 pub fn get_device_size(path: &str) -> io::Result<u64> {
-    // Try as regular file first
-    if let Ok(meta) = std::fs::metadata(path) {
-        if meta.len() > 0 {
-            return Ok(meta.len());
-        }
+    if classify_device(path) == super::DeviceKind::File {
+        return Ok(std::fs::metadata(path)?.len());
     }
 
-    // Try as device - use IOCTL_DISK_GET_LENGTH_INFO
     let wide_path = to_wide(path);
     let handle = unsafe {
         CreateFileW(
@@ -109,17 +243,35 @@ pub fn get_device_size(path: &str) -> io::Result<u64> {
     };
 
     if handle == INVALID_HANDLE_VALUE {
-        return Err(io::Error::last_os_error());
+        return Err(super::privilege_error(io::Error::last_os_error()));
+    }
+    let dev = DeviceHandle { handle };
+
+    // Physical drives answer IOCTL_DISK_GET_LENGTH_INFO; some volumes reject
+    // it or report 0, so fall back to the partition the volume lives on, then
+    // to the size of the filesystem mounted on it
+    let length = disk_length(&dev);
+    if let Ok(len @ 1..) = length {
+        return Ok(len);
+    }
+    if let Some(len) = partition_length(&dev).filter(|&len| len > 0) {
+        return Ok(len);
+    }
+    if let Some(len) = volume_fs_size(path).filter(|&len| len > 0) {
+        return Ok(len);
     }
+    length.map_err(|e| super::size_query_error("IOCTL_DISK_GET_LENGTH_INFO", e))
+}
 
-    // IOCTL_DISK_GET_LENGTH_INFO = 0x0007405C
+/// IOCTL_DISK_GET_LENGTH_INFO = 0x0007405C
+fn disk_length(dev: &DeviceHandle) -> io::Result<u64> {
     const IOCTL_DISK_GET_LENGTH_INFO: u32 = 0x0007405C;
     let mut length: i64 = 0;
     let mut bytes_returned: u32 = 0;
 
     let result = unsafe {
         DeviceIoControl(
-            handle,
+            dev.handle,
             IOCTL_DISK_GET_LENGTH_INFO,
             ptr::null(),
             0,
@@ -130,8 +282,6 @@ pub fn get_device_size(path: &str) -> io::Result<u64> {
         )
     };
 
-    unsafe { CloseHandle(handle) };
-
     if result == 0 {
         return Err(io::Error::last_os_error());
     }
@@ -139,12 +289,489 @@ pub fn get_device_size(path: &str) -> io::Result<u64> {
     Ok(length as u64)
 }
 
-/// Synchronous read at offset (for prep/simple operations)
-pub fn read_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) -> io::Result<u32> {
+/// PARTITION_INFORMATION_EX; the MBR/GPT union at the end is not needed
+#[repr(C)]
+struct PartitionInformationEx {
+    partition_style: u32,
+    starting_offset: i64,
+    partition_length: i64,
+    partition_number: u32,
+    rewrite_partition: u8,
+    is_service_partition: u8,
+    layout: [u64; 14],
+}
+
+/// Length of the partition behind a volume, from IOCTL_DISK_GET_PARTITION_INFO_EX
+fn partition_length(dev: &DeviceHandle) -> Option<u64> {
+    const IOCTL_DISK_GET_PARTITION_INFO_EX: u32 = 0x0007_0048;
+    let mut info: PartitionInformationEx = unsafe { std::mem::zeroed() };
+    let mut bytes_returned: u32 = 0;
+
+    let result = unsafe {
+        DeviceIoControl(
+            dev.handle,
+            IOCTL_DISK_GET_PARTITION_INFO_EX,
+            ptr::null(),
+            0,
+            &mut info as *mut PartitionInformationEx as *mut _,
+            std::mem::size_of::<PartitionInformationEx>() as u32,
+            &mut bytes_returned,
+            ptr::null_mut(),
+        )
+    };
+
+    if result == 0 {
+        return None;
+    }
+    Some(info.partition_length as u64)
+}
+
+/// Total size of the filesystem on a drive-letter volume (`\\.\C:`), which
+/// is a little smaller than the volume itself; the last resort when the
+/// volume answers neither disk IOCTL
+fn volume_fs_size(path: &str) -> Option<u64> {
+    let letter = path.trim().strip_prefix(r"\\.\")?;
+    if !matches!(letter.as_bytes(), [l, b':'] if l.is_ascii_alphabetic()) {
+        return None;
+    }
+    let root = to_wide(&format!(r"{}\", letter));
+    let mut total: u64 = 0;
+    let result = unsafe { GetDiskFreeSpaceExW(root.as_ptr(), ptr::null_mut(), &mut total, ptr::null_mut()) };
+    if result == 0 {
+        return None;
+    }
+    Some(total)
+}
+
+/// STORAGE_DEVICE_NUMBER: the disk a handle is on, and which partition of it.
+/// A whole disk reports partition 0.
+#[repr(C)]
+#[derive(Default)]
+struct StorageDeviceNumber {
+    device_type: u32,
+    device_number: u32,
+    partition_number: u32,
+}
+
+/// IOCTL_STORAGE_GET_DEVICE_NUMBER on a physical drive or volume. The handle
+/// is opened without access rights, so this works unelevated. Volumes that
+/// span several disks don't answer.
+fn device_number(path: &str) -> io::Result<StorageDeviceNumber> {
+    const IOCTL_STORAGE_GET_DEVICE_NUMBER: u32 = 0x002D_1080;
+    let wide_path = to_wide(path);
+    let handle = unsafe {
+        CreateFileW(
+            wide_path.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            ptr::null(),
+            OPEN_EXISTING,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    let dev = DeviceHandle { handle };
+
+    let mut number = StorageDeviceNumber::default();
+    let mut bytes_returned: u32 = 0;
+    let result = unsafe {
+        DeviceIoControl(
+            dev.handle,
+            IOCTL_STORAGE_GET_DEVICE_NUMBER,
+            ptr::null(),
+            0,
+            &mut number as *mut StorageDeviceNumber as *mut _,
+            std::mem::size_of::<StorageDeviceNumber>() as u32,
+            &mut bytes_returned,
+            ptr::null_mut(),
+        )
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(number)
+}
+
+/// Mount points (drive letters and folders) of the filesystems mounted on a
+/// physical drive, or on the volume itself for a volume path. Windows keeps a
+/// mounted filesystem's own view of the disk, so raw writes underneath it
+/// corrupt it or fail part-way. Volumes without a recognised filesystem (RAW)
+/// have nothing to lose and are left out.
+pub fn mounted_volumes(path: &str) -> io::Result<Vec<String>> {
+    if classify_device(path) != super::DeviceKind::BlockDevice {
+        return Ok(Vec::new());
+    }
+    let target = device_number(path)?;
+
+    // Volume GUID paths (\\?\Volume{...}\) are 49 characters
+    let mut name = [0u16; MAX_PATH as usize];
+    let find = unsafe { FindFirstVolumeW(name.as_mut_ptr(), name.len() as u32) };
+    if find == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    let mut mounts = Vec::new();
+    loop {
+        let volume = from_wide(&name);
+        // The volume device is the GUID path without its trailing backslash
+        if let Ok(number) = device_number(volume.trim_end_matches('\\')) {
+            let on_target = number.device_type == target.device_type
+                && number.device_number == target.device_number
+                && (target.partition_number == 0 || number.partition_number == target.partition_number);
+            if on_target && has_filesystem(&name) {
+                mounts.extend(volume_mount_points(&name));
+            }
+        }
+        if unsafe { FindNextVolumeW(find, name.as_mut_ptr(), name.len() as u32) } == 0 {
+            break;
+        }
+    }
+    unsafe { FindVolumeClose(find) };
+    Ok(mounts)
+}
+
+/// Whether Windows recognises a filesystem on the volume `name` (a
+/// NUL-terminated volume GUID path); RAW and empty drives fail the query
+fn has_filesystem(name: &[u16]) -> bool {
+    let mut fs_name = [0u16; MAX_PATH as usize + 1];
+    let result = unsafe {
+        GetVolumeInformationW(
+            name.as_ptr(),
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            fs_name.as_mut_ptr(),
+            fs_name.len() as u32,
+        )
+    };
+    result != 0
+}
+
+/// Drive letters and folder mount points of the volume `name`
+fn volume_mount_points(name: &[u16]) -> Vec<String> {
+    let mut paths = vec![0u16; 1024];
+    let mut needed: u32 = 0;
+    loop {
+        let result = unsafe { GetVolumePathNamesForVolumeNameW(name.as_ptr(), paths.as_mut_ptr(), paths.len() as u32, &mut needed) };
+        if result != 0 {
+            break;
+        }
+        if unsafe { GetLastError() } != ERROR_MORE_DATA {
+            return Vec::new();
+        }
+        paths.resize(needed as usize, 0);
+    }
+    // A list of NUL-terminated strings, ended by an empty one
+    paths.split(|&c| c == 0).take_while(|p| !p.is_empty()).map(String::from_utf16_lossy).collect()
+}
+
+/// The string in a NUL-terminated UTF-16 buffer
+fn from_wide(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+/// IOCTL_STORAGE_QUERY_PROPERTY with StorageDeviceProperty /
+/// StorageAccessAlignmentProperty and PropertyStandardQuery
+const IOCTL_STORAGE_QUERY_PROPERTY: u32 = 0x002D_1400;
+const STORAGE_DEVICE_PROPERTY: i32 = 0;
+const STORAGE_ACCESS_ALIGNMENT_PROPERTY: i32 = 6;
+const PROPERTY_STANDARD_QUERY: i32 = 0;
+
+#[repr(C)]
+struct StoragePropertyQuery {
+    property_id: i32,
+    query_type: i32,
+    additional_parameters: [u8; 1],
+}
+
+/// STORAGE_ACCESS_ALIGNMENT_DESCRIPTOR
+#[repr(C)]
+#[derive(Default)]
+struct StorageAccessAlignmentDescriptor {
+    version: u32,
+    size: u32,
+    bytes_per_cache_line: u32,
+    bytes_offset_for_cache_alignment: u32,
+    bytes_per_logical_sector: u32,
+    bytes_per_physical_sector: u32,
+    bytes_offset_for_sector_alignment: u32,
+}
+
+/// Logical and physical sector size of a drive or volume; None for files or
+/// when the device can't be queried
+pub fn get_sector_size(path: &str) -> Option<super::SectorSize> {
+    if classify_device(path) != super::DeviceKind::BlockDevice {
+        return None;
+    }
+
+    let mut desc = StorageAccessAlignmentDescriptor::default();
+    if !query_storage_property(path, STORAGE_ACCESS_ALIGNMENT_PROPERTY, &mut desc)
+        || desc.bytes_per_logical_sector == 0
+    {
+        return None;
+    }
+    Some(super::SectorSize {
+        logical: desc.bytes_per_logical_sector,
+        physical: desc.bytes_per_physical_sector.max(desc.bytes_per_logical_sector),
+    })
+}
+
+/// Fill in the vendor/product, serial number and firmware revision of a drive
+/// from its STORAGE_DEVICE_DESCRIPTOR. Files and volumes that don't answer are
+/// left untouched.
+pub fn fill_device_identity(path: &str, details: &mut crate::report::DeviceDetails) {
+    if classify_device(path) != super::DeviceKind::BlockDevice {
+        return;
+    }
+    // The descriptor is followed by the strings it points at, so read it raw
+    let mut raw = [0u8; 1024];
+    if !query_storage_property(path, STORAGE_DEVICE_PROPERTY, &mut raw) {
+        return;
+    }
+
+    // VendorIdOffset, ProductIdOffset, ProductRevisionOffset and
+    // SerialNumberOffset sit at bytes 12..28; an offset of 0 means "not reported"
+    let string_at = |field: usize| -> Option<String> {
+        let offset = u32::from_le_bytes(raw[field..field + 4].try_into().unwrap()) as usize;
+        if offset == 0 || offset >= raw.len() {
+            return None;
+        }
+        let end = raw[offset..].iter().position(|&b| b == 0).map_or(raw.len(), |n| offset + n);
+        let s = String::from_utf8_lossy(&raw[offset..end]).trim().to_string();
+        (!s.is_empty()).then_some(s)
+    };
+    let vendor = string_at(12);
+    let product = string_at(16);
+    details.model = match (vendor, product) {
+        (Some(v), Some(p)) if !p.starts_with(&v) => Some(format!("{} {}", v, p)),
+        (v, p) => p.or(v),
+    };
+    details.firmware = string_at(20);
+    details.serial = string_at(24);
+}
+
+/// IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES with DeviceDsmAction_Trim
+const IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES: u32 = 0x002D_9404;
+const DEVICE_DSM_ACTION_TRIM: u32 = 1;
+
+/// DEVICE_MANAGE_DATA_SET_ATTRIBUTES followed by one DEVICE_DATA_SET_RANGE
+#[repr(C)]
+struct TrimRequest {
+    size: u32,
+    action: u32,
+    flags: u32,
+    parameter_block_offset: u32,
+    parameter_block_length: u32,
+    data_set_ranges_offset: u32,
+    data_set_ranges_length: u32,
+    starting_offset: i64,
+    length_in_bytes: u64,
+}
+
+/// Tell the drive `len` bytes at `offset` no longer hold data (TRIM / UNMAP).
+/// Works on physical drives and volumes; files have no equivalent here.
+pub fn discard_range(path: &str, offset: u64, len: u64) -> io::Result<()> {
+    if classify_device(path) != super::DeviceKind::BlockDevice {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--trim needs a physical drive or volume on Windows",
+        ));
+    }
+    let wide_path = to_wide(path);
+    let handle = unsafe {
+        CreateFileW(
+            wide_path.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            ptr::null(),
+            OPEN_EXISTING,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(super::privilege_error(io::Error::last_os_error()));
+    }
+    let dev = DeviceHandle { handle };
+
+    let request = TrimRequest {
+        // sizeof(DEVICE_MANAGE_DATA_SET_ATTRIBUTES): the seven header fields
+        size: 7 * 4,
+        action: DEVICE_DSM_ACTION_TRIM,
+        flags: 0,
+        parameter_block_offset: 0,
+        parameter_block_length: 0,
+        data_set_ranges_offset: std::mem::offset_of!(TrimRequest, starting_offset) as u32,
+        data_set_ranges_length: 16, // one DEVICE_DATA_SET_RANGE
+        starting_offset: offset as i64,
+        length_in_bytes: len,
+    };
+    let mut bytes_returned: u32 = 0;
+    let ok = unsafe {
+        DeviceIoControl(
+            dev.handle,
+            IOCTL_STORAGE_MANAGE_DATA_SET_ATTRIBUTES,
+            &request as *const TrimRequest as *const _,
+            std::mem::size_of::<TrimRequest>() as u32,
+            ptr::null_mut(),
+            0,
+            &mut bytes_returned,
+            ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Issue IOCTL_STORAGE_QUERY_PROPERTY (standard query) for `property_id` and
+/// read the answer into `out`. Returns false if the device can't be opened or
+/// doesn't support the property.
+fn query_storage_property<T>(path: &str, property_id: i32, out: &mut T) -> bool {
+    let wide_path = to_wide(path);
+    let handle = unsafe {
+        CreateFileW(
+            wide_path.as_ptr(),
+            GENERIC_READ,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            ptr::null(),
+            OPEN_EXISTING,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return false;
+    }
+
+    let query = StoragePropertyQuery {
+        property_id,
+        query_type: PROPERTY_STANDARD_QUERY,
+        additional_parameters: [0],
+    };
+    let mut bytes_returned: u32 = 0;
+    let result = unsafe {
+        DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_QUERY_PROPERTY,
+            &query as *const StoragePropertyQuery as *const _,
+            std::mem::size_of::<StoragePropertyQuery>() as u32,
+            out as *mut T as *mut _,
+            std::mem::size_of::<T>() as u32,
+            &mut bytes_returned,
+            ptr::null_mut(),
+        )
+    };
+    unsafe { CloseHandle(handle) };
+    result != 0
+}
+
+/// First Ctrl-C / Ctrl-Break: ask the run to wind down and swallow the event.
+/// A second one falls through to the default handler, which ends the process.
+unsafe extern "system" fn on_console_ctrl(ctrl_type: u32) -> BOOL {
+    use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT};
+    use std::sync::atomic::Ordering;
+
+    if ctrl_type != CTRL_C_EVENT && ctrl_type != CTRL_BREAK_EVENT {
+        return 0;
+    }
+    if super::INTERRUPTED.swap(true, Ordering::Relaxed) {
+        return 0;
+    }
+    1
+}
+
+/// Route Ctrl-C to `super::interrupted()` instead of killing the process
+pub fn install_interrupt_handler() {
+    unsafe { windows_sys::Win32::System::Console::SetConsoleCtrlHandler(Some(on_console_ctrl), 1) };
+}
+
+/// Set of CPUs (within one processor group) a worker thread can be restricted to
+#[derive(Clone, Copy)]
+pub struct CpuAffinity {
+    group: GROUP_AFFINITY,
+}
+
+impl CpuAffinity {
+    /// All CPUs belonging to a NUMA node
+    pub fn for_node(node: u32) -> io::Result<Self> {
+        let mut group: GROUP_AFFINITY = unsafe { std::mem::zeroed() };
+        let ok = unsafe { GetNumaNodeProcessorMaskEx(node as u16, &mut group) };
+        if ok == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("NUMA node {} not found ({})", node, io::Error::last_os_error()),
+            ));
+        }
+        if group.Mask == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("NUMA node {} has no CPUs", node),
+            ));
+        }
+        Ok(Self { group })
+    }
+
+    /// The CPUs of the calling thread's processor group it may run on
+    pub fn current() -> io::Result<Self> {
+        let mut group: GROUP_AFFINITY = unsafe { std::mem::zeroed() };
+        let ok = unsafe { GetThreadGroupAffinity(GetCurrentThread(), &mut group) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { group })
+    }
+
+    /// Split this set into one single-CPU set per member, in CPU order
+    pub fn cores(&self) -> Vec<Self> {
+        (0..usize::BITS)
+            .filter(|bit| self.group.Mask & (1 << bit) != 0)
+            .map(|bit| {
+                let mut group = self.group;
+                group.Mask = 1 << bit;
+                Self { group }
+            })
+            .collect()
+    }
+
+    /// Restrict the calling thread to this CPU set.
+    /// Buffers are then placed on the thread's node by the default first-touch policy.
+    pub fn pin_current_thread(&self) -> io::Result<()> {
+        let ok = unsafe { SetThreadGroupAffinity(GetCurrentThread(), &self.group, ptr::null_mut()) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// A zeroed OVERLAPPED addressing `offset`. The offset is split across two
+/// 32-bit fields; dropping `OffsetHigh` would silently fold every I/O past
+/// 4 GB back into the first 4 GB of the device.
+fn overlapped_at(offset: u64) -> OVERLAPPED {
     let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
     overlapped.Anonymous.Anonymous.Offset = offset as u32;
     overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
 
+    let fields = unsafe { overlapped.Anonymous.Anonymous };
+    debug_assert_eq!(
+        (fields.OffsetHigh as u64) << 32 | fields.Offset as u64,
+        offset,
+        "OVERLAPPED does not address the requested offset"
+    );
+    overlapped
+}
+
+/// Synchronous read at offset (for prep/simple operations)
+pub fn read_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) -> io::Result<u32> {
+    let mut overlapped = overlapped_at(offset);
+
     let event = unsafe { CreateEventW(ptr::null(), 1, 0, ptr::null()) };
     overlapped.hEvent = event;
 
@@ -177,9 +804,7 @@ pub fn read_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) ->
 
 /// Synchronous write at offset (for prep/simple operations)
 pub fn write_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) -> io::Result<u32> {
-    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
-    overlapped.Anonymous.Anonymous.Offset = offset as u32;
-    overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
+    let mut overlapped = overlapped_at(offset);
 
     let event = unsafe { CreateEventW(ptr::null(), 1, 0, ptr::null()) };
     overlapped.hEvent = event;
@@ -211,21 +836,42 @@ pub fn write_at_raw(dev: &DeviceHandle, buf: &super::AlignedBuf, offset: u64) ->
     Ok(bytes_written)
 }
 
+/// Start an overlapped read or write for one slot. An error here means the
+/// request was rejected outright and no completion will be posted for it.
+fn issue_io(dev: &DeviceHandle, buf: &super::AlignedBuf, len: u32, overlapped: &mut OVERLAPPED, write: bool) -> io::Result<()> {
+    let ok = if write {
+        unsafe { WriteFile(dev.handle, buf.ptr as *const _, len, ptr::null_mut(), overlapped) }
+    } else {
+        unsafe { ReadFile(dev.handle, buf.ptr as *mut _, len, ptr::null_mut(), overlapped) }
+    };
+    if ok == 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(ERROR_IO_PENDING as i32) {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
 /// IOCP-based async I/O worker for maximum IOPS
 /// Each call submits `queue_depth` overlapped I/Os and polls for completion
 pub fn worker_iocp(
+    thread_id: u32,
     device_path: &str,
-    io_size: u64,
-    queue_depth: u32,
-    is_write: bool,
     test_range: u64,
+    config: &super::TestConfig,
     stop: &std::sync::atomic::AtomicBool,
     metrics: &super::Metrics,
 ) -> io::Result<()> {
-    let dev = if is_write {
-        open_device_write(device_path)?
+
+    let io_size = config.io_size;
+    let queue_depth = config.queue_depth;
+    let has_writes = config.has_writes();
+
+    let dev = if has_writes {
+        open_device_write(device_path, config.open_flags)?
     } else {
-        open_device_read(device_path)?
+        open_device_read(device_path, config.open_flags)?
     };
 
     // Create IOCP and associate the file handle
@@ -235,80 +881,89 @@ pub fn worker_iocp(
     }
 
     let qd = queue_depth as usize;
-    let sector_size: u64 = 4096;
-    let max_offset = test_range / io_size;
+    let sector_size = super::buffer_alignment(device_path) as u64;
+    let mut rng = config.worker_rng(thread_id);
 
     // Allocate aligned buffers and overlapped structures per slot
-    let mut buffers: Vec<super::AlignedBuf> = Vec::with_capacity(qd);
-    let mut overlappeds: Vec<OVERLAPPED> = Vec::with_capacity(qd);
-
-    for _ in 0..qd {
-        let mut buf = super::alloc_aligned(io_size as usize, sector_size as usize);
-        // Fill write buffers with random data
-        if is_write {
-            for chunk in buf.as_mut_slice().chunks_mut(8) {
-                let val = rand::random::<u64>();
-                let bytes = val.to_le_bytes();
-                let len = chunk.len().min(8);
-                chunk[..len].copy_from_slice(&bytes[..len]);
-            }
-        }
-        buffers.push(buf);
-        overlappeds.push(unsafe { std::mem::zeroed() });
-    }
+    let mut buffers = super::SlotBuffers::new(config, thread_id, qd, sector_size as usize, |_| {});
+    let mut overlappeds: Vec<OVERLAPPED> = (0..qd).map(|_| unsafe { std::mem::zeroed() }).collect();
 
-    // Pre-generate random offsets
-    let mut offsets: Vec<i64> = Vec::with_capacity(16384);
-    for _ in 0..16384 {
-        let rand_val = rand::random::<u64>();
-        let block_num = rand_val % max_offset;
-        offsets.push((block_num * io_size) as i64);
-    }
-    let mut offset_idx: usize = 0;
+    let mut offsets = super::OffsetGenerator::new(config, thread_id, &mut rng, test_range);
 
-    // Track start times for latency measurement
+    // Track start times for latency measurement, offsets and whether each slot holds a write
     let mut start_times: Vec<std::time::Instant> = vec![std::time::Instant::now(); qd];
+    let mut slot_offsets: Vec<u64> = vec![0; qd];
+    let mut slot_writes: Vec<bool> = vec![false; qd];
+    let mut slot_lens: Vec<u64> = vec![io_size; qd];
+    let mut inflight = super::InflightMix::default();
+    let mut latency = super::LatencySampler::new(config, thread_id, metrics);
+    let mut fsync = super::FsyncSchedule::new(config);
+    let mut verifier = super::verify::Verifier::new(config, thread_id);
+    let mut warming = metrics.warming_up.load(std::sync::atomic::Ordering::Relaxed);
+    let mut pacer = super::Pacer::new(config, thread_id);
+    // With --target-iops, slots that completed wait here for their turn
+    let mut idle: Vec<usize> = Vec::new();
 
-    // Submit initial batch of I/Os
+    // Submit initial batch of I/Os; a paced worker starts with every slot idle
     for slot in 0..qd {
-        let off = offsets[offset_idx] as u64;
-        offset_idx = (offset_idx + 1) % offsets.len();
+        if pacer.is_some() {
+            idle.push(slot);
+            continue;
+        }
+        let off = offsets.next(slot, &mut rng);
 
-        overlappeds[slot].Anonymous.Anonymous.Offset = off as u32;
-        overlappeds[slot].Anonymous.Anonymous.OffsetHigh = (off >> 32) as u32;
+        overlappeds[slot] = overlapped_at(off);
         start_times[slot] = std::time::Instant::now();
+        slot_offsets[slot] = off;
+        slot_writes[slot] = config.next_is_write(&mut rng);
+        slot_lens[slot] = config.next_io_len(&mut rng);
+        inflight.issue(slot_writes[slot]);
+        if slot_writes[slot] {
+            verifier.before_write(buffers[slot].as_mut_slice(), off);
+        }
 
-        if is_write {
-            unsafe {
-                WriteFile(
-                    dev.handle,
-                    buffers[slot].ptr as *const _,
-                    io_size as u32,
-                    ptr::null_mut(),
-                    &mut overlappeds[slot],
-                );
-            }
-        } else {
-            unsafe {
-                ReadFile(
-                    dev.handle,
-                    buffers[slot].ptr as *mut _,
-                    io_size as u32,
-                    ptr::null_mut(),
-                    &mut overlappeds[slot],
-                );
-            }
+        if let Err(e) = issue_io(&dev, &buffers[slot], slot_lens[slot] as u32, &mut overlappeds[slot], slot_writes[slot]) {
+            // Nothing will complete on this slot; leave it idle
+            inflight.complete(slot_writes[slot]);
+            metrics.record_io_error(e.raw_os_error());
         }
     }
 
     // Completion loop - batch completions with GetQueuedCompletionStatusEx
     let mut local_ops: u64 = 0;
     let mut local_bytes: u64 = 0;
+    let mut local_write_ops: u64 = 0;
+    let mut local_write_bytes: u64 = 0;
     let batch_size: u64 = 256;
+    let sample_rate = config.latency_sample_rate.max(1) as u64;
     let mut op_count: u64 = 0;
     const MAX_COMPLETIONS: usize = 64;
 
-    while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+    while !stop.load(std::sync::atomic::Ordering::Relaxed) && !fsync.failed() {
+        if warming && !metrics.warming_up.load(std::sync::atomic::Ordering::Relaxed) {
+            // Warmup is over: discard what it measured and start counting
+            warming = false;
+            local_ops = 0;
+            local_bytes = 0;
+            local_write_ops = 0;
+            local_write_bytes = 0;
+            latency.clear();
+            inflight.clear();
+            fsync.clear();
+            verifier.clear();
+        }
+
+        // A paced worker with nothing in flight sleeps until its next I/O is due
+        let mut timeout_ms = 1;
+        if let Some(p) = &pacer {
+            if inflight.in_flight() == 0 {
+                p.sleep(stop);
+            }
+            if !idle.is_empty() && p.due() <= std::time::Instant::now() {
+                timeout_ms = 0;
+            }
+        }
+
         let mut entries: [OVERLAPPED_ENTRY; MAX_COMPLETIONS] =
             unsafe { std::mem::zeroed() };
         let mut num_entries: u32 = 0;
@@ -320,19 +975,23 @@ pub fn worker_iocp(
                 entries.as_mut_ptr(),
                 MAX_COMPLETIONS as u32,
                 &mut num_entries,
-                1, // 1ms timeout
+                timeout_ms,
                 0, // not alertable
             )
         };
 
         if result == 0 {
-            // Timeout or error - just loop back to check stop flag
-            continue;
+            // Timeout or error - loop back to check the stop flag, unless a
+            // paced slot may be due
+            if pacer.is_none() {
+                continue;
+            }
+            num_entries = 0;
         }
 
         // Process all completions in this batch
-        for i in 0..num_entries as usize {
-            let entry = &entries[i];
+        let mut ready = Vec::with_capacity(num_entries as usize);
+        for entry in &entries[..num_entries as usize] {
             let overlapped_ptr = entry.lpOverlapped;
 
             if overlapped_ptr.is_null() {
@@ -351,59 +1010,92 @@ pub fn worker_iocp(
             }
 
             let bytes_transferred = entry.dwNumberOfBytesTransferred;
+            inflight.complete(slot_writes[slot]);
 
-            // Record latency (sample every 64th operation)
-            op_count += 1;
-            if op_count % 64 == 0 {
-                let lat_ns = start_times[slot].elapsed().as_nanos() as u64;
-                metrics.record_latency(lat_ns);
+            // A failed I/O still posts a completion; its status is in the OVERLAPPED
+            let failed = overlappeds[slot].Internal != STATUS_SUCCESS as usize;
+            if failed {
+                let mut n: u32 = 0;
+                let ok = unsafe { GetOverlappedResult(dev.handle, &overlappeds[slot], &mut n, 0) };
+                let code = if ok == 0 { io::Error::last_os_error().raw_os_error() } else { None };
+                metrics.record_io_error(code);
+            } else if (bytes_transferred as u64) < slot_lens[slot] {
+                metrics.record_io_error(None);
             }
 
-            local_ops += 1;
-            local_bytes += bytes_transferred as u64;
+            if !failed && bytes_transferred > 0 {
+                // Record latency (sample every Nth operation)
+                op_count += 1;
+                if op_count.is_multiple_of(sample_rate) {
+                    let lat_ns = start_times[slot].elapsed().as_nanos() as u64;
+                    latency.record(lat_ns, slot_writes[slot]);
+                }
+
+                local_ops += 1;
+                local_bytes += bytes_transferred as u64;
+                if slot_writes[slot] {
+                    local_write_ops += 1;
+                    local_write_bytes += bytes_transferred as u64;
+                    fsync.write_done(|| flush_handle(&dev));
+                } else {
+                    verifier.after_read(
+                        &buffers[slot].as_slice()[..bytes_transferred as usize],
+                        slot_offsets[slot],
+                    );
+                }
+            }
+            ready.push(slot);
+        }
 
-            // Reissue I/O on the completed slot
-            let off = offsets[offset_idx] as u64;
-            offset_idx = (offset_idx + 1) % offsets.len();
+        // Paced: completed slots queue up and only the I/Os now due go out
+        if let Some(p) = &mut pacer {
+            idle.append(&mut ready);
+            while !idle.is_empty() && p.take() {
+                ready.extend(idle.pop());
+            }
+        }
 
-            overlappeds[slot] = unsafe { std::mem::zeroed() };
-            overlappeds[slot].Anonymous.Anonymous.Offset = off as u32;
-            overlappeds[slot].Anonymous.Anonymous.OffsetHigh = (off >> 32) as u32;
+        // Reissue I/O on each completed slot
+        for slot in ready {
+            let off = offsets.next(slot, &mut rng);
+
+            overlappeds[slot] = overlapped_at(off);
             start_times[slot] = std::time::Instant::now();
+            slot_offsets[slot] = off;
+            // Draw read vs write afresh for every op
+            slot_writes[slot] = config.next_is_write(&mut rng);
+            slot_lens[slot] = config.next_io_len(&mut rng);
+            inflight.issue(slot_writes[slot]);
+            inflight.sample();
+            if slot_writes[slot] {
+                verifier.before_write(buffers[slot].as_mut_slice(), off);
+            }
 
-            if is_write {
-                unsafe {
-                    WriteFile(
-                        dev.handle,
-                        buffers[slot].ptr as *const _,
-                        io_size as u32,
-                        ptr::null_mut(),
-                        &mut overlappeds[slot],
-                    );
-                }
-            } else {
-                unsafe {
-                    ReadFile(
-                        dev.handle,
-                        buffers[slot].ptr as *mut _,
-                        io_size as u32,
-                        ptr::null_mut(),
-                        &mut overlappeds[slot],
-                    );
-                }
+            if let Err(e) = issue_io(&dev, &buffers[slot], slot_lens[slot] as u32, &mut overlappeds[slot], slot_writes[slot]) {
+                inflight.complete(slot_writes[slot]);
+                metrics.record_io_error(e.raw_os_error());
             }
         }
 
         // Batch update metrics
-        if local_ops >= batch_size {
+        if !warming && local_ops >= batch_size {
             metrics
                 .total_ops
                 .fetch_add(local_ops, std::sync::atomic::Ordering::Relaxed);
             metrics
                 .total_bytes
                 .fetch_add(local_bytes, std::sync::atomic::Ordering::Relaxed);
+            metrics
+                .write_ops
+                .fetch_add(local_write_ops, std::sync::atomic::Ordering::Relaxed);
+            metrics
+                .write_bytes
+                .fetch_add(local_write_bytes, std::sync::atomic::Ordering::Relaxed);
+            latency.flush_average(metrics);
             local_ops = 0;
             local_bytes = 0;
+            local_write_ops = 0;
+            local_write_bytes = 0;
         }
     }
 
@@ -415,22 +1107,53 @@ pub fn worker_iocp(
         metrics
             .total_bytes
             .fetch_add(local_bytes, std::sync::atomic::Ordering::Relaxed);
+        metrics
+            .write_ops
+            .fetch_add(local_write_ops, std::sync::atomic::Ordering::Relaxed);
+        metrics
+            .write_bytes
+            .fetch_add(local_write_bytes, std::sync::atomic::Ordering::Relaxed);
     }
 
-    // Cancel any outstanding I/Os
-    unsafe { CancelIo(dev.handle) };
+    inflight.flush(metrics);
+    verifier.flush(metrics);
+    latency.finish(metrics);
 
-    // Drain remaining completions
-    loop {
+    // Cancel the I/Os still in flight and wait for each to post its completion:
+    // until then the kernel may still write into its OVERLAPPED and buffer. A
+    // cancelled I/O completes with ERROR_OPERATION_ABORTED (a zero return with
+    // a non-null OVERLAPPED) and counts as reaped like any other.
+    unsafe { CancelIo(dev.handle) };
+    let mut outstanding = inflight.in_flight();
+    while outstanding > 0 {
         let mut bytes: u32 = 0;
         let mut key: usize = 0;
         let mut olp: *mut OVERLAPPED = ptr::null_mut();
-        let r = unsafe { GetQueuedCompletionStatus(iocp, &mut bytes, &mut key, &mut olp, 0) };
-        if r == 0 || olp.is_null() {
-            break;
+        unsafe { GetQueuedCompletionStatus(iocp, &mut bytes, &mut key, &mut olp, INFINITE) };
+        if olp.is_null() {
+            // The port itself failed, so the remaining completions can never be
+            // reaped; leak what they target rather than free it under the kernel
+            let err = io::Error::last_os_error();
+            std::mem::forget(buffers);
+            std::mem::forget(overlappeds);
+            return Err(err);
         }
+        outstanding -= 1;
     }
 
     unsafe { CloseHandle(iocp) };
-    Ok(())
+    fsync.finish(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapped_at_splits_offsets_past_4gb() {
+        let overlapped = overlapped_at(0x1_2345_6000);
+        let fields = unsafe { overlapped.Anonymous.Anonymous };
+        assert_eq!(fields.Offset, 0x2345_6000);
+        assert_eq!(fields.OffsetHigh, 0x1);
+    }
 }
@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+/// How long the endpoint gets to accept the connection, and then each write;
+/// one that stalls longer is treated as a lost connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the end of the run waits for the queued lines to go out
+const FINISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Newline-delimited JSON feed of progress intervals and final results,
+/// written to a TCP endpoint by a background thread.
+pub struct ResultStream {
+    tx: Option<Sender<String>>,
+    /// Disconnected once the writer thread has exited
+    done: Option<Receiver<()>>,
+}
+
+/// Cheap handle used by `run_test` to queue lines; sending never blocks
+#[derive(Clone)]
+pub struct StreamSender {
+    tx: Sender<String>,
+    host: String,
+}
+
+impl ResultStream {
+    /// Start the feed. The connection is made on the writer thread so a slow
+    /// or unreachable endpoint never delays the benchmark; on any connection
+    /// error the feed is dropped with a warning and the run continues.
+    pub fn connect(addr: &str) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+        let addr = addr.to_string();
+        std::thread::spawn(move || {
+            let _done = done_tx;
+            write_feed(&addr, rx);
+        });
+        Self {
+            tx: Some(tx),
+            done: Some(done_rx),
+        }
+    }
+
+    pub fn sender(&self) -> Option<StreamSender> {
+        self.tx.as_ref().map(|tx| StreamSender {
+            tx: tx.clone(),
+            host: super::hostname().unwrap_or_else(|| "unknown".to_string()),
+        })
+    }
+
+    /// Close the feed once every queued line has been written, or give up on
+    /// the rest after `FINISH_TIMEOUT` so a stalled endpoint can't hold up the
+    /// end of the run
+    pub fn finish(mut self) {
+        self.tx.take();
+        if let Some(done) = self.done.take() {
+            if done.recv_timeout(FINISH_TIMEOUT) == Err(RecvTimeoutError::Timeout) {
+                warn!(
+                    "  Warning: result stream still writing after {}s; dropping the rest of the feed",
+                    FINISH_TIMEOUT.as_secs()
+                );
+            }
+        }
+    }
+}
+
+impl StreamSender {
+    /// Queue one record; `kind` is "progress" or "result"
+    pub fn send(&self, kind: &str, test: &str, mut record: serde_json::Value) {
+        if let Some(obj) = record.as_object_mut() {
+            obj.insert("type".into(), kind.into());
+            obj.insert("host".into(), self.host.clone().into());
+            obj.insert("test".into(), test.into());
+        }
+        // The writer may have gone away after a connection loss; that's fine
+        let _ = self.tx.send(record.to_string());
+    }
+}
+
+/// Connect to the first address `addr` resolves to that answers in time
+fn connect(addr: &str) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for sock_addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&sock_addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address found")))
+}
+
+fn write_feed(addr: &str, rx: Receiver<String>) {
+    let connected = connect(addr).and_then(|stream| {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        Ok(stream)
+    });
+    let mut stream = match connected {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("  Warning: result stream {} unavailable: {}", addr, e);
+            return;
+        }
+    };
+
+    for mut line in rx {
+        line.push('\n');
+        if let Err(e) = stream.write_all(line.as_bytes()) {
+            warn!("  Warning: result stream {} lost: {}", addr, e);
+            return;
+        }
+    }
+}
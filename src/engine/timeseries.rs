@@ -0,0 +1,73 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "test,block_size_kb,elapsed_s,mbps,iops,avg_lat_us\n";
+
+/// Cumulative counters at one progress tick
+#[derive(Clone, Copy, Default)]
+pub struct Totals {
+    pub elapsed: f64,
+    pub ops: u64,
+    pub bytes: u64,
+    pub lat_sum_ns: u64,
+    pub lat_samples: u64,
+}
+
+/// `--timeseries` CSV log. Each row covers only the interval since the previous
+/// row, so a drop in speed mid-run shows up instead of being averaged away.
+/// Every test appends to the same file; rows are written straight through.
+pub struct TimeSeriesLog {
+    file: File,
+    path: PathBuf,
+    test: String,
+    block_size_kb: u64,
+    last: Totals,
+}
+
+impl TimeSeriesLog {
+    /// Open `path` for appending, writing the header if the file is new or empty
+    pub fn open(path: &Path, test: &str, block_size_kb: u64) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(HEADER.as_bytes())?;
+        }
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            test: test.to_string(),
+            block_size_kb,
+            last: Totals::default(),
+        })
+    }
+
+    /// Append one row for the interval ending at `now`
+    pub fn record(&mut self, now: Totals) {
+        let secs = now.elapsed - self.last.elapsed;
+        if secs <= 0.0 {
+            return;
+        }
+        let bytes = (now.bytes - self.last.bytes) as f64;
+        let ops = (now.ops - self.last.ops) as f64;
+        let samples = now.lat_samples - self.last.lat_samples;
+        let avg_lat_us = if samples > 0 {
+            (now.lat_sum_ns - self.last.lat_sum_ns) as f64 / samples as f64 / 1_000.0
+        } else {
+            0.0
+        };
+        self.last = now;
+
+        let row = format!(
+            "{},{},{:.3},{:.2},{:.0},{:.2}\n",
+            self.test,
+            self.block_size_kb,
+            now.elapsed,
+            bytes / secs / (1024.0 * 1024.0),
+            ops / secs,
+            avg_lat_us
+        );
+        if let Err(e) = self.file.write_all(row.as_bytes()) {
+            warn!("  Warning: time series {} not written: {}", self.path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+const HEADER: &str = "test,block_size_kb,queue_depth,thread,time_ns,latency_ns,op\n";
+
+/// Samples a worker collects before handing them to the writer
+const BATCH: usize = 4096;
+
+/// Batches queued for the writer before workers start dropping them
+const QUEUED_BATCHES: usize = 64;
+
+/// One sampled I/O
+struct TraceRecord {
+    thread: u32,
+    time_ns: u64,
+    latency_ns: u64,
+    write: bool,
+}
+
+/// `--latency-trace` CSV log: a row for every sampled latency, written by a
+/// background thread. Every test appends to the same file.
+pub struct LatencyTrace {
+    tx: Option<SyncSender<Vec<TraceRecord>>>,
+    handle: Option<JoinHandle<io::Result<u64>>>,
+    epoch: Instant,
+    dropped: Arc<AtomicU64>,
+}
+
+/// A worker's handle on the trace; sending never blocks
+#[derive(Clone)]
+pub struct TraceSender {
+    tx: SyncSender<Vec<TraceRecord>>,
+    epoch: Instant,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LatencyTrace {
+    /// Open `path` for appending, writing the header if the file is new or
+    /// empty, and start the writer. Times in the trace count from here.
+    pub fn open(path: &Path, test: &str, block_size_kb: u64, queue_depth: u32) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(HEADER.as_bytes())?;
+        }
+        let prefix = format!("{},{},{}", test, block_size_kb, queue_depth);
+        let (tx, rx) = mpsc::sync_channel(QUEUED_BATCHES);
+        let handle = std::thread::spawn(move || write_trace(file, &prefix, rx));
+        Ok(Self {
+            tx: Some(tx),
+            handle: Some(handle),
+            epoch: Instant::now(),
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn sender(&self) -> Option<TraceSender> {
+        self.tx.as_ref().map(|tx| TraceSender {
+            tx: tx.clone(),
+            epoch: self.epoch,
+            dropped: Arc::clone(&self.dropped),
+        })
+    }
+
+    /// Wait for every queued sample to be written, once the workers have
+    /// stopped. Returns the rows written and the samples dropped because the
+    /// writer fell behind.
+    pub fn finish(mut self) -> io::Result<(u64, u64)> {
+        // The metrics still hold senders, so end the log explicitly
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Vec::new());
+        }
+        let written = match self.handle.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result?,
+            _ => 0,
+        };
+        Ok((written, self.dropped.load(Ordering::Relaxed)))
+    }
+}
+
+/// A worker's unsent samples. Nothing is kept until `start`, so a warmup
+/// window leaves no rows.
+pub struct TraceBuffer {
+    sender: TraceSender,
+    thread: u32,
+    records: Vec<TraceRecord>,
+    active: bool,
+}
+
+impl TraceBuffer {
+    pub fn new(sender: TraceSender, thread: u32, active: bool) -> Self {
+        Self {
+            sender,
+            thread,
+            records: Vec::with_capacity(BATCH),
+            active,
+        }
+    }
+
+    /// Start keeping samples (the end of a warmup window)
+    pub fn start(&mut self) {
+        self.records.clear();
+        self.active = true;
+    }
+
+    pub fn push(&mut self, latency_ns: u64, write: bool) {
+        if !self.active {
+            return;
+        }
+        self.records.push(TraceRecord {
+            thread: self.thread,
+            time_ns: self.sender.epoch.elapsed().as_nanos() as u64,
+            latency_ns,
+            write,
+        });
+        if self.records.len() >= BATCH {
+            let batch = std::mem::replace(&mut self.records, Vec::with_capacity(BATCH));
+            // A full queue costs the batch, never the worker's time; a writer
+            // that has gone away already said why
+            if let Err(TrySendError::Full(batch)) = self.sender.tx.try_send(batch) {
+                self.sender.dropped.fetch_add(batch.len() as u64, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Hand over what is left; the test is over, so this may wait
+    pub fn finish(self) {
+        if !self.records.is_empty() {
+            let _ = self.sender.tx.send(self.records);
+        }
+    }
+}
+
+fn write_trace(file: File, prefix: &str, rx: Receiver<Vec<TraceRecord>>) -> io::Result<u64> {
+    let mut out = BufWriter::with_capacity(1 << 20, file);
+    let mut written = 0;
+    // An empty batch marks the end of the test
+    for batch in rx.iter().take_while(|batch| !batch.is_empty()) {
+        for r in &batch {
+            writeln!(
+                out,
+                "{},{},{},{},{}",
+                prefix,
+                r.thread,
+                r.time_ns,
+                r.latency_ns,
+                if r.write { "write" } else { "read" }
+            )?;
+        }
+        written += batch.len() as u64;
+    }
+    out.flush()?;
+    Ok(written)
+}
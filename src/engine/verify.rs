@@ -0,0 +1,129 @@
+use std::sync::atomic::Ordering;
+
+use super::Metrics;
+
+/// Stamps are laid out per 4 KiB chunk, so a read of any 4K-aligned size can
+/// check what a write of a different size left behind
+pub const CHUNK: usize = 4096;
+
+/// "4CVERIFY" - marks a chunk written by a verifying write test
+const MAGIC: u64 = 0x3443_5645_5249_4659;
+const HEADER_WORDS: usize = 3;
+
+/// Deterministic payload for one chunk: xorshift64* seeded by where the chunk
+/// lives and which write produced it
+fn payload_words(offset: u64, seq: u64) -> impl Iterator<Item = u64> {
+    let mut state = (offset ^ seq.rotate_left(32) ^ MAGIC) | 1;
+    std::iter::repeat_with(move || {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+fn read_word(chunk: &[u8], idx: usize) -> u64 {
+    u64::from_le_bytes(chunk[idx * 8..idx * 8 + 8].try_into().unwrap())
+}
+
+/// Fill `buf`, about to be written at `offset`, with self-describing chunks:
+/// a header of magic, chunk offset and `seq`, then a payload derived from both.
+/// Any later read can validate a chunk without knowing which write landed last.
+fn stamp(buf: &mut [u8], offset: u64, seq: u64) {
+    for (i, chunk) in buf.chunks_exact_mut(CHUNK).enumerate() {
+        let chunk_offset = offset + (i * CHUNK) as u64;
+        let header = [MAGIC, chunk_offset, seq];
+        let words = header.into_iter().chain(payload_words(chunk_offset, seq));
+        for (bytes, word) in chunk.chunks_exact_mut(8).zip(words) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+}
+
+/// Per-worker verification counts, published once when the worker stops
+#[derive(Default)]
+struct VerifyTally {
+    checked: u64,
+    unwritten: u64,
+    corrupt: u64,
+}
+
+impl VerifyTally {
+    /// Validate every chunk of `buf`, just read from `offset`
+    fn check(&mut self, buf: &[u8], offset: u64) {
+        for (i, chunk) in buf.chunks_exact(CHUNK).enumerate() {
+            let chunk_offset = offset + (i * CHUNK) as u64;
+            self.checked += 1;
+
+            // No stamp: never written by a verifying test (not an error)
+            if read_word(chunk, 0) != MAGIC {
+                self.unwritten += 1;
+                continue;
+            }
+            // A stamp for another offset means the data landed in the wrong place
+            if read_word(chunk, 1) != chunk_offset {
+                self.corrupt += 1;
+                continue;
+            }
+            let seq = read_word(chunk, 2);
+            let intact = (HEADER_WORDS..CHUNK / 8)
+                .zip(payload_words(chunk_offset, seq))
+                .all(|(idx, expected)| read_word(chunk, idx) == expected);
+            if !intact {
+                self.corrupt += 1;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn flush(&self, metrics: &Metrics) {
+        metrics.verify_checked.fetch_add(self.checked, Ordering::Relaxed);
+        metrics.verify_unwritten.fetch_add(self.unwritten, Ordering::Relaxed);
+        metrics.verify_corrupt.fetch_add(self.corrupt, Ordering::Relaxed);
+    }
+}
+
+/// Per-worker `--verify` state: stamps outgoing writes and checks completed reads
+pub struct Verifier {
+    enabled: bool,
+    next_seq: u64,
+    tally: VerifyTally,
+}
+
+impl Verifier {
+    pub fn new(config: &super::TestConfig, thread_id: u32) -> Self {
+        Self {
+            enabled: config.verify,
+            // Thread id in the high bits keeps sequence numbers unique across workers
+            next_seq: (thread_id as u64) << 40,
+            tally: VerifyTally::default(),
+        }
+    }
+
+    /// Stamp a buffer about to be written at `offset`
+    pub fn before_write(&mut self, buf: &mut [u8], offset: u64) {
+        if self.enabled {
+            stamp(buf, offset, self.next_seq);
+            self.next_seq += 1;
+        }
+    }
+
+    /// Check the bytes a read returned from `offset`
+    pub fn after_read(&mut self, buf: &[u8], offset: u64) {
+        if self.enabled {
+            self.tally.check(buf, offset);
+        }
+    }
+
+    /// Forget the counts so far (the end of a warmup window)
+    pub fn clear(&mut self) {
+        self.tally.clear();
+    }
+
+    pub fn flush(&self, metrics: &Metrics) {
+        self.tally.flush(metrics);
+    }
+}
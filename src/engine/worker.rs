@@ -1,38 +1,237 @@
 use std::io;
 use std::sync::atomic::AtomicBool;
 
-use super::Metrics;
+use super::{Metrics, TestConfig};
 
 /// Main worker entry point - dispatches to platform-specific async I/O
 pub fn run_worker(
-    _thread_id: u32,
+    thread_id: u32,
     device_path: &str,
-    io_size: u64,
-    queue_depth: u32,
-    is_write: bool,
     test_range: u64,
+    config: &TestConfig,
     stop: &AtomicBool,
     metrics: &Metrics,
 ) -> io::Result<()> {
     #[cfg(windows)]
     {
-        super::platform_windows::worker_iocp(
-            device_path, io_size, queue_depth, is_write, test_range, stop, metrics,
-        )
+        super::platform_windows::worker_iocp(thread_id, device_path, test_range, config, stop, metrics)
     }
 
     #[cfg(target_os = "linux")]
     {
-        super::platform_linux::worker_io_uring(
-            device_path, io_size, queue_depth, is_write, test_range, stop, metrics,
-        )
+        super::platform_linux::worker_io_uring(thread_id, device_path, test_range, config, stop, metrics)
     }
 
     #[cfg(not(any(windows, target_os = "linux")))]
     {
-        Err(io::Error::new(
-            io::ErrorKind::Unsupported,
-            "Platform not supported",
-        ))
+        sync::worker_sync(thread_id, device_path, test_range, config, stop, metrics)
+    }
+}
+
+/// Portable fallback for platforms without an async I/O path here (macOS):
+/// blocking `read_at_raw`/`write_at_raw` calls, with queue depth made up by
+/// threads instead of by requests in flight
+#[cfg(not(any(windows, target_os = "linux")))]
+mod sync {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    use rand::rngs::StdRng;
+
+    use super::super::verify::Verifier;
+    use super::super::{
+        buffer_alignment, flush_handle, open_device_read, open_device_write, read_at_raw, write_at_raw, AlignedBuf,
+        DeviceHandle, FsyncSchedule, InflightMix, LatencySampler, Metrics, OffsetGenerator, Pacer, SlotBuffers,
+        TestConfig,
+    };
+
+    /// Everything a worker's lanes share: where the next I/O goes, the pacing
+    /// schedule and what has been measured. Lanes hold the lock only between
+    /// I/Os, never across one.
+    struct WorkerState {
+        rng: StdRng,
+        offsets: OffsetGenerator,
+        pacer: Option<Pacer>,
+        inflight: InflightMix,
+        latency: LatencySampler,
+        fsync: FsyncSchedule,
+        verifier: Verifier,
+        warming: bool,
+        op_count: u64,
+        local_ops: u64,
+        local_bytes: u64,
+        local_write_ops: u64,
+        local_write_bytes: u64,
+    }
+
+    impl WorkerState {
+        /// Push the batched counters to the shared metrics
+        fn publish(&mut self, metrics: &Metrics) {
+            metrics.total_ops.fetch_add(self.local_ops, Ordering::Relaxed);
+            metrics.total_bytes.fetch_add(self.local_bytes, Ordering::Relaxed);
+            metrics.write_ops.fetch_add(self.local_write_ops, Ordering::Relaxed);
+            metrics.write_bytes.fetch_add(self.local_write_bytes, Ordering::Relaxed);
+            self.local_ops = 0;
+            self.local_bytes = 0;
+            self.local_write_ops = 0;
+            self.local_write_bytes = 0;
+        }
+    }
+
+    /// Synchronous worker. Each I/O blocks its thread, so the worker runs one
+    /// lane per queue slot on scoped threads, each keeping one I/O in flight;
+    /// queue depth then means the same concurrency as on the async workers.
+    pub fn worker_sync(
+        thread_id: u32,
+        device_path: &str,
+        test_range: u64,
+        config: &TestConfig,
+        stop: &AtomicBool,
+        metrics: &Metrics,
+    ) -> io::Result<()> {
+        let dev = if config.has_writes() {
+            open_device_write(device_path, config.open_flags)?
+        } else {
+            open_device_read(device_path, config.open_flags)?
+        };
+
+        let qd = config.queue_depth.max(1) as usize;
+        let mut rng = config.worker_rng(thread_id);
+        let mut bufs =
+            SlotBuffers::new(config, thread_id, qd, buffer_alignment(device_path), |_| {}).into_slots(qd);
+
+        let state = Mutex::new(WorkerState {
+            offsets: OffsetGenerator::new(config, thread_id, &mut rng, test_range),
+            rng,
+            pacer: Pacer::new(config, thread_id),
+            inflight: InflightMix::default(),
+            latency: LatencySampler::new(config, thread_id, metrics),
+            fsync: FsyncSchedule::new(config),
+            verifier: Verifier::new(config, thread_id),
+            warming: metrics.warming_up.load(Ordering::Relaxed),
+            op_count: 0,
+            local_ops: 0,
+            local_bytes: 0,
+            local_write_ops: 0,
+            local_write_bytes: 0,
+        });
+
+        std::thread::scope(|s| {
+            let (dev, state) = (&dev, &state);
+            let mut lanes = bufs.iter_mut().enumerate();
+            let (_, first) = lanes.next().expect("queue depth is at least 1");
+            for (slot, buf) in lanes {
+                s.spawn(move || run_lane(slot, buf, dev, config, stop, metrics, state));
+            }
+            run_lane(0, first, dev, config, stop, metrics, state);
+        });
+
+        let mut st = state.into_inner().unwrap();
+        st.publish(metrics);
+        st.inflight.flush(metrics);
+        st.verifier.flush(metrics);
+        st.latency.finish(metrics);
+        st.fsync.finish(metrics)
+    }
+
+    /// One lane: issue an I/O on `slot`, wait for it, account for it, repeat
+    fn run_lane(
+        slot: usize,
+        buf: &mut AlignedBuf,
+        dev: &DeviceHandle,
+        config: &TestConfig,
+        stop: &AtomicBool,
+        metrics: &Metrics,
+        state: &Mutex<WorkerState>,
+    ) {
+        let batch_size: u64 = 256;
+        let sample_rate = config.latency_sample_rate.max(1) as u64;
+
+        loop {
+            let (off, write, len) = {
+                let mut guard = state.lock().unwrap();
+                if stop.load(Ordering::Relaxed) || guard.fsync.failed() {
+                    return;
+                }
+                let st = &mut *guard;
+                if st.warming && !metrics.warming_up.load(Ordering::Relaxed) {
+                    // Warmup is over: discard what it measured and start counting
+                    st.warming = false;
+                    st.local_ops = 0;
+                    st.local_bytes = 0;
+                    st.local_write_ops = 0;
+                    st.local_write_bytes = 0;
+                    st.latency.clear();
+                    st.inflight.clear();
+                    st.fsync.clear();
+                    st.verifier.clear();
+                }
+
+                // With --target-iops, wait (without the lock) for this worker's next turn
+                if let Some(p) = &mut st.pacer {
+                    if !p.take() {
+                        let due = p.due();
+                        drop(guard);
+                        Pacer::sleep_until(due, stop);
+                        continue;
+                    }
+                }
+
+                let off = st.offsets.next(slot, &mut st.rng);
+                let write = config.next_is_write(&mut st.rng);
+                let len = config.next_io_len(&mut st.rng);
+                buf.set_io_len(len as usize);
+                st.inflight.issue(write);
+                st.inflight.sample();
+                if write {
+                    st.verifier.before_write(buf.as_mut_slice(), off);
+                }
+                (off, write, len)
+            };
+
+            let start = Instant::now();
+            let result = if write {
+                write_at_raw(dev, buf, off)
+            } else {
+                read_at_raw(dev, buf, off)
+            };
+            let latency_ns = start.elapsed().as_nanos() as u64;
+
+            let mut guard = state.lock().unwrap();
+            let st = &mut *guard;
+            st.inflight.complete(write);
+            let bytes = match result {
+                Ok(n) => n as u64,
+                Err(e) => {
+                    metrics.record_io_error(e.raw_os_error());
+                    continue;
+                }
+            };
+            if bytes < len {
+                metrics.record_io_error(None);
+            }
+            st.op_count += 1;
+            if st.op_count.is_multiple_of(sample_rate) {
+                st.latency.record(latency_ns, write);
+            }
+
+            st.local_ops += 1;
+            st.local_bytes += bytes;
+            if write {
+                st.local_write_ops += 1;
+                st.local_write_bytes += bytes;
+                st.fsync.write_done(|| flush_handle(dev));
+            } else {
+                st.verifier.after_read(&buf.as_slice()[..bytes as usize], off);
+            }
+
+            // Batch update metrics
+            if !st.warming && st.local_ops >= batch_size {
+                st.publish(metrics);
+                st.latency.flush_average(metrics);
+            }
+        }
     }
 }
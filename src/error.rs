@@ -0,0 +1,76 @@
+use std::io;
+use thiserror::Error;
+
+/// Why a test could not run. The device-level variants are the failures a
+/// script most often wants to tell apart, and each has its own exit status.
+#[derive(Debug, Error)]
+pub enum BenchError {
+    #[error("device {device} not found: {source}")]
+    NotFound { device: String, source: io::Error },
+
+    #[error("permission denied on {device}: {source}")]
+    PermissionDenied { device: String, source: io::Error },
+
+    #[error("device {device} is busy (mounted, or held open exclusively by another process): {source}")]
+    DeviceBusy { device: String, source: io::Error },
+
+    /// Nothing could be measured; `first` is why the first test failed
+    #[error("{}", if *attempted == 1 { "the test failed".to_string() } else { format!("all {} tests failed", attempted) })]
+    AllTestsFailed {
+        attempted: usize,
+        #[source]
+        first: Box<BenchError>,
+    },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Exit status when a run fails for a reason without its own code
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_PERMISSION_DENIED: i32 = 4;
+pub const EXIT_DEVICE_BUSY: i32 = 5;
+
+impl BenchError {
+    /// Classify an error raised while opening or using `device`
+    pub fn device(device: &str, source: io::Error) -> Self {
+        let device = device.to_string();
+        if is_busy(&source) {
+            return BenchError::DeviceBusy { device, source };
+        }
+        match source.kind() {
+            io::ErrorKind::NotFound => BenchError::NotFound { device, source },
+            io::ErrorKind::PermissionDenied => BenchError::PermissionDenied { device, source },
+            _ => BenchError::Io(source),
+        }
+    }
+
+    /// Process exit status for a run that ends on this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BenchError::NotFound { .. } => EXIT_NOT_FOUND,
+            BenchError::PermissionDenied { .. } => EXIT_PERMISSION_DENIED,
+            BenchError::DeviceBusy { .. } => EXIT_DEVICE_BUSY,
+            BenchError::AllTestsFailed { first, .. } => first.exit_code(),
+            BenchError::Io(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+fn is_busy(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::ResourceBusy {
+        return true;
+    }
+    // Another process holding the volume or a mounted filesystem shows up
+    // as a sharing or lock violation rather than ERROR_BUSY
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::{ERROR_BUSY, ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION};
+        if let Some(code) = err.raw_os_error() {
+            let code = code as u32;
+            return code == ERROR_SHARING_VIOLATION || code == ERROR_LOCK_VIOLATION || code == ERROR_BUSY;
+        }
+    }
+    false
+}
@@ -0,0 +1,28 @@
+//! 4Corners disk benchmark as a library. [`run_benchmark`] prepares the
+//! devices, runs the tests a [`BenchmarkConfig`] selects and returns the
+//! [`BenchmarkReport`]. Nothing is printed unless [`output::set_verbose`] turns
+//! output on; the `4c` binary does, and is otherwise a thin wrapper.
+//!
+//! ```no_run
+//! let mut config = fourcorners::BenchmarkConfig::default();
+//! config.device = vec!["/dev/nvme0n1".to_string()];
+//! config.tests = "read-iops".to_string();
+//! config.duration = 10;
+//! let report = fourcorners::run_benchmark(config)?;
+//! println!("{}", report.to_json());
+//! # Ok::<(), fourcorners::BenchError>(())
+//! ```
+
+#[macro_use]
+pub mod output;
+
+pub mod engine;
+pub mod error;
+pub mod report;
+pub mod settings;
+pub mod suite;
+
+pub use error::BenchError;
+pub use report::BenchmarkReport;
+pub use settings::BenchmarkConfig;
+pub use suite::run_benchmark;
@@ -1,182 +1,551 @@
 mod cli;
-mod engine;
-mod report;
+mod config;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use cli::Args;
-use engine::TestConfig;
-use report::BenchmarkReport;
+use config::Config;
+use fourcorners::engine::{self, DeviceKind};
+use fourcorners::error;
+use fourcorners::report::{BenchmarkReport, NumberFormat};
+use fourcorners::suite::{self, number_format, open_flags, test_config};
+use fourcorners::{info, output};
+use std::io::{IsTerminal, Write};
 use std::path::Path;
 
-/// Parse device argument(s) and normalize Windows paths
-fn parse_devices(device_args: Vec<String>) -> Vec<String> {
-    let mut devices = Vec::new();
-
-    for arg in device_args {
-        // Handle comma-separated values
-        for part in arg.split(',') {
-            let trimmed = part.trim();
-            if !trimmed.is_empty() {
-                #[cfg(windows)]
-                let normalized = engine::normalize_device_path(trimmed);
-                #[cfg(not(windows))]
-                let normalized = trimmed.to_string();
-
-                devices.push(normalized);
+/// The devices under test from `--device` and `--devices-file`; a bad list ends the run
+fn parse_devices(args: &Args) -> Vec<String> {
+    suite::resolve_devices(&args.bench).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// The devices `--prep`, `--fill-percent` or `--prep-devices` will overwrite;
+/// a `--prep-devices` entry that isn't under test ends the run
+fn prep_devices(args: &Args, devices: &[String]) -> Vec<String> {
+    suite::prep_devices(&args.bench, devices).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// One numbered line per disk: index, path, size and model
+fn print_disks(disks: &[engine::DiskInfo], nf: NumberFormat) {
+    for (i, disk) in disks.iter().enumerate() {
+        let line = format!(
+            "  [{}] {:<24} {:>10} {:<3}  {}",
+            i + 1,
+            disk.path,
+            nf.size(disk.size_bytes as f64, 1),
+            nf.size_unit(),
+            disk.model.as_deref().unwrap_or("")
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+/// `4c list`: print the detected disks and exit
+fn list_disks(nf: NumberFormat) {
+    let disks = engine::list_devices();
+    if disks.is_empty() {
+        #[cfg(windows)]
+        let hint = " (drives are only visible from an elevated prompt)";
+        #[cfg(target_os = "macos")]
+        let hint = " (disks are only visible with sudo)";
+        #[cfg(not(any(windows, target_os = "macos")))]
+        let hint = "";
+        println!("No disks found{}", hint);
+        return;
+    }
+    println!("{:<6}{:<24} {:>10} {:<3}  Model", "  #", "Path", "Size", "");
+    print_disks(&disks, nf);
+}
+
+/// Present a numbered menu of detected disks and read the user's selection.
+/// Used when no device was given and the session is interactive.
+fn pick_devices(nf: NumberFormat) -> Vec<String> {
+    let disks = engine::list_devices();
+    if disks.is_empty() {
+        eprintln!("Error: No devices found - specify one with --device");
+        std::process::exit(1);
+    }
+
+    println!("No device specified. Detected devices:");
+    print_disks(&disks, nf);
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("Select device(s) to test (e.g. 1 or 1,3): ");
+        let _ = std::io::stdout().flush();
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+            eprintln!("Error: No device selected");
+            std::process::exit(1);
+        }
+
+        let picks: Option<Vec<String>> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                let n: usize = s.parse().ok()?;
+                disks.get(n.checked_sub(1)?).map(|d| d.path.clone())
+            })
+            .collect();
+
+        match picks {
+            Some(mut picks) if !picks.is_empty() => {
+                picks.dedup();
+                println!();
+                return picks;
             }
+            _ => println!("Enter one or more numbers between 1 and {}", disks.len()),
         }
     }
+}
+
+/// Whether overwriting `device` is harmless: an existing regular file, or the
+/// file `--create-file` is about to create
+fn is_disposable(args: &Args, devices: &[String], device: &str) -> bool {
+    (args.bench.create_file && devices.first().is_some_and(|d| d == device))
+        || engine::classify_device(device) == DeviceKind::File
+}
+
+/// Ask before write tests or `--prep` overwrite a raw device. `--yes` skips the
+/// prompt; without a terminal to ask on, the run stops instead.
+fn confirm_destructive(args: &Args, devices: &[String], writes: bool) {
+    let prep_devices = prep_devices(args, devices);
+    let targets: Vec<&String> = devices
+        .iter()
+        .filter(|d| writes || args.bench.trim || prep_devices.contains(d))
+        .filter(|d| !is_disposable(args, devices, d))
+        .collect();
+    refuse_mounted(args, &targets);
+    if targets.is_empty() || args.yes {
+        return;
+    }
 
-    if devices.is_empty() {
-        eprintln!("Error: No valid devices specified");
+    if !std::io::stdin().is_terminal() {
+        eprintln!(
+            "Error: refusing to overwrite {} without confirmation; pass --yes to run non-interactively",
+            targets.iter().map(|d| d.as_str()).collect::<Vec<_>>().join(", ")
+        );
         std::process::exit(1);
     }
 
-    devices
+    for device in targets {
+        // On stderr, so a prompt never lands in `--quiet` JSON
+        eprint!("This will destroy data on {}, continue? [y/N] ", device);
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).is_err()
+            || !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+        {
+            eprintln!("Aborted");
+            std::process::exit(1);
+        }
+    }
+    info!();
 }
 
-fn main() {
-    let args = Args::parse();
+/// Stop before writing to a device that has filesystems mounted on it, unless
+/// `--force`. A check that cannot run only warns.
+fn refuse_mounted(args: &Args, targets: &[&String]) {
+    if args.force {
+        return;
+    }
+    for device in targets {
+        match engine::mounted_volumes(device) {
+            Ok(mounts) if mounts.is_empty() => {}
+            Ok(mounts) => {
+                eprintln!(
+                    "Error: {} has mounted filesystems ({}); writing to it would corrupt them. \
+                     Take them offline first (mountvol <path> /p), or pass --force",
+                    device,
+                    mounts.join(", ")
+                );
+                std::process::exit(error::EXIT_DEVICE_BUSY);
+            }
+            Err(e) => eprintln!("Warning: cannot check {} for mounted filesystems: {}", device, e),
+        }
+    }
+}
+
+/// Create `--output-dir` if needed and make sure a report can be written there,
+/// so a bad directory stops the run before the tests rather than after them
+fn prepare_output_dir(dir: &Path) {
+    let probe = dir.join(format!(".4c-write-test-{}", std::process::id()));
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b""))
+        .and_then(|_| std::fs::remove_file(&probe));
+    if let Err(e) = result {
+        eprintln!("Error: cannot write reports to {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+}
 
-    println!("4Corners Disk Benchmark (Rust)");
-    println!("==============================");
-    println!();
+/// Resolve and open every device read-only, then print what a real run would
+/// do to each one. Nothing is created, prepped or written.
+fn run_dry_run(args: &Args, devices: &[String], tests: &[(bool, &str, bool)]) {
+    let prep_devices = prep_devices(args, devices);
+    let writers: Vec<&str> = tests.iter().filter(|t| t.0 && t.2).map(|t| t.1).collect();
+    let mut failed = 0;
 
-    // Parse and normalize device list
-    let devices = parse_devices(args.device);
-    let device_display = if devices.len() == 1 {
-        devices[0].clone()
+    info!("Dry run: no tests will be run and nothing will be written");
+    info!();
+    for (i, device) in devices.iter().enumerate() {
+        if args.bench.create_file && i == 0 && !Path::new(device).exists() {
+            info!("  • {}: file would be created ({} GiB)", device, args.bench.file_size);
+            continue;
+        }
+
+        let kind = engine::classify_device(device);
+        let opened = engine::device_size(device, args.bench.device_size)
+            .and_then(|size| engine::open_device_read(device, open_flags(&args.bench)).map(|_| size));
+        let nf = number_format(&args.bench);
+        match opened {
+            Ok(size) => info!(
+                "  ✓ {}: {}, {} {} ({} bytes), opens for direct read",
+                device,
+                kind.as_str(),
+                nf.size(size as f64, 2),
+                nf.size_unit(),
+                size
+            ),
+            Err(e) => {
+                info!("  ✗ {}: {}", device, e);
+                failed += 1;
+                continue;
+            }
+        }
+
+        if args.bench.trim {
+            info!("      --trim would discard {}", if args.bench.range_len.is_some() || args.bench.range_start > 0 { "the test range" } else { "the whole device" });
+        }
+        if prep_devices.contains(device) {
+            info!("      --prep would overwrite the whole device");
+        }
+        if !writers.is_empty() {
+            info!("      would be overwritten by: {}", writers.join(", "));
+        }
+    }
+
+    let selected: Vec<&str> = tests.iter().filter(|t| t.0).map(|t| t.1).collect();
+    info!();
+    info!("Tests selected: {}", if selected.is_empty() { "none".to_string() } else { selected.join(", ") });
+
+    if failed > 0 {
+        eprintln!("{} device{} could not be opened", failed, if failed == 1 { "" } else { "s" });
+        std::process::exit(1);
+    }
+}
+
+/// Verify every device can be opened with direct I/O and read, then exit
+fn run_device_check(args: &Args, devices: &[String], check_write: bool) {
+    let io_size = args.bench.read_iops_bs as u64 * 1024;
+    let mut failed = 0;
+    let nf = number_format(&args.bench);
+
+    info!("Checking {} device{}...", devices.len(), if devices.len() == 1 { "" } else { "s" });
+    for device in devices {
+        match engine::check_device(device, args.bench.device_size, open_flags(&args.bench), io_size, check_write) {
+            Ok(size) => info!(
+                "  ✓ {}: {} {}, aligned {}KB direct read OK{}",
+                device,
+                nf.size(size as f64, 2),
+                nf.size_unit(),
+                io_size / 1024,
+                if check_write { ", writable" } else { "" }
+            ),
+            Err(e) => {
+                info!("  ✗ {}: {}", device, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{} device{} failed the check", failed, if failed == 1 { "" } else { "s" });
+        std::process::exit(1);
+    }
+    info!("All devices passed");
+}
+
+/// The --align-check offset shift: --misalign-bytes, or else the largest
+/// logical sector size, which is the smallest shift direct I/O accepts. A shift
+/// that is a multiple of a device's physical sector is still aligned on it, so
+/// it is rejected; None when a device has equal logical and physical sectors
+/// and so cannot be misaligned at all.
+fn misalign_shift(args: &Args, devices: &[String]) -> Option<u64> {
+    let sectors: Vec<_> = devices
+        .iter()
+        .filter_map(|d| engine::get_sector_size(d).map(|s| (d, s)))
+        .collect();
+    if let Some((device, sector)) = sectors.iter().find(|(_, s)| s.logical == s.physical) {
+        info!(
+            "{}: {}-byte logical and physical sectors, no sub-physical misalignment possible on this device",
+            device, sector.logical
+        );
+        return None;
+    }
+
+    let shift = args
+        .misalign_bytes
+        .or_else(|| sectors.iter().map(|(_, s)| s.logical as u64).max())
+        .unwrap_or(512);
+    for (device, sector) in &sectors {
+        let (logical, physical) = (sector.logical as u64, sector.physical as u64);
+        let problem = if !cfg!(target_os = "macos") && !shift.is_multiple_of(logical) {
+            format!("is not a multiple of the {}-byte logical sector, so direct I/O would reject every read", logical)
+        } else if shift.is_multiple_of(physical) {
+            format!("is a multiple of the {}-byte physical sector, so the pass would still be aligned", physical)
+        } else {
+            continue;
+        };
+        eprintln!(
+            "Error: --misalign-bytes {} {} on {}",
+            shift, problem, device
+        );
+        std::process::exit(1);
+    }
+    Some(shift)
+}
+
+/// Run a short aligned and misaligned random-read pass and report the penalty
+fn run_align_check(args: &Args, devices: &[String]) {
+    let Some(shift) = misalign_shift(args, devices) else {
+        return;
+    };
+    let secs = args.bench.duration.min(10);
+    let mut config = test_config(
+        &args.bench,
+        devices,
+        args.bench.read_iops_bs,
+        args.bench.read_iops_threads,
+        args.bench.read_iops_qd,
+        false,
+    );
+    config.duration_secs = secs;
+    // Both passes must run for the same time, flat out, to be comparable
+    config.size_limit = None;
+    config.ops_limit = None;
+    config.target_iops = None;
+    // And walk the same offsets
+    config.seed.get_or_insert_with(rand::random);
+
+    info!("Running alignment check ({} seconds per pass)...", secs);
+    info!("Aligned pass:");
+    let aligned = match engine::run_test(&config) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Aligned pass error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+    info!();
+
+    info!("Misaligned pass ({} byte shift):", shift);
+    config.offset_shift = shift;
+    let misaligned = match engine::run_test(&config) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Misaligned pass error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+    info!();
+
+    let penalty = if aligned.iops > 0.0 {
+        (aligned.iops - misaligned.iops) / aligned.iops * 100.0
     } else {
-        format!("{} devices", devices.len())
+        0.0
     };
+    info!(
+        "  Aligned:    {:>10.0} IOPS | avg {:>8.1} us | p99 {:>8.1} us",
+        aligned.iops, aligned.latency_avg_us, aligned.latency_p99_us
+    );
+    info!(
+        "  Misaligned: {:>10.0} IOPS | avg {:>8.1} us | p99 {:>8.1} us",
+        misaligned.iops, misaligned.latency_avg_us, misaligned.latency_p99_us
+    );
+    info!(
+        "Alignment penalty: {:.1}% IOPS at a {}-byte offset shift",
+        penalty, shift
+    );
+}
 
-    // Create file device if requested (only for first device)
-    if args.create_file {
-        if let Err(e) = engine::create_file_device(&devices[0], args.file_size) {
-            eprintln!("Error creating file device: {}", e);
-            std::process::exit(1);
+/// Parse the command line, layering it over a --preset and a --config profile
+/// when given (command line over config over preset)
+fn parse_args() -> Args {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if let Some(preset) = args.preset {
+        preset.profile().apply(&mut args.bench, &matches);
+    }
+    if let Some(path) = args.config.clone() {
+        match Config::load(&path) {
+            Ok(config) => config.apply(&mut args.bench, &matches),
+            Err(e) => {
+                eprintln!("Error: cannot load config {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
         }
-        println!("File device created successfully");
-        println!();
     }
+    // The --test shapes are command-line flags too, so they win over both
+    args.bench.apply_test_params();
 
-    // Prep device if requested (all devices in parallel)
-    if args.prep {
-        println!("Preparing {} device{}...", devices.len(), if devices.len() == 1 { "" } else { "s" });
+    args
+}
 
-        let mut handles = Vec::new();
-        for device in devices.clone() {
-            let handle = std::thread::spawn(move || {
-                if let Err(e) = engine::prep_device(&device) {
-                    eprintln!("Error preparing device {}: {}", device, e);
-                    Err(e)
-                } else {
-                    println!("  ✓ {}", device);
-                    Ok(())
-                }
-            });
-            handles.push(handle);
-        }
+fn main() {
+    let mut args = parse_args();
 
-        // Wait for all preps to complete and check for errors
-        for handle in handles {
-            if handle.join().unwrap().is_err() {
+    output::set_verbose(true);
+    output::set_quiet(args.quiet);
+
+    if args.command == Some(cli::Command::List) || args.list_devices {
+        list_disks(number_format(&args.bench));
+        return;
+    }
+
+    if args.dump_config {
+        match Config::from_args(&args.bench).to_toml() {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("Error: cannot write config: {}", e);
                 std::process::exit(1);
             }
         }
-        println!("All devices prepared successfully");
-        println!();
+        return;
     }
 
-    // Determine which tests to run
-    let run_all = args.tests == "all";
-    let run_read_tp = run_all || args.tests.contains("read-tp");
-    let run_write_tp = run_all || args.tests.contains("write-tp");
-    let run_read_iops = run_all || args.tests.contains("read-iops");
-    let run_write_iops = run_all || args.tests.contains("write-iops");
+    info!("4Corners Disk Benchmark (Rust)");
+    info!("==============================");
+    info!();
 
-    let mut report = BenchmarkReport::new(&device_display);
+    // Parse and normalize device list
+    let interactive =
+        std::io::stdin().is_terminal() && std::io::stdout().is_terminal() && !args.quiet;
+    let devices = if args.bench.device.is_empty() && args.bench.devices_file.is_none() && interactive {
+        pick_devices(number_format(&args.bench))
+    } else {
+        parse_devices(&args)
+    };
+    // The library gets the list as resolved here, picked or read from a file
+    args.bench.device = devices.clone();
+    args.bench.devices_file = None;
 
-    println!("Starting benchmark tests...");
-    println!();
+    let selected = suite::selected_tests(&args.bench);
+    let [_, (run_write_tp, ..), _, (run_write_iops, ..), (run_mixed, ..)] = selected;
 
-    // Read Throughput
-    if run_read_tp {
-        println!("Running Read Throughput Test...");
-        let config = TestConfig {
-            device_paths: devices.clone(),
-            io_size: args.read_tp_bs as u64 * 1024,
-            threads: args.read_tp_threads,
-            queue_depth: args.read_tp_qd,
-            duration_secs: args.duration,
-            is_write: false,
-        };
-        match engine::run_test(&config) {
-            Ok(result) => report.read_throughput = Some(result),
-            Err(e) => eprintln!("Read throughput error: {}", e),
-        }
-        println!();
-    }
-
-    // Write Throughput
-    if run_write_tp {
-        println!("Running Write Throughput Test...");
-        let config = TestConfig {
-            device_paths: devices.clone(),
-            io_size: args.write_tp_bs as u64 * 1024,
-            threads: args.write_tp_threads,
-            queue_depth: args.write_tp_qd,
-            duration_secs: args.duration,
-            is_write: true,
-        };
-        match engine::run_test(&config) {
-            Ok(result) => report.write_throughput = Some(result),
-            Err(e) => eprintln!("Write throughput error: {}", e),
-        }
-        println!();
-    }
-
-    // Read IOPS
-    if run_read_iops {
-        println!("Running Read IOPS Test...");
-        let config = TestConfig {
-            device_paths: devices.clone(),
-            io_size: args.read_iops_bs as u64 * 1024,
-            threads: args.read_iops_threads,
-            queue_depth: args.read_iops_qd,
-            duration_secs: args.duration,
-            is_write: false,
-        };
-        match engine::run_test(&config) {
-            Ok(result) => report.read_iops = Some(result),
-            Err(e) => eprintln!("Read IOPS error: {}", e),
-        }
-        println!();
-    }
-
-    // Write IOPS
-    if run_write_iops {
-        println!("Running Write IOPS Test...");
-        let config = TestConfig {
-            device_paths: devices.clone(),
-            io_size: args.write_iops_bs as u64 * 1024,
-            threads: args.write_iops_threads,
-            queue_depth: args.write_iops_qd,
-            duration_secs: args.duration,
-            is_write: true,
-        };
-        match engine::run_test(&config) {
-            Ok(result) => report.write_iops = Some(result),
-            Err(e) => eprintln!("Write IOPS error: {}", e),
+    if args.dry_run {
+        run_dry_run(&args, &devices, &selected);
+        return;
+    }
+
+    if args.check {
+        run_device_check(&args, &devices, run_write_tp || run_write_iops || run_mixed);
+        return;
+    }
+
+    // Load the baseline up front so a bad path fails before the run, not after
+    let baseline = args.baseline.as_deref().map(|path| match BenchmarkReport::load(path) {
+        Ok(baseline) => {
+            if let Some(reason) = baseline.schema_mismatch() {
+                eprintln!(
+                    "Warning: baseline {} may compare incorrectly: {}",
+                    path.display(),
+                    reason
+                );
+            }
+            baseline
+        }
+        Err(e) => {
+            eprintln!("Error: cannot load baseline {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    });
+
+    if !args.align_check {
+        prepare_output_dir(&args.output_dir);
+    }
+
+    // --align-check only reads, whatever --tests selects; prep still writes
+    let writes = !args.align_check && selected.iter().any(|t| t.0 && t.2);
+    confirm_destructive(&args, &devices, writes);
+    // From here on Ctrl-C ends the current test early and still saves the report
+    engine::install_interrupt_handler();
+
+    if args.align_check {
+        if let Err(e) = suite::prepare_devices(&args.bench, &devices, None) {
+            eprintln!("Error: {}", e);
+            std::process::exit(e.exit_code());
         }
-        println!();
+        run_align_check(&args, &devices);
+        suite::cleanup_file(&args.bench, &devices);
+        return;
     }
 
-    println!("Benchmark completed!");
-    println!();
-    println!("{}", report.generate_text_report());
+    let report = match fourcorners::run_benchmark(args.bench.clone()) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    if report.interrupted {
+        info!("Benchmark interrupted; reporting the tests that ran");
+    } else if report.max_runtime_reached {
+        info!("Max runtime reached; reporting the tests that ran");
+    } else {
+        info!("Benchmark completed!");
+    }
+    info!();
+    info!("{}", report.generate_text_report());
+    if let Some(baseline) = &baseline {
+        info!("{}", report.compare(baseline).format(report.number_format));
+    }
+    if args.quiet {
+        println!("{}", report.to_json());
+    }
 
-    if let Err(e) = report.save(Path::new(".")) {
+    let mut formats = Vec::new();
+    for format in &args.format {
+        if !formats.contains(format) {
+            formats.push(*format);
+        }
+    }
+    if let Err(e) = report.save(&args.output_dir, &formats) {
         eprintln!("Warning: failed to save reports: {}", e);
     }
+    if let Some(path) = &args.history {
+        if let Err(e) = report.append_history(path) {
+            eprintln!("Warning: failed to append to history {}: {}", path.display(), e);
+        }
+    }
+    if let Some(target) = &args.influx {
+        if let Err(e) = report.write_influx(target) {
+            eprintln!("Warning: failed to write Influx points to {}: {}", target, e);
+        }
+    }
+
+    let corrupt = [&report.write_throughput, &report.write_iops]
+        .into_iter()
+        .flatten()
+        .chain(report.sweeps.iter().chain(&report.qd_sweeps).flat_map(|sweep| &sweep.results))
+        .filter_map(|r| r.verify.as_ref())
+        .any(|v| !v.passed());
+    if corrupt {
+        eprintln!("Error: data verification failed");
+        std::process::exit(1);
+    }
+    if report.interrupted {
+        // Conventional exit status for a run ended by SIGINT
+        std::process::exit(130);
+    }
+    if report.max_runtime_reached {
+        // Same status timeout(1) uses for a command that ran out of time
+        std::process::exit(124);
+    }
 }
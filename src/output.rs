@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Off until `set_verbose`, so a program embedding the library prints nothing
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--quiet`: stdout carries only the final JSON report
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Print progress and results on stdout and warnings on stderr, as `4c` does
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Whether stdout is silent: under `--quiet`, or when not verbose at all
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed) || !verbose()
+}
+
+/// `println!` for banners, progress and results; silent under `--quiet`.
+/// Errors and warnings go to `warn!`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !$crate::output::quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `print!` without a newline for an updating progress line; flushes stdout so
+/// the line shows up right away
+#[macro_export]
+#[doc(hidden)]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if !$crate::output::quiet() {
+            use std::io::Write as _;
+            print!($($arg)*);
+            let _ = std::io::stdout().flush();
+        }
+    };
+}
+
+/// `eprintln!` for errors and warnings; still shown under `--quiet`, but not
+/// when the library runs without `set_verbose`
+#[macro_export]
+#[doc(hidden)]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::output::verbose() {
+            eprintln!($($arg)*);
+        }
+    };
+}
@@ -1,44 +1,703 @@
+use crate::engine::{AccessPattern, BlockSizeMix, DeviceKind, OffsetDistribution, SectorSize, StopReason, WritePattern};
 use chrono::{DateTime, Local};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
-#[derive(Debug, Clone, Serialize)]
+mod html;
+
+/// Report file formats written by `BenchmarkReport::save`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+    Md,
+    Html,
+}
+
+/// Unit prefixes for throughput and sizes in console, text and Markdown output.
+/// Results are measured and stored (JSON, CSV) in binary units either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Units {
+    /// Decimal: MB/s and GB (10^6 / 10^9 bytes), as drive datasheets use
+    Si,
+    /// Binary: MiB/s and GiB (2^20 / 2^30 bytes)
+    #[default]
+    Iec,
+}
+
+const MIB: f64 = 1024.0 * 1024.0;
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Number formatting for console and text output.
+/// `precision` overrides the per-field default number of decimal places;
+/// counts (IOPS) are always whole numbers with thousands separators.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumberFormat {
+    pub precision: Option<usize>,
+    pub units: Units,
+}
+
+impl NumberFormat {
+    /// Convert a throughput given in MiB/s (as results store it) to the chosen units
+    pub fn throughput_value(&self, mib_per_sec: f64) -> f64 {
+        match self.units {
+            Units::Si => mib_per_sec * MIB / 1e6,
+            Units::Iec => mib_per_sec,
+        }
+    }
+
+    /// Format a throughput given in MiB/s in the chosen units
+    pub fn throughput(&self, mib_per_sec: f64, default: usize) -> String {
+        self.fixed(self.throughput_value(mib_per_sec), default)
+    }
+
+    pub fn throughput_unit(&self) -> &'static str {
+        match self.units {
+            Units::Si => "MB/s",
+            Units::Iec => "MiB/s",
+        }
+    }
+
+    /// Format a byte count as GB or GiB
+    pub fn size(&self, bytes: f64, default: usize) -> String {
+        let v = match self.units {
+            Units::Si => bytes / 1e9,
+            Units::Iec => bytes / GIB,
+        };
+        self.fixed(v, default)
+    }
+
+    pub fn size_unit(&self) -> &'static str {
+        match self.units {
+            Units::Si => "GB",
+            Units::Iec => "GiB",
+        }
+    }
+
+    /// Format with `default` decimal places unless a precision was configured
+    pub fn fixed(&self, v: f64, default: usize) -> String {
+        group_thousands(&format!("{:.*}", self.precision.unwrap_or(default), v))
+    }
+
+    /// Format a count such as IOPS: no decimals, thousands separators
+    pub fn count(&self, v: f64) -> String {
+        group_thousands(&format!("{:.0}", v))
+    }
+}
+
+/// Insert `,` separators into the integer part of a formatted number
+fn group_thousands(num: &str) -> String {
+    let (sign, rest) = match num.strip_prefix('-') {
+        Some(r) => ("-", r),
+        None => ("", num),
+    };
+    let (int, frac) = match rest.find('.') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+
+    let mut grouped = String::with_capacity(num.len() + int.len() / 3);
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    format!("{}{}{}", sign, grouped, frac)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TestResult {
     pub throughput_mbps: f64,
     pub iops: f64,
     pub latency_avg_us: f64,
     pub latency_p50_us: f64,
     pub latency_p99_us: f64,
+    pub latency_p999_us: f64,
+    pub latency_p9999_us: f64,
+    /// Smallest / largest sampled latency
+    pub latency_min_us: f64,
+    pub latency_max_us: f64,
+    /// Standard deviation of the sampled latencies
+    pub latency_stddev_us: f64,
+    /// Bytes transferred during the test
+    pub total_bytes: u64,
     pub threads: u32,
+    /// I/Os in flight per worker thread (`threads` x this per device)
     pub queue_depth: u32,
+    /// The block size, or the largest size of a `--bs-mix`
     pub block_size_kb: u32,
+    /// I/O sizes and their shares (`--bs-mix`); absent for a single block size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_size_mix: Option<BlockSizeMix>,
+    pub access_pattern: AccessPattern,
+    /// Skewed spread of the random offsets (`--distribution`) and its skew;
+    /// absent for uniform random and sequential tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distribution: Option<OffsetDistribution>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skew: Option<f64>,
+    /// Contents of the written blocks (`--write-pattern`); absent for read-only and verifying tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_pattern: Option<WritePattern>,
+    /// Ran through the OS cache (`--buffered`) rather than with direct I/O
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub buffered: bool,
+    /// Writes completed only once on stable media: `--write-through` on Linux,
+    /// always on Windows (FILE_FLAG_WRITE_THROUGH)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub write_through: bool,
+    /// io_uring completion polling (`--io-poll`) / submission polling (`--sq-poll`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub io_poll: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sq_poll: bool,
+    /// Registered io_uring buffers and fixed file (`--fixed-buffers`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fixed_buffers: bool,
+    /// I/Os that could not be reissued because the io_uring submission queue was full
+    #[serde(default)]
+    pub dropped_submissions: u64,
+    /// I/Os that failed or transferred fewer bytes than requested
+    #[serde(default)]
+    pub io_errors: u64,
+    /// OS error text of the first failed I/O
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_io_error: Option<String>,
+    /// Configured test duration
     pub duration_secs: u32,
+    /// Total rate the workers were paced to (`--target-iops`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_iops: Option<u64>,
+    /// Whether the duration, a size/ops limit or Ctrl+C ended the run
+    #[serde(default)]
+    pub stop_reason: StopReason,
+    /// Measured wall-clock time of the run
+    pub elapsed_secs: f64,
+    /// Unmeasured run time before the measured window
+    pub warmup_secs: u32,
+    pub numa_node: Option<u32>,
+    /// Each worker's buffers were allocated on its own core's NUMA node (`--numa`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub numa_local: bool,
+    /// Cores the workers were pinned to, one per worker round-robin (`--cpu-affinity`)
+    pub pinned_cores: Option<usize>,
+    /// Seconds until windowed IOPS first reached 90% of steady state
+    pub time_to_steady_secs: Option<f64>,
+    /// Percentage of reads in a mixed workload
+    pub read_pct: Option<u8>,
+    /// Per-direction throughput for mixed workloads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split: Option<DirectionSplit>,
+    /// Average reads / writes in flight per worker (mixed workloads)
+    pub avg_inflight_reads: Option<f64>,
+    pub avg_inflight_writes: Option<f64>,
+    /// Average I/Os each worker actually kept in flight, against the
+    /// requested `queue_depth`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub achieved_queue_depth: Option<f64>,
+    /// Seconds to flush the device write cache after a write test
+    pub drain_secs: Option<f64>,
+    /// Data check from the `--verify` read-back of this test's writes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify: Option<VerifySummary>,
+    /// In-test device flushes (`--fsync-every`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fsync: Option<FsyncSummary>,
+    /// How far apart the workers' own p99 latencies are (`--per-thread-stats`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_spread: Option<ThreadSpread>,
+    /// Throughput and IOPS in each second of the measured window
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timeline: Vec<TimelinePoint>,
+    /// Sampled latencies counted per power-of-two range
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub latency_distribution: Vec<LatencyBucket>,
+    /// Breakdown by device when several devices were tested together
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub per_device: Vec<DeviceResult>,
+    /// Each run and the run-to-run spread when the test was repeated (`--repeat`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<RepeatSummary>,
 }
 
+impl TestResult {
+    /// "4 KB", or the mix of sizes a `--bs-mix` test drew from
+    pub fn block_size(&self) -> String {
+        match &self.block_size_mix {
+            Some(mix) => format!("mix {}", mix),
+            None => format!("{} KB", self.block_size_kb),
+        }
+    }
+
+    /// The workers kept less than half the requested queue depth in flight,
+    /// so something before the device held them back. A paced run keeps
+    /// fewer in flight on purpose and never counts.
+    pub fn queue_depth_starved(&self) -> bool {
+        self.target_iops.is_none()
+            && self.queue_depth > 1
+            && self.achieved_queue_depth.is_some_and(|qd| qd < self.queue_depth as f64 * 0.5)
+    }
+
+    /// Average the runs of a repeated test into one result that also keeps
+    /// each run's headline numbers and their spread. Per-device rows, the
+    /// timeline and the latency histogram are the last run's.
+    pub fn combine_runs(mut runs: Vec<TestResult>) -> Option<TestResult> {
+        if runs.len() <= 1 {
+            return runs.pop();
+        }
+        let n = runs.len() as f64;
+        let mean = |f: fn(&TestResult) -> f64| runs.iter().map(f).sum::<f64>() / n;
+        let stddev = |f: fn(&TestResult) -> f64| {
+            let m = mean(f);
+            (runs.iter().map(|r| (f(r) - m).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        };
+
+        let mut combined = runs[runs.len() - 1].clone();
+        combined.throughput_mbps = mean(|r| r.throughput_mbps);
+        combined.iops = mean(|r| r.iops);
+        combined.latency_avg_us = mean(|r| r.latency_avg_us);
+        combined.latency_p50_us = mean(|r| r.latency_p50_us);
+        combined.latency_p99_us = mean(|r| r.latency_p99_us);
+        combined.latency_p999_us = mean(|r| r.latency_p999_us);
+        combined.latency_p9999_us = mean(|r| r.latency_p9999_us);
+        combined.latency_stddev_us = mean(|r| r.latency_stddev_us);
+        combined.latency_min_us = runs.iter().map(|r| r.latency_min_us).fold(f64::INFINITY, f64::min);
+        combined.latency_max_us = runs.iter().map(|r| r.latency_max_us).fold(0.0, f64::max);
+        combined.total_bytes = (mean(|r| r.total_bytes as f64)) as u64;
+        combined.elapsed_secs = mean(|r| r.elapsed_secs);
+        // Errors are never averaged away
+        combined.io_errors = runs.iter().map(|r| r.io_errors).sum();
+        combined.dropped_submissions = runs.iter().map(|r| r.dropped_submissions).sum();
+        let achieved: Option<Vec<f64>> = runs.iter().map(|r| r.achieved_queue_depth).collect();
+        combined.achieved_queue_depth = achieved.map(|qd| qd.iter().sum::<f64>() / n);
+        let splits: Vec<&DirectionSplit> = runs.iter().filter_map(|r| r.split.as_ref()).collect();
+        if splits.len() == runs.len() {
+            combined.split = Some(DirectionSplit::mean(&splits));
+        }
+
+        combined.repeat = Some(RepeatSummary {
+            runs: runs
+                .iter()
+                .map(|r| RunResult {
+                    throughput_mbps: r.throughput_mbps,
+                    iops: r.iops,
+                    latency_avg_us: r.latency_avg_us,
+                    latency_p99_us: r.latency_p99_us,
+                })
+                .collect(),
+            throughput_stddev_mbps: stddev(|r| r.throughput_mbps),
+            iops_stddev: stddev(|r| r.iops),
+            latency_avg_stddev_us: stddev(|r| r.latency_avg_us),
+            latency_p99_stddev_us: stddev(|r| r.latency_p99_us),
+        });
+        Some(combined)
+    }
+
+    /// Coefficient of variation of latency (stddev / mean): how noisy the device is
+    /// regardless of how fast it is. Well above 1 usually means a cache falling off a cliff.
+    pub fn latency_cv(&self) -> f64 {
+        if self.latency_avg_us > 0.0 {
+            self.latency_stddev_us / self.latency_avg_us
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Headline numbers of one run of a repeated test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunResult {
+    pub throughput_mbps: f64,
+    pub iops: f64,
+    pub latency_avg_us: f64,
+    pub latency_p99_us: f64,
+}
+
+/// The runs of a test repeated with `--repeat`; the test's own numbers are
+/// their mean, and the standard deviations (across runs, not within one)
+/// show how repeatable it is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeatSummary {
+    pub runs: Vec<RunResult>,
+    pub throughput_stddev_mbps: f64,
+    pub iops_stddev: f64,
+    pub latency_avg_stddev_us: f64,
+    pub latency_p99_stddev_us: f64,
+}
+
+/// Device flushes issued during a write test, timed apart from the I/O latencies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsyncSummary {
+    /// Writes per thread between flushes
+    pub every: u32,
+    pub count: u64,
+    pub latency_avg_us: f64,
+    pub latency_max_us: f64,
+}
+
+/// Each worker's own p99 latency, summarized: one slow thread (a throttled
+/// or busy core) shows up as a max well above the median
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadSpread {
+    /// Workers that sampled at least one latency
+    pub threads: usize,
+    pub p99_min_us: f64,
+    pub p99_median_us: f64,
+    pub p99_max_us: f64,
+    /// Worker id with the highest p99
+    pub slowest_thread: u32,
+}
+
+impl ThreadSpread {
+    pub fn describe(&self, nf: NumberFormat) -> String {
+        format!(
+            "min {} us | median {} us | max {} us (worker {}) across {} threads",
+            nf.fixed(self.p99_min_us, 1),
+            nf.fixed(self.p99_median_us, 1),
+            nf.fixed(self.p99_max_us, 1),
+            self.slowest_thread,
+            self.threads
+        )
+    }
+}
+
+/// Outcome of a `--verify` read pass, counted in 4 KiB chunks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifySummary {
+    pub checked: u64,
+    /// Chunks that carried no verify stamp (not reached by the write pass)
+    pub unwritten: u64,
+    pub corrupt: u64,
+}
+
+impl VerifySummary {
+    pub fn passed(&self) -> bool {
+        self.corrupt == 0
+    }
+
+    /// One-line verdict for console and text output
+    pub fn describe(&self, nf: NumberFormat) -> String {
+        format!(
+            "{} ({} blocks checked, {} corrupt, {} not written)",
+            if self.passed() { "PASS" } else { "FAIL" },
+            nf.count(self.checked as f64),
+            nf.count(self.corrupt as f64),
+            nf.count(self.unwritten as f64)
+        )
+    }
+}
+
+/// One device's share of a multi-device test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceResult {
+    pub path: String,
+    pub throughput_mbps: f64,
+    pub iops: f64,
+    pub latency_avg_us: f64,
+    pub latency_p50_us: f64,
+    pub latency_p99_us: f64,
+    pub latency_p999_us: f64,
+    pub latency_p9999_us: f64,
+    pub latency_min_us: f64,
+    pub latency_max_us: f64,
+    pub total_bytes: u64,
+}
+
+/// One 1-second window of a test
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TimelinePoint {
+    /// End of the window, from the start of the measured run
+    pub elapsed_secs: f64,
+    pub mbps: f64,
+    pub iops: f64,
+}
+
+/// Sampled latencies that fell in `[from_us, to_us)`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LatencyBucket {
+    pub from_us: f64,
+    pub to_us: f64,
+    pub count: u64,
+}
+
+/// Read and write throughput and latency of a mixed workload, reported
+/// separately; the combined latency blends two very different service times
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectionSplit {
+    pub read_mbps: f64,
+    pub read_iops: f64,
+    pub write_mbps: f64,
+    pub write_iops: f64,
+    #[serde(default)]
+    pub read_latency_avg_us: f64,
+    #[serde(default)]
+    pub read_latency_p50_us: f64,
+    #[serde(default)]
+    pub read_latency_p99_us: f64,
+    #[serde(default)]
+    pub read_latency_p999_us: f64,
+    #[serde(default)]
+    pub read_latency_max_us: f64,
+    #[serde(default)]
+    pub write_latency_avg_us: f64,
+    #[serde(default)]
+    pub write_latency_p50_us: f64,
+    #[serde(default)]
+    pub write_latency_p99_us: f64,
+    #[serde(default)]
+    pub write_latency_p999_us: f64,
+    #[serde(default)]
+    pub write_latency_max_us: f64,
+}
+
+impl DirectionSplit {
+    /// Field-by-field mean of several runs' splits
+    fn mean(splits: &[&DirectionSplit]) -> DirectionSplit {
+        let n = splits.len() as f64;
+        let mean = |f: fn(&DirectionSplit) -> f64| splits.iter().map(|d| f(d)).sum::<f64>() / n;
+        DirectionSplit {
+            read_mbps: mean(|d| d.read_mbps),
+            read_iops: mean(|d| d.read_iops),
+            write_mbps: mean(|d| d.write_mbps),
+            write_iops: mean(|d| d.write_iops),
+            read_latency_avg_us: mean(|d| d.read_latency_avg_us),
+            read_latency_p50_us: mean(|d| d.read_latency_p50_us),
+            read_latency_p99_us: mean(|d| d.read_latency_p99_us),
+            read_latency_p999_us: mean(|d| d.read_latency_p999_us),
+            read_latency_max_us: mean(|d| d.read_latency_max_us),
+            write_latency_avg_us: mean(|d| d.write_latency_avg_us),
+            write_latency_p50_us: mean(|d| d.write_latency_p50_us),
+            write_latency_p99_us: mean(|d| d.write_latency_p99_us),
+            write_latency_p999_us: mean(|d| d.write_latency_p999_us),
+            write_latency_max_us: mean(|d| d.write_latency_max_us),
+        }
+    }
+}
+
+/// Per-device settings captured alongside the results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceDetails {
+    pub path: String,
+    pub kind: DeviceKind,
+    /// Drive identity as reported by the OS (Linux and Windows block devices)
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub firmware: Option<String>,
+    /// Active I/O scheduler (Linux block devices only)
+    pub scheduler: Option<String>,
+    pub nr_requests: Option<u32>,
+    pub read_ahead_kb: Option<u32>,
+    pub rotational: Option<bool>,
+    /// Logical / physical sector size (block devices only)
+    pub sector_size: Option<SectorSize>,
+    /// Seconds the `--trim` discard took before the tests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim_secs: Option<f64>,
+}
+
+impl DeviceDetails {
+    pub fn new(path: &str, kind: DeviceKind) -> Self {
+        Self {
+            path: path.to_string(),
+            kind,
+            model: None,
+            serial: None,
+            firmware: None,
+            scheduler: None,
+            nr_requests: None,
+            read_ahead_kb: None,
+            rotational: None,
+            sector_size: None,
+            trim_secs: None,
+        }
+    }
+
+    /// "Samsung SSD 980 PRO (S/N ..., FW ...)", or None if the drive didn't say
+    pub fn identity(&self) -> Option<String> {
+        let model = self.model.as_deref().unwrap_or("Unknown model");
+        let extra: Vec<String> = [("S/N", &self.serial), ("FW", &self.firmware)]
+            .into_iter()
+            .filter_map(|(key, v)| v.as_ref().map(|v| format!("{} {}", key, v)))
+            .collect();
+        match (self.model.is_some(), extra.is_empty()) {
+            (false, true) => None,
+            (_, true) => Some(model.to_string()),
+            _ => Some(format!("{} ({})", model, extra.join(", "))),
+        }
+    }
+
+    fn has_settings(&self) -> bool {
+        self.sector_size.is_some()
+            || self.scheduler.is_some()
+            || self.nr_requests.is_some()
+            || self.read_ahead_kb.is_some()
+            || self.rotational.is_some()
+    }
+}
+
+/// What a sweep varies from one run of a test to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepOver {
+    /// `--bs-sweep`
+    BlockSize,
+    /// `--qd-sweep`
+    QueueDepth,
+}
+
+/// One test run at each step of a `--bs-sweep` or `--qd-sweep`
 #[derive(Debug, Clone, Serialize)]
+pub struct Sweep {
+    /// Report key of the test (`read_iops`, ...)
+    pub test: &'static str,
+    /// Display name (`Read IOPS`, ...)
+    #[serde(skip)]
+    pub label: &'static str,
+    /// Which of the report's sweep lists this belongs in
+    #[serde(skip)]
+    pub over: SweepOver,
+    /// Results in sweep order
+    pub results: Vec<TestResult>,
+}
+
+/// Layout version of the saved JSON report. Bump it when a field is renamed,
+/// removed or changes meaning; a new optional field doesn't need it.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BenchmarkReport {
+    /// `REPORT_SCHEMA_VERSION` of the 4c that wrote the report; 0 for
+    /// reports saved before the field existed
+    #[serde(default)]
+    pub schema_version: u32,
     pub test_date: DateTime<Local>,
+    /// Version of 4c that produced the report
+    #[serde(default)]
+    pub version: String,
+    pub hostname: Option<String>,
+    /// Operating system and CPU architecture, e.g. `linux x86_64`
+    #[serde(default)]
+    pub os: String,
+    /// Logical CPUs available to the benchmark
+    pub cpu_count: Option<usize>,
     pub device: String,
+    /// Free-form note describing why the run was done
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Seed of the offset and write-buffer generators; `--seed` with it replays the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Share of each device (or test range) written before the tests with
+    /// `--fill-percent`; every test ran inside that filled part
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill_percent: Option<u8>,
+    pub device_details: Vec<DeviceDetails>,
+    /// The run was stopped with Ctrl-C; the last test is cut short and later ones are missing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// `--max-runtime` ran out; the last test is cut short and later ones are missing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub max_runtime_reached: bool,
     pub read_throughput: Option<TestResult>,
     pub write_throughput: Option<TestResult>,
     pub read_iops: Option<TestResult>,
     pub write_iops: Option<TestResult>,
+    pub mixed: Option<TestResult>,
+    /// `--bs-sweep` results, one series per test (not read back by `load`)
+    #[serde(skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    pub sweeps: Vec<Sweep>,
+    /// `--qd-sweep` results, one series per test (not read back by `load`)
+    #[serde(skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    pub qd_sweeps: Vec<Sweep>,
+    /// The four corners side by side; filled in by `summarize` once the tests have run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<CornerSummary>,
+    #[serde(skip)]
+    pub number_format: NumberFormat,
+}
+
+/// Reference device for the score: a typical SATA SSD, read/write MiB/s and IOPS
+const SCORE_REFERENCE: [f64; 4] = [500.0, 450.0, 90_000.0, 80_000.0];
+
+/// The headline numbers of the four corners that ran, and a single score when
+/// all four did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CornerSummary {
+    pub read_throughput_mbps: Option<f64>,
+    pub write_throughput_mbps: Option<f64>,
+    pub read_iops: Option<f64>,
+    pub write_iops: Option<f64>,
+    /// Geometric mean of the four corners relative to `SCORE_REFERENCE`, x1000
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+impl CornerSummary {
+    fn from_report(report: &BenchmarkReport) -> Option<Self> {
+        let summary = CornerSummary {
+            read_throughput_mbps: report.read_throughput.as_ref().map(|r| r.throughput_mbps),
+            write_throughput_mbps: report.write_throughput.as_ref().map(|r| r.throughput_mbps),
+            read_iops: report.read_iops.as_ref().map(|r| r.iops),
+            write_iops: report.write_iops.as_ref().map(|r| r.iops),
+            score: None,
+        };
+        let corners = [
+            summary.read_throughput_mbps,
+            summary.write_throughput_mbps,
+            summary.read_iops,
+            summary.write_iops,
+        ];
+        if corners.iter().all(Option::is_none) {
+            return None;
+        }
+        // A partial score would not be comparable with a full one, so all four
+        // corners must have run and measured something
+        let score = corners
+            .iter()
+            .zip(SCORE_REFERENCE)
+            .map(|(v, reference)| v.filter(|v| *v > 0.0).map(|v| (v / reference).ln()))
+            .sum::<Option<f64>>()
+            .map(|log_sum| 1000.0 * (log_sum / 4.0).exp());
+        Some(CornerSummary { score, ..summary })
+    }
 }
 
 impl BenchmarkReport {
     pub fn new(device: &str) -> Self {
         Self {
+            schema_version: REPORT_SCHEMA_VERSION,
             test_date: Local::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: crate::engine::hostname(),
+            os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+            cpu_count: std::thread::available_parallelism().ok().map(|n| n.get()),
             device: device.to_string(),
+            notes: None,
+            seed: None,
+            fill_percent: None,
+            device_details: Vec::new(),
+            interrupted: false,
+            max_runtime_reached: false,
             read_throughput: None,
             write_throughput: None,
             read_iops: None,
             write_iops: None,
+            mixed: None,
+            sweeps: Vec::new(),
+            qd_sweeps: Vec::new(),
+            summary: None,
+            number_format: NumberFormat::default(),
         }
     }
 
+    /// Fill in `summary` from the corner tests that ran
+    pub fn summarize(&mut self) {
+        self.summary = CornerSummary::from_report(self);
+    }
+
     pub fn generate_text_report(&self) -> String {
         let mut s = String::new();
         s.push_str("========================================\n");
@@ -48,63 +707,945 @@ impl BenchmarkReport {
             "Test Date: {}\n",
             self.test_date.format("%Y-%m-%d %H:%M:%S")
         ));
-        s.push_str(&format!("Device: {}\n\n", self.device));
+        if let Some(host) = &self.hostname {
+            s.push_str(&format!("Host: {}\n", host));
+        }
+        if !self.os.is_empty() {
+            s.push_str(&format!("Platform: {}", self.os));
+            if let Some(n) = self.cpu_count {
+                s.push_str(&format!(", {} CPU{}", n, if n == 1 { "" } else { "s" }));
+            }
+            s.push('\n');
+        }
+        if !self.version.is_empty() {
+            s.push_str(&format!("4c Version: {}\n", self.version));
+        }
+        s.push_str(&format!("Device: {}\n", self.device));
+        if let Some(notes) = &self.notes {
+            s.push_str(&format!("Notes: {}\n", notes));
+        }
+        if let Some(seed) = self.seed {
+            s.push_str(&format!("Seed: {}\n", seed));
+        }
+        if let Some(percent) = self.fill_percent {
+            s.push_str(&format!("Fill: {}% of each device written before the tests, which ran inside it\n", percent));
+        }
+        if self.interrupted {
+            s.push_str("Interrupted: yes (partial results)\n");
+        }
+        if self.max_runtime_reached {
+            s.push_str("Max Runtime: reached (partial results)\n");
+        }
+        for d in &self.device_details {
+            if let Some(identity) = d.identity() {
+                s.push_str(&format!("  {}: {}\n", d.path, identity));
+            }
+        }
+        for d in self.device_details.iter().filter(|d| d.has_settings()) {
+            s.push_str(&format!("  {}:", d.path));
+            if let Some(v) = d.sector_size {
+                s.push_str(&format!(" sector={}/{}", v.logical, v.physical));
+            }
+            if let Some(v) = &d.scheduler {
+                s.push_str(&format!(" scheduler={}", v));
+            }
+            if let Some(v) = d.nr_requests {
+                s.push_str(&format!(" nr_requests={}", v));
+            }
+            if let Some(v) = d.read_ahead_kb {
+                s.push_str(&format!(" read_ahead_kb={}", v));
+            }
+            if let Some(v) = d.rotational {
+                s.push_str(&format!(" rotational={}", if v { "yes" } else { "no" }));
+            }
+            s.push('\n');
+        }
+        for d in &self.device_details {
+            if let Some(secs) = d.trim_secs {
+                s.push_str(&format!("  {}: trimmed in {:.2} s before the tests\n", d.path, secs));
+            }
+        }
+        s.push('\n');
+
+        format_summary_matrix(&mut s, self);
 
         if let Some(r) = &self.read_throughput {
             s.push_str("Read Throughput Test:\n");
-            format_result(&mut s, r);
+            format_result(&mut s, r, self.number_format);
         }
         if let Some(r) = &self.write_throughput {
             s.push_str("Write Throughput Test:\n");
-            format_result(&mut s, r);
+            format_result(&mut s, r, self.number_format);
         }
         if let Some(r) = &self.read_iops {
             s.push_str("Read IOPS Test:\n");
-            format_result(&mut s, r);
+            format_result(&mut s, r, self.number_format);
         }
         if let Some(r) = &self.write_iops {
             s.push_str("Write IOPS Test:\n");
-            format_result(&mut s, r);
+            format_result(&mut s, r, self.number_format);
+        }
+        if let Some(r) = &self.mixed {
+            s.push_str("Mixed Read/Write Test:\n");
+            format_result(&mut s, r, self.number_format);
+        }
+        for sweep in self.sweeps.iter().chain(&self.qd_sweeps) {
+            format_sweep(&mut s, sweep, self.number_format);
         }
 
         s.push_str("========================================\n");
         s
     }
 
-    pub fn save(&self, dir: &Path) -> io::Result<()> {
-        let timestamp = self.test_date.format("%Y%m%d-%H%M%S");
+    /// The results that were run, with their report key, in report order.
+    /// Sweep results follow, one per block size or queue depth.
+    fn results(&self) -> Vec<(&'static str, &TestResult)> {
+        let sweeps = self
+            .sweeps
+            .iter()
+            .chain(&self.qd_sweeps)
+            .flat_map(|sweep| sweep.results.iter().map(move |r| (sweep.test, r)));
+        [
+            ("read_throughput", &self.read_throughput),
+            ("write_throughput", &self.write_throughput),
+            ("read_iops", &self.read_iops),
+            ("write_iops", &self.write_iops),
+            ("mixed", &self.mixed),
+        ]
+        .into_iter()
+        .filter_map(|(name, r)| r.as_ref().map(|r| (name, r)))
+        .chain(sweeps)
+        .collect()
+    }
 
-        let text_path = dir.join(format!("4c-report-{}.txt", timestamp));
-        fs::write(&text_path, self.generate_text_report())?;
-        println!("Text report saved: {}", text_path.display());
+    /// One row per test, raw (unformatted) numbers. Multi-device runs add a
+    /// row per device after each test's combined row, with `device` filled in.
+    pub fn generate_csv_report(&self) -> String {
+        let mut s = String::from(
+            "test,threads,queue_depth,block_size_kb,duration_secs,elapsed_secs,throughput_mbps,iops,\
+             latency_avg_us,latency_p50_us,latency_p99_us,latency_p999_us,latency_p9999_us,\
+             latency_min_us,latency_max_us,total_bytes,device\n",
+        );
+        for (name, r) in self.results() {
+            s.push_str(&format!(
+                "{},{},{},{},{},{:.3},{:.2},{:.0},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{},\n",
+                name,
+                r.threads,
+                r.queue_depth,
+                r.block_size_kb,
+                r.duration_secs,
+                r.elapsed_secs,
+                r.throughput_mbps,
+                r.iops,
+                r.latency_avg_us,
+                r.latency_p50_us,
+                r.latency_p99_us,
+                r.latency_p999_us,
+                r.latency_p9999_us,
+                r.latency_min_us,
+                r.latency_max_us,
+                r.total_bytes
+            ));
+            for d in &r.per_device {
+                s.push_str(&format!(
+                    "{},{},{},{},{},{:.3},{:.2},{:.0},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{},{}\n",
+                    name,
+                    r.threads,
+                    r.queue_depth,
+                    r.block_size_kb,
+                    r.duration_secs,
+                    r.elapsed_secs,
+                    d.throughput_mbps,
+                    d.iops,
+                    d.latency_avg_us,
+                    d.latency_p50_us,
+                    d.latency_p99_us,
+                    d.latency_p999_us,
+                    d.latency_p9999_us,
+                    d.latency_min_us,
+                    d.latency_max_us,
+                    d.total_bytes,
+                    d.path
+                ));
+            }
+        }
+        s
+    }
+
+    pub fn generate_markdown_report(&self) -> String {
+        let nf = self.number_format;
+        let mut s = String::new();
+        s.push_str("# 4Corners Disk Benchmark Report\n\n");
+        s.push_str(&format!(
+            "- **Test Date:** {}\n- **Device:** `{}`\n\n",
+            self.test_date.format("%Y-%m-%d %H:%M:%S"),
+            self.device
+        ));
+        if let Some(notes) = &self.notes {
+            s.push_str(&format!("> {}\n\n", notes));
+        }
+        s.push_str(&format!(
+            "| Test | Threads | QD | Block | {} | IOPS | Avg (us) | P50 (us) | P99 (us) |\n",
+            nf.throughput_unit()
+        ));
+        s.push_str("|------|--------:|---:|------:|-----:|-----:|---------:|---------:|---------:|\n");
+        for (name, r) in self.results() {
+            s.push_str(&format!(
+                "| {} | {} | {} | {} KB | {} | {} | {} | {} | {} |\n",
+                name,
+                r.threads,
+                r.queue_depth,
+                r.block_size_kb,
+                nf.throughput(r.throughput_mbps, 2),
+                nf.count(r.iops),
+                nf.fixed(r.latency_avg_us, 2),
+                nf.fixed(r.latency_p50_us, 2),
+                nf.fixed(r.latency_p99_us, 2)
+            ));
+            for d in &r.per_device {
+                s.push_str(&format!(
+                    "| &nbsp;&nbsp;`{}` | | | | {} | {} | {} | {} | {} |\n",
+                    d.path,
+                    nf.throughput(d.throughput_mbps, 2),
+                    nf.count(d.iops),
+                    nf.fixed(d.latency_avg_us, 2),
+                    nf.fixed(d.latency_p50_us, 2),
+                    nf.fixed(d.latency_p99_us, 2)
+                ));
+            }
+        }
+        s
+    }
 
-        let json_path = dir.join(format!("4c-report-{}.json", timestamp));
-        let json = serde_json::to_string_pretty(self).unwrap();
-        fs::write(&json_path, json)?;
-        println!("JSON report saved: {}", json_path.display());
+    /// Read back a JSON report written by `save`, e.g. as a `--baseline`
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
 
+    /// Why a loaded report may not read correctly with this version of 4c,
+    /// if its schema differs
+    pub fn schema_mismatch(&self) -> Option<String> {
+        match self.schema_version {
+            REPORT_SCHEMA_VERSION => None,
+            0 => Some("it was saved before reports carried a schema version".to_string()),
+            v => Some(format!(
+                "it has report schema {} and this 4c reads schema {}",
+                v, REPORT_SCHEMA_VERSION
+            )),
+        }
+    }
+
+    /// Compare every test both reports ran, metric by metric
+    pub fn compare(&self, baseline: &BenchmarkReport) -> ReportComparison {
+        let tests = [
+            ("Read Throughput", &self.read_throughput, &baseline.read_throughput),
+            ("Write Throughput", &self.write_throughput, &baseline.write_throughput),
+            ("Read IOPS", &self.read_iops, &baseline.read_iops),
+            ("Write IOPS", &self.write_iops, &baseline.write_iops),
+            ("Mixed Read/Write", &self.mixed, &baseline.mixed),
+        ];
+
+        let mut deltas = Vec::new();
+        for (test, current, base) in tests {
+            let (Some(current), Some(base)) = (current, base) else {
+                continue;
+            };
+            let metrics = [
+                ("Throughput", "MiB/s", current.throughput_mbps, base.throughput_mbps, true),
+                ("IOPS", "", current.iops, base.iops, true),
+                ("Avg latency", "us", current.latency_avg_us, base.latency_avg_us, false),
+                ("p99 latency", "us", current.latency_p99_us, base.latency_p99_us, false),
+            ];
+            for (metric, unit, current, baseline, higher_is_better) in metrics {
+                deltas.push(MetricDelta {
+                    test,
+                    metric,
+                    unit,
+                    current,
+                    baseline,
+                    higher_is_better,
+                });
+            }
+        }
+
+        ReportComparison {
+            baseline_date: baseline.test_date,
+            baseline_device: baseline.device.clone(),
+            deltas,
+        }
+    }
+
+    /// Append the report as one compact JSON line to a `--history` file.
+    /// The line goes out in a single write, and a last line cut short by an
+    /// earlier crash is closed off first so it can't swallow this one.
+    pub fn append_history(&self, path: &Path) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut line = serde_json::to_string(self).unwrap();
+        line.push('\n');
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+        file.write_all(line.as_bytes())?;
+        info!("Appended to history: {}", path.display());
         Ok(())
     }
+
+    /// The results as InfluxDB line protocol: one `fourcorners` point per
+    /// test (and per sweep step), stamped with the start of the run. Block
+    /// size and queue depth are tags so the steps of a sweep stay apart.
+    pub fn generate_influx_lines(&self) -> String {
+        let timestamp = self.test_date.timestamp_nanos_opt().unwrap_or(0);
+        let mut tags = format!("device={}", influx_tag(&self.device));
+        if let Some(host) = self.hostname.as_deref().filter(|h| !h.is_empty()) {
+            tags.push_str(&format!(",host={}", influx_tag(host)));
+        }
+
+        let mut s = String::new();
+        for (name, r) in self.results() {
+            s.push_str(&format!(
+                "fourcorners,test={},{},block_size_kb={},queue_depth={} \
+                 throughput_mbps={},iops={},latency_avg_us={},latency_p50_us={},latency_p99_us={},\
+                 latency_p999_us={},latency_max_us={},threads={}i,io_errors={}i {}\n",
+                name,
+                tags,
+                r.block_size_kb,
+                r.queue_depth,
+                r.throughput_mbps,
+                r.iops,
+                r.latency_avg_us,
+                r.latency_p50_us,
+                r.latency_p99_us,
+                r.latency_p999_us,
+                r.latency_max_us,
+                r.threads,
+                r.io_errors,
+                timestamp
+            ));
+        }
+        s
+    }
+
+    /// Send the `--influx` points to `target`: POSTed to an `http://` URL
+    /// (an InfluxDB write endpoint), otherwise appended to a file
+    pub fn write_influx(&self, target: &str) -> io::Result<()> {
+        let lines = self.generate_influx_lines();
+        if let Some(url) = target.strip_prefix("http://") {
+            post_http(url, &lines)?;
+            info!("Influx points sent: {}", target);
+        } else if target.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "https is not supported; use an http:// endpoint or a file",
+            ));
+        } else {
+            let mut file = fs::OpenOptions::new().append(true).create(true).open(target)?;
+            file.write_all(lines.as_bytes())?;
+            info!("Influx points appended: {}", target);
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Write one report file per requested format into `dir`
+    pub fn save(&self, dir: &Path, formats: &[ReportFormat]) -> io::Result<()> {
+        let timestamp = self.test_date.format("%Y%m%d-%H%M%S");
+
+        for format in formats {
+            let (contents, ext, label) = match format {
+                ReportFormat::Text => (self.generate_text_report(), "txt", "Text"),
+                ReportFormat::Json => (self.to_json(), "json", "JSON"),
+                ReportFormat::Csv => (self.generate_csv_report(), "csv", "CSV"),
+                ReportFormat::Md => (self.generate_markdown_report(), "md", "Markdown"),
+                ReportFormat::Html => (self.generate_html_report(), "html", "HTML"),
+            };
+            let path = dir.join(format!("4c-report-{}.{}", timestamp, ext));
+            fs::write(&path, contents)?;
+            info!("{} report saved: {}", label, path.display());
+        }
+
+        Ok(())
+    }
+}
+
+/// One metric of one test, this run against the baseline
+#[derive(Debug, Clone)]
+pub struct MetricDelta {
+    pub test: &'static str,
+    pub metric: &'static str,
+    pub unit: &'static str,
+    pub current: f64,
+    pub baseline: f64,
+    /// Throughput and IOPS improve upwards, latency downwards
+    pub higher_is_better: bool,
+}
+
+impl MetricDelta {
+    /// Change from the baseline in percent, or None if the baseline is zero
+    pub fn change_pct(&self) -> Option<f64> {
+        (self.baseline != 0.0).then(|| (self.current - self.baseline) / self.baseline * 100.0)
+    }
+
+    pub fn improved(&self) -> bool {
+        (self.current > self.baseline) == self.higher_is_better
+    }
+}
+
+/// Result of `BenchmarkReport::compare`: the metrics of every test both runs have
+#[derive(Debug, Clone)]
+pub struct ReportComparison {
+    pub baseline_date: DateTime<Local>,
+    pub baseline_device: String,
+    pub deltas: Vec<MetricDelta>,
+}
+
+impl ReportComparison {
+    /// Side-by-side listing, grouped by test, for the console
+    pub fn format(&self, nf: NumberFormat) -> String {
+        let mut s = format!(
+            "Comparison with baseline ({}, {})\n",
+            self.baseline_date.format("%Y-%m-%d %H:%M:%S"),
+            self.baseline_device
+        );
+        if self.deltas.is_empty() {
+            s.push_str("  No tests in common with the baseline\n");
+            return s;
+        }
+
+        let mut last_test = "";
+        for d in &self.deltas {
+            if d.test != last_test {
+                s.push_str(&format!("  {}:\n", d.test));
+                last_test = d.test;
+            }
+            let value = |v: f64| match d.unit {
+                "" => nf.count(v),
+                "MiB/s" => format!("{} {}", nf.throughput(v, 2), nf.throughput_unit()),
+                unit => format!("{} {}", nf.fixed(v, 2), unit),
+            };
+            let change = match d.change_pct() {
+                Some(pct) if pct.abs() < 0.05 => "no change".to_string(),
+                Some(pct) => format!(
+                    "{:+.1}% ({})",
+                    pct,
+                    if d.improved() { "better" } else { "worse" }
+                ),
+                None => "n/a".to_string(),
+            };
+            s.push_str(&format!(
+                "    {:<13} {:>15} vs {:>15} baseline, {}\n",
+                format!("{}:", d.metric),
+                value(d.current),
+                value(d.baseline),
+                change
+            ));
+        }
+        s
+    }
 }
 
-fn format_result(s: &mut String, r: &TestResult) {
+/// Read/write x throughput/IOPS at a glance: the headline number and p99
+/// latency for each corner that was run
+fn format_summary_matrix(s: &mut String, report: &BenchmarkReport) {
+    let corners = [
+        &report.read_throughput,
+        &report.read_iops,
+        &report.write_throughput,
+        &report.write_iops,
+    ];
+    if corners.iter().all(|r| r.is_none()) {
+        return;
+    }
+
+    let nf = report.number_format;
+    let tp_cell = |r: &Option<TestResult>| match r {
+        Some(r) => format!(
+            "{:>9} {:<5} p99 {:>8} us",
+            nf.throughput(r.throughput_mbps, 1),
+            nf.throughput_unit(),
+            nf.fixed(r.latency_p99_us, 1)
+        ),
+        None => format!("{:>9}", "-"),
+    };
+    let iops_cell = |r: &Option<TestResult>| match r {
+        Some(r) => format!("{:>9} IOPS  p99 {:>8} us", nf.count(r.iops), nf.fixed(r.latency_p99_us, 1)),
+        None => format!("{:>9}", "-"),
+    };
+
+    s.push_str("Summary:\n");
+    s.push_str(&format!("  {:<7}{:<32}{}\n", "", "Throughput", "IOPS"));
+    s.push_str(&format!(
+        "  {:<7}{:<32}{}\n",
+        "Read",
+        tp_cell(&report.read_throughput),
+        iops_cell(&report.read_iops)
+    ));
+    s.push_str(&format!(
+        "  {:<7}{:<32}{}\n",
+        "Write",
+        tp_cell(&report.write_throughput),
+        iops_cell(&report.write_iops)
+    ));
+    if let Some(score) = CornerSummary::from_report(report).and_then(|c| c.score) {
+        s.push_str(&format!(
+            "  Score: {} ({} = a typical SATA SSD)\n",
+            nf.count(score),
+            nf.count(1000.0)
+        ));
+    }
+    s.push('\n');
+}
+
+/// A sweep as one table: a row per block size or queue depth, so the curve
+/// reads top to bottom
+fn format_sweep(s: &mut String, sweep: &Sweep, nf: NumberFormat) {
+    let Some(first) = sweep.results.first() else {
+        return;
+    };
+    let (title, shape, step) = match sweep.over {
+        SweepOver::BlockSize => ("Block Size", format!("QD {}", first.queue_depth), "Block"),
+        SweepOver::QueueDepth => ("Queue Depth", format!("{} KB", first.block_size_kb), "QD"),
+    };
+    s.push_str(&format!(
+        "{} Sweep - {} ({} threads, {}, {}):\n",
+        title,
+        sweep.label,
+        first.threads,
+        shape,
+        first.access_pattern.as_str()
+    ));
+    s.push_str(&format!(
+        "  {:>8}  {:>12}  {:>12}  {:>12}  {:>12}  {:>12}\n",
+        step,
+        nf.throughput_unit(),
+        "IOPS",
+        "Avg (us)",
+        "P99 (us)",
+        "Max (us)"
+    ));
+    for r in &sweep.results {
+        let step = match sweep.over {
+            SweepOver::BlockSize => format!("{} KB", r.block_size_kb),
+            SweepOver::QueueDepth => r.queue_depth.to_string(),
+        };
+        s.push_str(&format!(
+            "  {:>8}  {:>12}  {:>12}  {:>12}  {:>12}  {:>12}\n",
+            step,
+            nf.throughput(r.throughput_mbps, 2),
+            nf.count(r.iops),
+            nf.fixed(r.latency_avg_us, 2),
+            nf.fixed(r.latency_p99_us, 2),
+            nf.fixed(r.latency_max_us, 2)
+        ));
+        if let Some(v) = &r.verify {
+            s.push_str(&format!("           Verify: {}\n", v.describe(nf)));
+        }
+    }
+    s.push('\n');
+}
+
+fn format_result(s: &mut String, r: &TestResult, nf: NumberFormat) {
     s.push_str(&format!("  Threads:         {}\n", r.threads));
-    s.push_str(&format!("  Queue Depth:     {}\n", r.queue_depth));
-    s.push_str(&format!("  Block Size:      {} KB\n", r.block_size_kb));
-    s.push_str(&format!("  Duration:        {} seconds\n", r.duration_secs));
-    s.push_str(&format!("  Throughput:    {:>10.2} MB/s\n", r.throughput_mbps));
-    s.push_str(&format!("  IOPS:          {:>10.0}\n", r.iops));
     s.push_str(&format!(
-        "  Avg Latency:   {:>10.2} us\n",
-        r.latency_avg_us
+        "  Queue Depth:     {} per thread ({} total per device)\n",
+        r.queue_depth,
+        r.queue_depth * r.threads
+    ));
+    if let Some(qd) = r.achieved_queue_depth {
+        s.push_str(&format!(
+            "  Achieved QD:     {:.1} per thread ({:.0}% of requested{})\n",
+            qd,
+            qd / r.queue_depth as f64 * 100.0,
+            if r.queue_depth_starved() { "; CPU or submission bound, not the device" } else { "" }
+        ));
+    }
+    s.push_str(&format!("  Block Size:      {}\n", r.block_size()));
+    s.push_str(&format!("  Access Pattern:  {}\n", r.access_pattern.as_str()));
+    if let (Some(dist), Some(skew)) = (r.distribution, r.skew) {
+        s.push_str(&format!("  Distribution:    {}, skew {}\n", dist.as_str(), skew));
+    }
+    if let Some(p) = r.write_pattern.filter(|&p| p != WritePattern::Random) {
+        s.push_str(&format!("  Write Pattern:   {}\n", p.as_str()));
+    }
+    if r.buffered {
+        s.push_str("  I/O Mode:        buffered (includes OS cache)\n");
+    }
+    if r.write_through {
+        s.push_str("  Write Mode:      write-through (durable before completion)\n");
+    }
+    let ring_modes: Vec<&str> = [
+        (r.io_poll, "IOPOLL"),
+        (r.sq_poll, "SQPOLL"),
+        (r.fixed_buffers, "fixed buffers"),
+    ]
+    .into_iter()
+    .filter_map(|(on, name)| on.then_some(name))
+    .collect();
+    if !ring_modes.is_empty() {
+        s.push_str(&format!("  io_uring:        {}\n", ring_modes.join(" + ")));
+    }
+    s.push_str(&format!(
+        "  Duration:        {} seconds ({:.2} s elapsed)\n",
+        r.duration_secs, r.elapsed_secs
     ));
+    if r.warmup_secs > 0 {
+        s.push_str(&format!("  Warmup:          {} seconds (not measured)\n", r.warmup_secs));
+    }
+    if let Some(pct) = r.read_pct {
+        s.push_str(&format!("  Read/Write Mix:  {}/{}\n", pct, 100 - pct));
+    }
+    if let (Some(reads), Some(writes)) = (r.avg_inflight_reads, r.avg_inflight_writes) {
+        s.push_str(&format!("  In Flight:       {:.1} reads / {:.1} writes per thread\n", reads, writes));
+    }
+    if let Some(node) = r.numa_node {
+        s.push_str(&format!("  NUMA Node:       {}\n", node));
+    } else if r.numa_local {
+        s.push_str("  NUMA Node:       each worker's own (buffers local to its core)\n");
+    }
+    if let Some(cores) = r.pinned_cores {
+        s.push_str(&format!(
+            "  CPU Pinning:     one core per worker, across {} core{}\n",
+            cores,
+            if cores == 1 { "" } else { "s" }
+        ));
+    }
     s.push_str(&format!(
-        "  P50 Latency:   {:>10.2} us\n",
-        r.latency_p50_us
+        "  Throughput:    {:>10} {}\n",
+        nf.throughput(r.throughput_mbps, 2),
+        nf.throughput_unit()
     ));
+    s.push_str(&format!("  IOPS:          {:>10}\n", nf.count(r.iops)));
+    if let Some(d) = &r.split {
+        s.push_str(&format!(
+            "    Reads:       {:>10} {:<5} {:>10} IOPS\n",
+            nf.throughput(d.read_mbps, 2),
+            nf.throughput_unit(),
+            nf.count(d.read_iops)
+        ));
+        s.push_str(&format!(
+            "    Writes:      {:>10} {:<5} {:>10} IOPS\n",
+            nf.throughput(d.write_mbps, 2),
+            nf.throughput_unit(),
+            nf.count(d.write_iops)
+        ));
+    }
     s.push_str(&format!(
-        "  P99 Latency:   {:>10.2} us\n",
-        r.latency_p99_us
+        "  Total Data:    {:>10} {}\n",
+        nf.size(r.total_bytes as f64, 2),
+        nf.size_unit()
     ));
+    s.push_str(&format!("  Avg Latency:   {:>10} us\n", nf.fixed(r.latency_avg_us, 2)));
+    s.push_str(&format!("  P50 Latency:   {:>10} us\n", nf.fixed(r.latency_p50_us, 2)));
+    s.push_str(&format!("  P99 Latency:   {:>10} us\n", nf.fixed(r.latency_p99_us, 2)));
+    s.push_str(&format!("  P99.9 Latency: {:>10} us\n", nf.fixed(r.latency_p999_us, 2)));
+    s.push_str(&format!("  P99.99 Latency:{:>10} us\n", nf.fixed(r.latency_p9999_us, 2)));
+    s.push_str(&format!("  Min Latency:   {:>10} us\n", nf.fixed(r.latency_min_us, 2)));
+    s.push_str(&format!("  Max Latency:   {:>10} us\n", nf.fixed(r.latency_max_us, 2)));
+    s.push_str(&format!(
+        "  Latency StdDev:{:>10} us (CV {:.2})\n",
+        nf.fixed(r.latency_stddev_us, 2),
+        r.latency_cv()
+    ));
+    if let Some(d) = &r.split {
+        s.push_str(&format!(
+            "  Latency by direction (us): {:>10} {:>10} {:>10} {:>10} {:>10}\n",
+            "Avg", "P50", "P99", "P99.9", "Max"
+        ));
+        for (label, lat) in [
+            ("Reads", [d.read_latency_avg_us, d.read_latency_p50_us, d.read_latency_p99_us, d.read_latency_p999_us, d.read_latency_max_us]),
+            ("Writes", [d.write_latency_avg_us, d.write_latency_p50_us, d.write_latency_p99_us, d.write_latency_p999_us, d.write_latency_max_us]),
+        ] {
+            s.push_str(&format!("    {:<24}", format!("{}:", label)));
+            for v in lat {
+                s.push_str(&format!(" {:>10}", nf.fixed(v, 2)));
+            }
+            s.push('\n');
+        }
+    }
+    if let Some(rep) = &r.repeat {
+        s.push_str(&format!(
+            "  {:<26} {:>10} {:>10} {:>10} {:>10}\n",
+            format!("Runs ({}, mean above):", rep.runs.len()),
+            nf.throughput_unit(),
+            "IOPS",
+            "Avg us",
+            "P99 us"
+        ));
+        let rows = rep
+            .runs
+            .iter()
+            .enumerate()
+            .map(|(i, run)| (format!("Run {}:", i + 1), run.throughput_mbps, run.iops, run.latency_avg_us, run.latency_p99_us))
+            .chain(std::iter::once((
+                "StdDev:".to_string(),
+                rep.throughput_stddev_mbps,
+                rep.iops_stddev,
+                rep.latency_avg_stddev_us,
+                rep.latency_p99_stddev_us,
+            )));
+        for (label, mbps, iops, avg, p99) in rows {
+            s.push_str(&format!(
+                "    {:<24} {:>10} {:>10} {:>10} {:>10}\n",
+                label,
+                nf.throughput(mbps, 2),
+                nf.count(iops),
+                nf.fixed(avg, 2),
+                nf.fixed(p99, 2)
+            ));
+        }
+    }
+    if let Some(d) = r.drain_secs {
+        s.push_str(&format!("  Drain Time:    {:>10} s\n", nf.fixed(d, 2)));
+        s.push_str(&format!(
+            "  Durable:       {:>10} {}\n",
+            nf.throughput(r.total_bytes as f64 / (r.elapsed_secs + d) / MIB, 2),
+            nf.throughput_unit()
+        ));
+    }
+    if let Some(t) = &r.thread_spread {
+        s.push_str(&format!("  Per-Thread P99:  {}\n", t.describe(nf)));
+    }
+    if let Some(v) = &r.verify {
+        s.push_str(&format!("  Verify:          {}\n", v.describe(nf)));
+    }
+    if r.dropped_submissions > 0 {
+        s.push_str(&format!(
+            "  Dropped I/Os:    {} (io_uring queue full; effective queue depth shrank)\n",
+            nf.count(r.dropped_submissions as f64)
+        ));
+    }
+    if r.io_errors > 0 {
+        s.push_str(&format!(
+            "  I/O Errors:      {} (first: {})\n",
+            nf.count(r.io_errors as f64),
+            r.first_io_error.as_deref().unwrap_or("short transfer")
+        ));
+    }
+    if let Some(f) = &r.fsync {
+        s.push_str(&format!(
+            "  Fsync:           every {} writes, {} flushes, avg {} us, max {} us\n",
+            f.every,
+            nf.count(f.count as f64),
+            nf.fixed(f.latency_avg_us, 2),
+            nf.fixed(f.latency_max_us, 2)
+        ));
+    }
+    if let Some(target) = r.target_iops {
+        s.push_str(&format!(
+            "  Target IOPS:     {} (reached {:.1}%)\n",
+            nf.count(target as f64),
+            r.iops / target as f64 * 100.0
+        ));
+    }
+    if r.stop_reason != StopReason::Duration {
+        s.push_str(&format!("  Stopped By:      {}\n", r.stop_reason.as_str()));
+    }
+    if let Some(t) = r.time_to_steady_secs {
+        s.push_str(&format!("  Time to Steady:{:>10.0} s\n", t));
+    }
+    if !r.per_device.is_empty() {
+        s.push_str("  Per Device:\n");
+        for d in &r.per_device {
+            s.push_str(&format!(
+                "    {}: {} {}, {} IOPS, avg {} us, p99 {} us, max {} us\n",
+                d.path,
+                nf.throughput(d.throughput_mbps, 2),
+                nf.throughput_unit(),
+                nf.count(d.iops),
+                nf.fixed(d.latency_avg_us, 2),
+                nf.fixed(d.latency_p99_us, 2),
+                nf.fixed(d.latency_max_us, 2)
+            ));
+        }
+    }
     s.push('\n');
 }
+
+/// Escape an InfluxDB tag value: commas, spaces, equals signs and backslashes,
+/// so a Windows path like `\\.\PhysicalDrive0` is stored as written
+fn influx_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | ' ' | '=' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// POST `body` to `url` (host[:port][/path], the `http://` already removed)
+/// over a plain HTTP/1.1 connection and fail unless the server answers 2xx.
+/// `INFLUX_TOKEN`, when set, goes in the Authorization header (InfluxDB 2).
+fn post_http(url: &str, body: &str) -> io::Result<()> {
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::time::Duration;
+
+    let (host, path) = match url.find('/') {
+        Some(i) => (&url[..i], &url[i..]),
+        None => (url, "/"),
+    };
+    let addr_str = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    let addr = addr_str
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", host)))?;
+    let timeout = Duration::from_secs(10);
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let auth = match std::env::var("INFLUX_TOKEN") {
+        Ok(token) if !token.is_empty() => format!("Authorization: Token {}\r\n", token),
+        _ => String::new(),
+    };
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; charset=utf-8\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        auth,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status_line = response.lines().next().unwrap_or("");
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => {
+            // InfluxDB explains a rejected write in the body
+            let detail = response.split("\r\n\r\n").nth(1).unwrap_or("").trim();
+            Err(io::Error::other(format!("server answered '{}' {}", status_line, detail).trim_end().to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A 4K random-read result with these headline numbers
+    fn result(throughput_mbps: f64, iops: f64, latency_avg_us: f64, latency_p99_us: f64) -> TestResult {
+        serde_json::from_value(serde_json::json!({
+            "throughput_mbps": throughput_mbps,
+            "iops": iops,
+            "latency_avg_us": latency_avg_us,
+            "latency_p50_us": latency_avg_us,
+            "latency_p99_us": latency_p99_us,
+            "latency_p999_us": latency_p99_us,
+            "latency_p9999_us": latency_p99_us,
+            "latency_min_us": latency_avg_us / 2.0,
+            "latency_max_us": latency_p99_us * 2.0,
+            "latency_stddev_us": 1.0,
+            "total_bytes": (iops * 4096.0) as u64,
+            "threads": 4,
+            "queue_depth": 32,
+            "block_size_kb": 4,
+            "access_pattern": "random",
+            "duration_secs": 1,
+            "elapsed_secs": 1.0,
+            "warmup_secs": 0,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn influx_tags_escape_backslashes_spaces_commas_and_equals() {
+        assert_eq!(influx_tag(r"\\.\PhysicalDrive0"), r"\\\\.\\PhysicalDrive0");
+        assert_eq!(influx_tag("lab host,rack=2"), r"lab\ host\,rack\=2");
+        assert_eq!(influx_tag("/dev/nvme0n1"), "/dev/nvme0n1");
+    }
+
+    #[test]
+    fn influx_lines_have_one_point_per_test() {
+        let mut report = BenchmarkReport::new(r"\\.\PhysicalDrive0");
+        report.test_date = Local.timestamp_opt(1_700_000_000, 123).unwrap();
+        report.hostname = Some("lab host,rack 2".to_string());
+        report.read_iops = Some(result(512.5, 131_200.0, 243.25, 900.0));
+        let mut errors = result(100.0, 25_600.0, 1000.0, 4000.0);
+        errors.io_errors = 3;
+        report.write_iops = Some(errors);
+
+        let lines = report.generate_influx_lines();
+        let tags = r"device=\\\\.\\PhysicalDrive0,host=lab\ host\,rack\ 2,block_size_kb=4,queue_depth=32";
+        assert_eq!(
+            lines,
+            format!(
+                "fourcorners,test=read_iops,{tags} throughput_mbps=512.5,iops=131200,latency_avg_us=243.25,\
+                 latency_p50_us=243.25,latency_p99_us=900,latency_p999_us=900,latency_max_us=1800,\
+                 threads=4i,io_errors=0i 1700000000000000123\n\
+                 fourcorners,test=write_iops,{tags} throughput_mbps=100,iops=25600,latency_avg_us=1000,\
+                 latency_p50_us=1000,latency_p99_us=4000,latency_p999_us=4000,latency_max_us=8000,\
+                 threads=4i,io_errors=3i 1700000000000000123\n"
+            )
+        );
+    }
+
+    #[test]
+    fn repeated_runs_combine_into_their_mean() {
+        let mut runs = vec![
+            result(100.0, 1000.0, 40.0, 90.0),
+            result(200.0, 2000.0, 20.0, 60.0),
+            result(300.0, 3000.0, 30.0, 120.0),
+        ];
+        for (run, (errors, achieved)) in runs.iter_mut().zip([(1, 30.0), (0, 31.0), (2, 32.0)]) {
+            run.io_errors = errors;
+            run.achieved_queue_depth = Some(achieved);
+        }
+        runs[2].pinned_cores = Some(4);
+
+        let combined = TestResult::combine_runs(runs).unwrap();
+        assert_eq!(combined.throughput_mbps, 200.0);
+        assert_eq!(combined.iops, 2000.0);
+        assert_eq!(combined.latency_avg_us, 30.0);
+        assert_eq!(combined.latency_p99_us, 90.0);
+        assert_eq!(combined.total_bytes, 2000 * 4096);
+        assert_eq!(combined.achieved_queue_depth, Some(31.0));
+        // Extremes over every run, errors summed rather than averaged
+        assert_eq!(combined.latency_min_us, 10.0);
+        assert_eq!(combined.latency_max_us, 240.0);
+        assert_eq!(combined.io_errors, 3);
+        // Fields that aren't combined are the last run's
+        assert_eq!(combined.pinned_cores, Some(4));
+
+        // The spread across runs is the sample standard deviation (n - 1)
+        let repeat = combined.repeat.unwrap();
+        assert_eq!(repeat.runs.len(), 3);
+        assert_eq!(repeat.runs[1].iops, 2000.0);
+        assert_eq!(repeat.throughput_stddev_mbps, 100.0);
+        assert_eq!(repeat.iops_stddev, 1000.0);
+        assert_eq!(repeat.latency_avg_stddev_us, 10.0);
+        assert_eq!(repeat.latency_p99_stddev_us, 30.0);
+    }
+
+    #[test]
+    fn a_single_run_is_returned_as_is() {
+        assert!(TestResult::combine_runs(Vec::new()).is_none());
+        let combined = TestResult::combine_runs(vec![result(100.0, 1000.0, 40.0, 90.0)]).unwrap();
+        assert_eq!(combined.iops, 1000.0);
+        assert!(combined.repeat.is_none());
+    }
+
+    #[test]
+    fn achieved_queue_depth_needs_every_run() {
+        let mut runs = vec![result(100.0, 1000.0, 40.0, 90.0), result(200.0, 2000.0, 20.0, 60.0)];
+        runs[0].achieved_queue_depth = Some(30.0);
+        assert_eq!(TestResult::combine_runs(runs).unwrap().achieved_queue_depth, None);
+    }
+}
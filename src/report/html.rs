@@ -0,0 +1,344 @@
+use super::{BenchmarkReport, LatencyBucket, NumberFormat, TestResult, TimelinePoint};
+
+const CHART_WIDTH: f64 = 640.0;
+const CHART_HEIGHT: f64 = 220.0;
+/// Room for the axis labels: left, right, top, bottom
+const MARGIN: (f64, f64, f64, f64) = (64.0, 16.0, 12.0, 36.0);
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 960px; color: #222; }
+h1 { margin-bottom: 0.2em; }
+h2 { margin-top: 2em; border-bottom: 1px solid #ccc; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { padding: 0.3em 0.8em; border-bottom: 1px solid #eee; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+.meta td { text-align: left; }
+.charts { display: flex; flex-wrap: wrap; gap: 1em; }
+.chart h3 { font-size: 0.95em; margin: 0.5em 0; }
+svg text { font-size: 11px; fill: #555; }
+.line { fill: none; stroke: #2a6fdb; stroke-width: 2; }
+.bar { fill: #2a6fdb; }
+.grid { stroke: #e4e4e4; }
+";
+
+/// Escape text for use in HTML content and attributes
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Short label for a latency bound, e.g. `512 us`, `2 ms`, `1.1 s`
+fn latency_label(us: f64) -> String {
+    if us >= 1_000_000.0 {
+        format!("{:.1} s", us / 1_000_000.0)
+    } else if us >= 1_000.0 {
+        format!("{:.0} ms", us / 1_000.0)
+    } else if us >= 1.0 {
+        format!("{:.0} us", us)
+    } else {
+        format!("{:.0} ns", us * 1_000.0)
+    }
+}
+
+/// Up to five round values from 0 to at least `max` for a chart axis
+fn axis_ticks(max: f64) -> Vec<f64> {
+    if max <= 0.0 {
+        return vec![0.0, 1.0];
+    }
+    let raw = max / 4.0;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 2.5, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= raw)
+        .unwrap_or(10.0 * magnitude);
+    let count = (max / step).ceil() as usize;
+    (0..=count).map(|i| i as f64 * step).collect()
+}
+
+/// Gridlines and labels of the y axis; returns the scale's top value
+fn y_axis(svg: &mut String, max: f64, nf: NumberFormat) -> f64 {
+    let (left, right, top, bottom) = MARGIN;
+    let ticks = axis_ticks(max);
+    let y_max = *ticks.last().unwrap();
+    // Steps below 1 (a nearly idle test) need decimals to tell the ticks apart
+    let decimals = if ticks[1] < 1.0 { 2 } else { 0 };
+    for &tick in &ticks {
+        let y = CHART_HEIGHT - bottom - tick / y_max * (CHART_HEIGHT - top - bottom);
+        svg.push_str(&format!(
+            "<line class=\"grid\" x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\"/>\
+             <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            left,
+            y,
+            CHART_WIDTH - right,
+            y,
+            left - 6.0,
+            y + 4.0,
+            nf.fixed(tick, decimals)
+        ));
+    }
+    y_max
+}
+
+/// Throughput over the run as a line chart
+fn timeline_chart(points: &[TimelinePoint], nf: NumberFormat) -> String {
+    let (left, right, top, bottom) = MARGIN;
+    let values: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| (p.elapsed_secs, nf.throughput_value(p.mbps)))
+        .collect();
+    let x_max = values.iter().map(|&(t, _)| t).fold(1.0, f64::max);
+    let v_max = values.iter().map(|&(_, v)| v).fold(0.0, f64::max);
+
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" role=\"img\">",
+        CHART_WIDTH, CHART_HEIGHT
+    );
+    let y_max = y_axis(&mut svg, v_max, nf);
+    let x = |t: f64| left + t / x_max * (CHART_WIDTH - left - right);
+    let y = |v: f64| CHART_HEIGHT - bottom - v / y_max * (CHART_HEIGHT - top - bottom);
+
+    let line: Vec<String> = values
+        .iter()
+        .map(|&(t, v)| format!("{:.1},{:.1}", x(t), y(v)))
+        .collect();
+    svg.push_str(&format!("<polyline class=\"line\" points=\"{}\"/>", line.join(" ")));
+    for t in [0.0, x_max / 2.0, x_max] {
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{:.0}s</text>",
+            x(t),
+            CHART_HEIGHT - bottom + 16.0,
+            t
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Sampled latencies per power-of-two range as a bar chart
+fn latency_chart(buckets: &[LatencyBucket], nf: NumberFormat) -> String {
+    let (left, right, top, bottom) = MARGIN;
+    let total: u64 = buckets.iter().map(|b| b.count).sum();
+    let max = buckets.iter().map(|b| b.count).max().unwrap_or(0) as f64;
+
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" role=\"img\">",
+        CHART_WIDTH, CHART_HEIGHT
+    );
+    let y_max = y_axis(&mut svg, max, nf);
+    let slot = (CHART_WIDTH - left - right) / buckets.len().max(1) as f64;
+    // Label every bar if they fit, otherwise every other one
+    let label_every = if slot < 40.0 { 2 } else { 1 };
+    for (i, b) in buckets.iter().enumerate() {
+        let height = b.count as f64 / y_max * (CHART_HEIGHT - top - bottom);
+        let x = left + i as f64 * slot;
+        svg.push_str(&format!(
+            "<rect class=\"bar\" x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\">\
+             <title>{} to {}: {} samples ({:.2}%)</title></rect>",
+            x + slot * 0.1,
+            CHART_HEIGHT - bottom - height,
+            slot * 0.8,
+            height,
+            latency_label(b.from_us),
+            latency_label(b.to_us),
+            nf.count(b.count as f64),
+            b.count as f64 / total.max(1) as f64 * 100.0
+        ));
+        if i % label_every == 0 {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+                x + slot / 2.0,
+                CHART_HEIGHT - bottom + 16.0,
+                latency_label(b.from_us)
+            ));
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn metric_row(s: &mut String, name: &str, value: String) {
+    s.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", name, value));
+}
+
+fn test_section(s: &mut String, title: &str, r: &TestResult, nf: NumberFormat) {
+    s.push_str(&format!("<h2>{}</h2>\n", title));
+    let pattern = match (r.distribution, r.skew) {
+        (Some(dist), Some(skew)) => format!("{} ({} {})", r.access_pattern.as_str(), dist.as_str(), skew),
+        _ => r.access_pattern.as_str().to_string(),
+    };
+    s.push_str(&format!(
+        "<p>{} blocks, {} threads, QD {} per thread, {}, {:.1}s measured</p>\n",
+        r.block_size(),
+        r.threads,
+        r.queue_depth,
+        pattern,
+        r.elapsed_secs
+    ));
+
+    s.push_str("<table>\n");
+    metric_row(s, "Throughput", format!("{} {}", nf.throughput(r.throughput_mbps, 2), nf.throughput_unit()));
+    metric_row(s, "IOPS", nf.count(r.iops));
+    if let Some(qd) = r.achieved_queue_depth {
+        metric_row(s, "Achieved QD", format!("{:.1} of {} per thread", qd, r.queue_depth));
+    }
+    if let Some(target) = r.target_iops {
+        metric_row(s, "Target IOPS", nf.count(target as f64));
+    }
+    metric_row(s, "Avg latency", format!("{} us", nf.fixed(r.latency_avg_us, 2)));
+    metric_row(s, "P50 latency", format!("{} us", nf.fixed(r.latency_p50_us, 2)));
+    metric_row(s, "P99 latency", format!("{} us", nf.fixed(r.latency_p99_us, 2)));
+    metric_row(s, "P99.9 latency", format!("{} us", nf.fixed(r.latency_p999_us, 2)));
+    metric_row(s, "Max latency", format!("{} us", nf.fixed(r.latency_max_us, 2)));
+    if let Some(d) = &r.split {
+        for (label, mbps, iops, avg, p99) in [
+            ("Reads", d.read_mbps, d.read_iops, d.read_latency_avg_us, d.read_latency_p99_us),
+            ("Writes", d.write_mbps, d.write_iops, d.write_latency_avg_us, d.write_latency_p99_us),
+        ] {
+            metric_row(
+                s,
+                label,
+                format!(
+                    "{} {}, {} IOPS, avg {} us, p99 {} us",
+                    nf.throughput(mbps, 2),
+                    nf.throughput_unit(),
+                    nf.count(iops),
+                    nf.fixed(avg, 2),
+                    nf.fixed(p99, 2)
+                ),
+            );
+        }
+    }
+    if let Some(rep) = &r.repeat {
+        metric_row(
+            s,
+            "Runs",
+            format!(
+                "{} (mean shown); stddev {} {}, {} IOPS, p99 {} us",
+                rep.runs.len(),
+                nf.throughput(rep.throughput_stddev_mbps, 2),
+                nf.throughput_unit(),
+                nf.count(rep.iops_stddev),
+                nf.fixed(rep.latency_p99_stddev_us, 2)
+            ),
+        );
+        for (i, run) in rep.runs.iter().enumerate() {
+            metric_row(
+                s,
+                &format!("Run {}", i + 1),
+                format!(
+                    "{} {}, {} IOPS, avg {} us, p99 {} us",
+                    nf.throughput(run.throughput_mbps, 2),
+                    nf.throughput_unit(),
+                    nf.count(run.iops),
+                    nf.fixed(run.latency_avg_us, 2),
+                    nf.fixed(run.latency_p99_us, 2)
+                ),
+            );
+        }
+    }
+    if let Some(t) = r.time_to_steady_secs {
+        metric_row(s, "Time to steady state", format!("{:.0} s", t));
+    }
+    if r.io_errors > 0 {
+        metric_row(s, "I/O errors", nf.count(r.io_errors as f64));
+    }
+    s.push_str("</table>\n");
+
+    s.push_str("<div class=\"charts\">\n");
+    if r.timeline.len() > 1 {
+        s.push_str(&format!(
+            "<div class=\"chart\"><h3>Throughput over time ({})</h3>{}</div>\n",
+            nf.throughput_unit(),
+            timeline_chart(&r.timeline, nf)
+        ));
+    }
+    if !r.latency_distribution.is_empty() {
+        s.push_str(&format!(
+            "<div class=\"chart\"><h3>Latency distribution (sampled I/Os)</h3>{}</div>\n",
+            latency_chart(&r.latency_distribution, nf)
+        ));
+    }
+    s.push_str("</div>\n");
+}
+
+impl BenchmarkReport {
+    /// A single self-contained page: summary table, then per test its numbers,
+    /// a throughput-over-time chart and a latency histogram, drawn as inline SVG
+    pub fn generate_html_report(&self) -> String {
+        let nf = self.number_format;
+        let mut s = String::new();
+        s.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        s.push_str(&format!(
+            "<title>4Corners report - {}</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+            escape(&self.device),
+            STYLE
+        ));
+        s.push_str("<h1>4Corners Disk Benchmark Report</h1>\n<table class=\"meta\">\n");
+        metric_row(&mut s, "Test Date", self.test_date.format("%Y-%m-%d %H:%M:%S").to_string());
+        if let Some(host) = &self.hostname {
+            metric_row(&mut s, "Host", escape(host));
+        }
+        if !self.os.is_empty() {
+            metric_row(&mut s, "Platform", escape(&self.os));
+        }
+        if !self.version.is_empty() {
+            metric_row(&mut s, "4c Version", escape(&self.version));
+        }
+        metric_row(&mut s, "Device", escape(&self.device));
+        for d in &self.device_details {
+            if let Some(identity) = d.identity() {
+                metric_row(&mut s, &escape(&d.path), escape(&identity));
+            }
+        }
+        if let Some(notes) = &self.notes {
+            metric_row(&mut s, "Notes", escape(notes));
+        }
+        if let Some(seed) = self.seed {
+            metric_row(&mut s, "Seed", seed.to_string());
+        }
+        if self.interrupted {
+            metric_row(&mut s, "Interrupted", "yes (partial results)".to_string());
+        }
+        if self.max_runtime_reached {
+            metric_row(&mut s, "Max Runtime", "reached (partial results)".to_string());
+        }
+        s.push_str("</table>\n");
+
+        s.push_str(&format!(
+            "<h2>Summary</h2>\n<table>\n<tr><th>Test</th><th>Block</th><th>{}</th><th>IOPS</th>\
+             <th>Avg (us)</th><th>P99 (us)</th></tr>\n",
+            nf.throughput_unit()
+        ));
+        for (name, r) in self.results() {
+            s.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                name,
+                r.block_size(),
+                nf.throughput(r.throughput_mbps, 2),
+                nf.count(r.iops),
+                nf.fixed(r.latency_avg_us, 2),
+                nf.fixed(r.latency_p99_us, 2)
+            ));
+        }
+        s.push_str("</table>\n");
+
+        let tests = [
+            ("Read Throughput", &self.read_throughput),
+            ("Write Throughput", &self.write_throughput),
+            ("Read IOPS", &self.read_iops),
+            ("Write IOPS", &self.write_iops),
+            ("Mixed Read/Write", &self.mixed),
+        ];
+        for (title, r) in tests {
+            if let Some(r) = r {
+                test_section(&mut s, title, r, nf);
+            }
+        }
+
+        s.push_str("</body>\n</html>\n");
+        s
+    }
+}
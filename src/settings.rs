@@ -0,0 +1,489 @@
+use crate::engine::{
+    AccessPattern, BlockSizeMix, FileAlloc, OffsetDistribution, QdSemantics, WritePattern, DEFAULT_HISTOGRAM_BITS, MAX_HISTOGRAM_BITS,
+    MIN_HISTOGRAM_BITS,
+};
+use crate::report::Units;
+use clap::{Args, FromArgMatches};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Everything that shapes a benchmark run: the devices, the tests and how each
+/// one runs. The `4c` command line is these flags plus its own (prompts,
+/// report files, dry runs), so each field's doc is the flag's help text.
+#[derive(Args, Debug, Clone)]
+pub struct BenchmarkConfig {
+    /// Device or file path(s) - can specify multiple times or comma-separated
+    /// On Windows: use \\.\PhysicalDrive4 or just 4
+    #[arg(short, long)]
+    pub device: Vec<String>,
+
+    /// File listing device paths, one per line (blank lines and # comments ignored)
+    #[arg(long)]
+    pub devices_file: Option<PathBuf>,
+
+    /// Test duration in seconds
+    #[arg(long, default_value_t = 30)]
+    pub duration: u32,
+
+    /// End each test once this many bytes have been transferred (K/M/G/T suffixes allowed)
+    #[arg(long, value_parser = parse_size)]
+    pub size_limit: Option<u64>,
+
+    /// End each test once this many I/Os have completed
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub ops_limit: Option<u64>,
+
+    /// Pace each test to this many IOPS in total instead of running flat out, to measure latency at a fixed load
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub target_iops: Option<u64>,
+
+    /// Seconds to run each test before measuring (excluded from results)
+    #[arg(long, default_value_t = 0)]
+    pub warmup: u32,
+
+    /// Wall-clock budget in seconds for the whole run, device preparation included; when it runs out the current step stops early and the rest are skipped
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_runtime: Option<u64>,
+
+    /// Read throughput threads
+    #[arg(long, default_value_t = 30)]
+    pub read_tp_threads: u32,
+
+    /// Write throughput threads
+    #[arg(long, default_value_t = 16)]
+    pub write_tp_threads: u32,
+
+    /// Read IOPS threads
+    #[arg(long, default_value_t = 120)]
+    pub read_iops_threads: u32,
+
+    /// Write IOPS threads
+    #[arg(long, default_value_t = 120)]
+    pub write_iops_threads: u32,
+
+    /// Read throughput queue depth per thread
+    #[arg(long, default_value_t = 1)]
+    pub read_tp_qd: u32,
+
+    /// Write throughput queue depth per thread
+    #[arg(long, default_value_t = 1)]
+    pub write_tp_qd: u32,
+
+    /// Read IOPS queue depth per thread
+    #[arg(long, default_value_t = 1)]
+    pub read_iops_qd: u32,
+
+    /// Write IOPS queue depth per thread
+    #[arg(long, default_value_t = 1)]
+    pub write_iops_qd: u32,
+
+    /// Read throughput block size (KB)
+    #[arg(long, default_value_t = 128)]
+    pub read_tp_bs: u32,
+
+    /// Write throughput block size (KB)
+    #[arg(long, default_value_t = 64)]
+    pub write_tp_bs: u32,
+
+    /// Read IOPS block size (KB)
+    #[arg(long, default_value_t = 4)]
+    pub read_iops_bs: u32,
+
+    /// Write IOPS block size (KB)
+    #[arg(long, default_value_t = 4)]
+    pub write_iops_bs: u32,
+
+    /// Mixed test threads
+    #[arg(long, default_value_t = 120)]
+    pub mixed_threads: u32,
+
+    /// Mixed test queue depth per thread
+    #[arg(long, default_value_t = 1)]
+    pub mixed_qd: u32,
+
+    /// Mixed test block size (KB)
+    #[arg(long, default_value_t = 4)]
+    pub mixed_bs: u32,
+
+    /// Whether the --*-qd options give the depth per thread or the total per device, split across its threads
+    #[arg(long, value_enum, default_value = "per-thread")]
+    pub qd_semantics: QdSemantics,
+
+    /// Run each selected test once per block size in KB instead of at its own block size (comma-separated, e.g. 4,8,64,1024)
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(1..))]
+    pub bs_sweep: Vec<u32>,
+
+    /// Run each selected test once per queue depth instead of at its own depth, for a latency-vs-throughput curve (comma-separated, e.g. 1,2,4,8,16,32,64)
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "bs_sweep")]
+    pub qd_sweep: Vec<u32>,
+
+    /// Run each selected test this many times and report the mean, with each run and the spread between runs
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: u32,
+
+    /// Give each I/O a size drawn from this mix instead of the test's block size, e.g. 4k:70,64k:30 (KB:percent of I/Os, adding up to 100)
+    #[arg(long, value_name = "SIZE:PERCENT,...", conflicts_with_all = ["bs_sweep", "verify"])]
+    pub bs_mix: Option<BlockSizeMix>,
+
+    /// Run a mixed read/write test with this percentage of reads (0-100, default 70 with --tests mixed)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub rw_mix: Option<u8>,
+
+    /// Prep device before testing (writes random data)
+    #[arg(long)]
+    pub prep: bool,
+
+    /// Prep only these devices (comma-separated or repeated; must also be given with --device)
+    #[arg(long)]
+    pub prep_devices: Vec<String>,
+
+    /// Write this percentage of each device (or test range) sequentially before testing, then keep every test inside the filled part (1-100)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100), conflicts_with_all = ["prep", "prep_devices"])]
+    pub fill_percent: Option<u8>,
+
+    /// Discard (TRIM) the device, or the --range-start/--range-len window, before testing, so an SSD starts from its erased state
+    #[arg(long)]
+    pub trim: bool,
+
+    /// Create a file device before testing
+    #[arg(long)]
+    pub create_file: bool,
+
+    /// File device size in GiB (if creating)
+    #[arg(long, default_value_t = 10)]
+    pub file_size: u64,
+
+    /// How --create-file sets aside the file: fill writes every byte (slow); preallocate only allocates the space (instant, but unwritten)
+    #[arg(long, value_enum, default_value = "fill", requires = "create_file")]
+    pub file_alloc: FileAlloc,
+
+    /// Delete the file made by --create-file once the reports are saved (default: keep it)
+    #[arg(long, requires = "create_file")]
+    pub cleanup_file: bool,
+
+    /// Fall back to buffered I/O when a device rejects direct (unbuffered) access
+    #[arg(long)]
+    pub buffered_fallback: bool,
+
+    /// Use buffered I/O through the OS cache instead of direct I/O (filesystem and cache-behavior testing)
+    #[arg(long, conflicts_with = "buffered_fallback")]
+    pub buffered: bool,
+
+    /// Open devices for writing with O_DSYNC on Linux, so each write is on stable media before it completes (Windows always writes through)
+    #[arg(long)]
+    pub write_through: bool,
+
+    /// Bind all worker threads and their I/O buffers to one NUMA node
+    #[arg(long)]
+    pub affinity_node: Option<u32>,
+
+    /// Pin each worker thread to its own CPU core, round-robin (within --affinity-node if given)
+    #[arg(long)]
+    pub cpu_affinity: bool,
+
+    /// Linux: pin workers as --cpu-affinity does and allocate each one's I/O buffers on its core's NUMA node
+    #[arg(long)]
+    pub numa: bool,
+
+    /// After each write test, flush the device write cache and report how long it took
+    #[arg(long)]
+    pub drain_flush: bool,
+
+    /// In write tests, flush the device after every N completed writes per thread (0 = never)
+    #[arg(long, default_value_t = 0)]
+    pub fsync_every: u32,
+
+    /// Stamp every block written by the write tests, then read it back and count corrupt blocks
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Free-form note recorded in the report header (e.g. "after cable swap")
+    #[arg(long, alias = "comment")]
+    pub notes: Option<String>,
+
+    /// Stream progress and results as newline-delimited JSON to this TCP address (host:port)
+    #[arg(long)]
+    pub stream_tcp: Option<String>,
+
+    /// Flush OS caches for each device before every test (best effort; some steps need admin/root)
+    #[arg(long)]
+    pub flush_caches: bool,
+
+    /// Serve live counters as JSON on this Unix socket (Linux) or named pipe (Windows, \\.\pipe\name)
+    #[arg(long)]
+    pub control_socket: Option<PathBuf>,
+
+    /// Record the latency of every Nth I/O (1 = every I/O: most accurate percentiles, more CPU)
+    #[arg(long, default_value_t = 64, value_parser = clap::value_parser!(u32).range(1..))]
+    pub latency_sample_rate: u32,
+
+    /// Latency histogram precision in bits (4-14): percentiles are within 1/2^(N-1) of the true value; memory doubles per bit
+    #[arg(long, default_value_t = DEFAULT_HISTOGRAM_BITS, value_parser = clap::value_parser!(u32).range(MIN_HISTOGRAM_BITS as i64..=MAX_HISTOGRAM_BITS as i64))]
+    pub histogram_bits: u32,
+
+    /// Report the spread of each worker thread's own p99 latency, to spot a straggler
+    #[arg(long)]
+    pub per_thread_stats: bool,
+
+    /// Print progress as one JSON object per interval on stderr instead of the console line
+    #[arg(long)]
+    pub progress_json: bool,
+
+    /// Append a CSV row per progress interval (throughput, IOPS, avg latency over that interval) to this file
+    #[arg(long)]
+    pub timeseries: Option<PathBuf>,
+
+    /// Append every sampled I/O latency (thread, time, latency, read/write) to this CSV file, for analysis outside 4c
+    #[arg(long)]
+    pub latency_trace: Option<PathBuf>,
+
+    /// Decimal places for throughput and latency in console and text output
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=6))]
+    pub precision: Option<u8>,
+
+    /// Units for throughput and sizes in console, text and Markdown output: si (MB/s, GB) or iec (MiB/s, GiB)
+    #[arg(long, value_enum, default_value = "iec")]
+    pub units: Units,
+
+    /// Access pattern for the throughput tests (IOPS tests are always random)
+    #[arg(long, value_enum, default_value = "sequential")]
+    pub tp_pattern: AccessPattern,
+
+    /// Seed for the random offsets and write buffer contents; the seed used is recorded in the report (default: random)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Linux: busy-poll for completions (IORING_SETUP_IOPOLL); needs direct I/O and NVMe poll queues
+    #[arg(long)]
+    pub io_poll: bool,
+
+    /// Linux: let a kernel thread poll the submission queue (IORING_SETUP_SQPOLL), so submitting costs no syscall
+    #[arg(long)]
+    pub sq_poll: bool,
+
+    /// Linux: register the I/O buffers and device with io_uring once instead of mapping them on every I/O
+    #[arg(long)]
+    pub fixed_buffers: bool,
+
+    /// In write-only tests, issue every queue slot of a thread from one buffer instead of one buffer per slot
+    #[arg(long)]
+    pub shared_write_buffer: bool,
+
+    /// What written blocks contain, for write tests and --prep/--create-file: zero, random or repeating
+    #[arg(long, value_enum, default_value = "random")]
+    pub write_pattern: WritePattern,
+
+    /// Replay a pre-generated pool of N random offsets per thread instead of drawing each offset fresh (0 = off)
+    #[arg(long, default_value_t = 0)]
+    pub offset_pool: usize,
+
+    /// How random offsets spread over the device: uniform, zipf or exponential (skewed ones favour the start of the range)
+    #[arg(long, value_enum, default_value = "uniform")]
+    pub distribution: OffsetDistribution,
+
+    /// Skew of --distribution: the zipf exponent (default 1.2) or the exponential decay rate across the range (default 10)
+    #[arg(long, value_parser = parse_skew)]
+    pub skew: Option<f64>,
+
+    /// Tests to run: all, read-tp, write-tp, read-iops, write-iops, mixed (comma-separated)
+    #[arg(long, default_value = "all")]
+    pub tests: String,
+
+    /// Threads, queue depth and block size for one test, e.g. read-tp:threads=30,qd=1,bs=128 (repeatable; overrides that test's --*-threads, --*-qd and --*-bs)
+    #[arg(long = "test", value_name = "TEST:KEY=VALUE,...", value_parser = parse_test_spec)]
+    pub test: Vec<(&'static str, TestParams)>,
+
+    /// Only test from this byte offset of each device onward (K/M/G/T suffixes allowed; 4K aligned)
+    #[arg(long, default_value = "0", value_parser = parse_size)]
+    pub range_start: u64,
+
+    /// Only test this many bytes from --range-start (K/M/G/T suffixes allowed; default: to the end)
+    #[arg(long, value_parser = parse_size)]
+    pub range_len: Option<u64>,
+
+    /// Assume every device is this many bytes instead of asking the OS (K/M/G/T suffixes allowed), for storage stacks whose size query fails
+    #[arg(long, value_parser = parse_device_size)]
+    pub device_size: Option<u64>,
+}
+
+/// The test names `--tests` and `--test` accept
+const TEST_NAMES: [&str; 5] = ["read-tp", "write-tp", "read-iops", "write-iops", "mixed"];
+
+/// Shape of one test given with `--test`; keys left out keep the per-test flag
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestParams {
+    pub threads: Option<u32>,
+    pub qd: Option<u32>,
+    pub bs: Option<u32>,
+}
+
+/// Every setting at its command-line default, with no devices
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        let matches = BenchmarkConfig::augment_args(clap::Command::new("4c")).get_matches_from(["4c"]);
+        BenchmarkConfig::from_arg_matches(&matches).expect("the defaults parse")
+    }
+}
+
+impl BenchmarkConfig {
+    /// The `--test` parameters by test name. Several `--test` for the same
+    /// test combine, a later key overriding an earlier one.
+    pub fn test_params(&self) -> BTreeMap<&'static str, TestParams> {
+        let mut map: BTreeMap<&'static str, TestParams> = BTreeMap::new();
+        for &(name, params) in &self.test {
+            let entry = map.entry(name).or_default();
+            entry.threads = params.threads.or(entry.threads);
+            entry.qd = params.qd.or(entry.qd);
+            entry.bs = params.bs.or(entry.bs);
+        }
+        map
+    }
+
+    /// Fold the `--test` parameters into the per-test flags they stand for,
+    /// so everything downstream (and --dump-config) sees one set of values
+    pub fn apply_test_params(&mut self) {
+        for (name, params) in self.test_params() {
+            let (threads, qd, bs) = match name {
+                "read-tp" => (&mut self.read_tp_threads, &mut self.read_tp_qd, &mut self.read_tp_bs),
+                "write-tp" => (&mut self.write_tp_threads, &mut self.write_tp_qd, &mut self.write_tp_bs),
+                "read-iops" => (&mut self.read_iops_threads, &mut self.read_iops_qd, &mut self.read_iops_bs),
+                "write-iops" => (&mut self.write_iops_threads, &mut self.write_iops_qd, &mut self.write_iops_bs),
+                _ => (&mut self.mixed_threads, &mut self.mixed_qd, &mut self.mixed_bs),
+            };
+            *threads = params.threads.unwrap_or(*threads);
+            *qd = params.qd.unwrap_or(*qd);
+            *bs = params.bs.unwrap_or(*bs);
+        }
+    }
+}
+
+/// Parse a `--test` spec: a test name, a colon, then comma-separated
+/// `threads=`, `qd=` and `bs=` (KB) settings, e.g. "read-tp:threads=30,qd=1,bs=128"
+fn parse_test_spec(s: &str) -> Result<(&'static str, TestParams), String> {
+    let (name, settings) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected TEST:KEY=VALUE,... (e.g. read-tp:threads=30,qd=1), got '{}'", s))?;
+    let name = TEST_NAMES
+        .iter()
+        .find(|&&t| t == name.trim())
+        .ok_or_else(|| format!("unknown test '{}' (use {})", name.trim(), TEST_NAMES.join(", ")))?;
+
+    let mut params = TestParams::default();
+    for setting in settings.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (key, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("expected KEY=VALUE in '{}'", setting))?;
+        let slot = match key.trim() {
+            "threads" => &mut params.threads,
+            "qd" => &mut params.qd,
+            "bs" => &mut params.bs,
+            other => return Err(format!("unknown key '{}' (use threads, qd or bs)", other)),
+        };
+        match value.trim().parse::<u32>() {
+            Ok(v) if v > 0 => *slot = Some(v),
+            _ => return Err(format!("{} must be a whole number of at least 1, got '{}'", key.trim(), value.trim())),
+        }
+    }
+    if params == TestParams::default() {
+        return Err(format!("no settings given for {} (use threads, qd or bs)", name));
+    }
+    Ok((name, params))
+}
+
+/// Parse a byte count with an optional binary K/M/G/T suffix (e.g. "512M")
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let shift = match c.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(format!("unknown size suffix '{}' (use K, M, G or T)", c)),
+            };
+            (&s[..i], shift)
+        }
+        _ => (s, 0),
+    };
+    let n: u64 = digits.parse().map_err(|_| format!("invalid size '{}'", s))?;
+    n.checked_mul(1 << shift).ok_or_else(|| format!("size '{}' is too large", s))
+}
+
+/// A positive, finite --skew
+fn parse_skew(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        _ => Err(format!("skew must be a positive number, got '{}'", s)),
+    }
+}
+
+/// `parse_size` for --device-size, where 0 would leave nothing to test
+fn parse_device_size(s: &str) -> Result<u64, String> {
+    match parse_size(s)? {
+        0 => Err("device size must be greater than 0".to_string()),
+        n => Ok(n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config parsed from these command-line flags
+    fn parse(flags: &[&str]) -> BenchmarkConfig {
+        let command = BenchmarkConfig::augment_args(clap::Command::new("4c"));
+        let matches = command.try_get_matches_from(std::iter::once("4c").chain(flags.iter().copied())).unwrap();
+        BenchmarkConfig::from_arg_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn test_spec_parses_every_key() {
+        let (name, params) = parse_test_spec("read-tp:threads=30, qd=1 ,bs=128").unwrap();
+        assert_eq!(name, "read-tp");
+        assert_eq!(params, TestParams { threads: Some(30), qd: Some(1), bs: Some(128) });
+    }
+
+    #[test]
+    fn test_spec_rejects_an_unknown_test() {
+        let err = parse_test_spec("read-lat:qd=1").unwrap_err();
+        assert!(err.starts_with("unknown test 'read-lat'"), "{}", err);
+    }
+
+    #[test]
+    fn test_spec_rejects_an_unknown_key() {
+        let err = parse_test_spec("read-tp:depth=4").unwrap_err();
+        assert!(err.starts_with("unknown key 'depth'"), "{}", err);
+    }
+
+    #[test]
+    fn test_spec_rejects_zero() {
+        let err = parse_test_spec("write-iops:qd=0").unwrap_err();
+        assert_eq!(err, "qd must be a whole number of at least 1, got '0'");
+    }
+
+    #[test]
+    fn test_spec_needs_a_setting() {
+        let err = parse_test_spec("mixed:").unwrap_err();
+        assert!(err.starts_with("no settings given for mixed"), "{}", err);
+        let err = parse_test_spec("mixed").unwrap_err();
+        assert!(err.starts_with("expected TEST:KEY=VALUE"), "{}", err);
+    }
+
+    #[test]
+    fn repeated_test_specs_merge_keys() {
+        let config = parse(&["--test", "read-tp:threads=30,bs=256", "--test", "read-tp:qd=1,threads=8"]);
+        let params = config.test_params();
+        assert_eq!(params.len(), 1);
+        assert_eq!(params["read-tp"], TestParams { threads: Some(8), qd: Some(1), bs: Some(256) });
+    }
+
+    #[test]
+    fn test_params_override_only_the_keys_given() {
+        let mut config = parse(&["--read-iops-bs", "8", "--test", "read-iops:qd=4", "--test", "mixed:threads=2"]);
+        let (threads, mixed_qd) = (config.read_iops_threads, config.mixed_qd);
+        config.apply_test_params();
+        assert_eq!((config.read_iops_threads, config.read_iops_qd, config.read_iops_bs), (threads, 4, 8));
+        assert_eq!((config.mixed_threads, config.mixed_qd), (2, mixed_qd));
+    }
+}
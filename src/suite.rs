@@ -0,0 +1,723 @@
+use crate::engine::{self, AccessPattern, BlockSizeMix, OpenFlags, ResultStream, StopReason, TestConfig};
+use crate::error::BenchError;
+use crate::settings::BenchmarkConfig;
+use crate::report::{BenchmarkReport, NumberFormat, Sweep, SweepOver, TestResult};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Read device entries from a file: one per line, blank lines and `#` comments ignored
+fn read_devices_file(path: &Path) -> io::Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("cannot read devices file {}: {}", path.display(), e)))?;
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Split comma-separated device arguments and normalize Windows paths
+fn split_device_args(device_args: &[String]) -> Vec<String> {
+    let mut devices = Vec::new();
+
+    for arg in device_args {
+        // Handle comma-separated values
+        for part in arg.split(',') {
+            let trimmed = part.trim();
+            if !trimmed.is_empty() {
+                #[cfg(windows)]
+                let normalized = engine::normalize_device_path(trimmed);
+                #[cfg(not(windows))]
+                let normalized = trimmed.to_string();
+
+                devices.push(normalized);
+            }
+        }
+    }
+
+    devices
+}
+
+/// The devices under test: every `--device` entry, then those in `--devices-file`
+pub fn resolve_devices(args: &BenchmarkConfig) -> io::Result<Vec<String>> {
+    let mut all_args = args.device.clone();
+    if let Some(path) = &args.devices_file {
+        all_args.extend(read_devices_file(path)?);
+    }
+
+    let devices = split_device_args(&all_args);
+    if devices.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no valid devices specified"));
+    }
+
+    Ok(devices)
+}
+
+pub fn open_flags(args: &BenchmarkConfig) -> OpenFlags {
+    OpenFlags {
+        buffered_fallback: args.buffered_fallback,
+        buffered: args.buffered,
+        write_through: args.write_through,
+    }
+}
+
+pub fn number_format(args: &BenchmarkConfig) -> NumberFormat {
+    NumberFormat {
+        precision: args.precision.map(usize::from),
+        units: args.units,
+    }
+}
+
+/// Build a test config from the shared options plus the per-test shape, for a
+/// test run on its own: no result stream and no deadline
+pub fn test_config(
+    args: &BenchmarkConfig,
+    devices: &[String],
+    block_size_kb: u32,
+    threads: u32,
+    queue_depth: u32,
+    is_write: bool,
+) -> TestConfig {
+    TestConfig {
+        device_paths: devices.to_vec(),
+        io_size: args.bs_mix.as_ref().map_or(block_size_kb, BlockSizeMix::largest_kb) as u64 * 1024,
+        threads,
+        queue_depth,
+        qd_semantics: args.qd_semantics,
+        duration_secs: args.duration,
+        size_limit: args.size_limit,
+        ops_limit: args.ops_limit,
+        target_iops: args.target_iops,
+        warmup_secs: args.warmup,
+        deadline: None,
+        is_write,
+        offset_shift: 0,
+        range_start: args.range_start,
+        range_len: args.range_len,
+        device_size: args.device_size,
+        fill_percent: args.fill_percent,
+        open_flags: open_flags(args),
+        numa_node: args.affinity_node,
+        cpu_affinity: args.cpu_affinity || args.numa,
+        numa_local: args.numa,
+        control_socket: args.control_socket.clone(),
+        timeseries: args.timeseries.clone(),
+        latency_trace: args.latency_trace.clone(),
+        flush_caches: args.flush_caches,
+        drain_flush: args.drain_flush,
+        fsync_every: args.fsync_every,
+        verify: false,
+        stream: None,
+        latency_sample_rate: args.latency_sample_rate,
+        histogram_bits: args.histogram_bits,
+        per_thread_stats: args.per_thread_stats,
+        progress_json: args.progress_json,
+        number_format: number_format(args),
+        rw_mix: None,
+        bs_mix: args.bs_mix.clone(),
+        seed: args.seed,
+        shared_write_buffer: args.shared_write_buffer,
+        write_pattern: args.write_pattern,
+        io_poll: args.io_poll,
+        sq_poll: args.sq_poll,
+        fixed_buffers: args.fixed_buffers,
+        offset_pool: args.offset_pool,
+        offset_distribution: args.distribution,
+        skew: args.skew,
+        access_pattern: AccessPattern::Random,
+    }
+}
+
+/// One `run_benchmark` call: what its tests share beyond the settings, the
+/// tests it attempted and the errors of those that failed
+struct Run {
+    stream: Option<ResultStream>,
+    /// `--max-runtime`: when every test must be done by
+    deadline: Option<Instant>,
+    /// The deadline cut a test short or kept one from starting
+    deadline_hit: bool,
+    attempted: usize,
+    failures: Vec<BenchError>,
+}
+
+impl Run {
+    fn new(args: &BenchmarkConfig, deadline: Option<Instant>) -> Self {
+        Self {
+            stream: args.stream_tcp.as_deref().map(ResultStream::connect),
+            deadline,
+            deadline_hit: false,
+            attempted: 0,
+            failures: Vec::new(),
+        }
+    }
+
+    /// `test_config` for a test of this run, streaming to it and bound by its deadline
+    fn test_config(
+        &self,
+        args: &BenchmarkConfig,
+        devices: &[String],
+        block_size_kb: u32,
+        threads: u32,
+        queue_depth: u32,
+        is_write: bool,
+    ) -> TestConfig {
+        TestConfig {
+            stream: self.stream.as_ref().and_then(|s| s.sender()),
+            deadline: self.deadline,
+            ..test_config(args, devices, block_size_kb, threads, queue_depth, is_write)
+        }
+    }
+
+    /// Whether to start nothing more: Ctrl-C, or `--max-runtime` ran out
+    fn stop_requested(&mut self) -> bool {
+        if engine::interrupted() {
+            return true;
+        }
+        if self.deadline.is_some_and(|at| Instant::now() >= at) {
+            self.deadline_hit = true;
+        }
+        self.deadline_hit
+    }
+
+    /// Count one test, printing its error if it failed
+    fn record(&mut self, label: &str, result: Result<TestResult, BenchError>) -> Option<TestResult> {
+        self.attempted += 1;
+        match result {
+            Ok(r) => {
+                self.deadline_hit |= r.stop_reason == StopReason::MaxRuntime;
+                Some(r)
+            }
+            Err(e) => {
+                warn!("{} error: {}", label, e);
+                self.failures.push(e);
+                None
+            }
+        }
+    }
+
+    /// The run's error if every attempted test failed
+    fn all_failed(&mut self) -> Option<BenchError> {
+        if self.attempted == 0 || self.failures.len() < self.attempted {
+            return None;
+        }
+        Some(BenchError::AllTestsFailed {
+            attempted: self.attempted,
+            first: Box::new(self.failures.swap_remove(0)),
+        })
+    }
+}
+
+/// Run a test `--repeat` times and average the runs into one result. A
+/// verifying write test is read back once, after its last run.
+fn run_repeated(args: &BenchmarkConfig, label: &str, config: &TestConfig, run: &mut Run) -> Option<TestResult> {
+    if args.repeat <= 1 {
+        return run.record(label, engine::run_test(config));
+    }
+    let mut runs = Vec::new();
+    for n in 1..=args.repeat {
+        if run.stop_requested() {
+            break;
+        }
+        info!("  Run {} of {}", n, args.repeat);
+        let run_label = format!("{} (run {} of {})", label, n, args.repeat);
+        if let Some(result) = run.record(&run_label, engine::run_test(config)) {
+            runs.push(result);
+        }
+    }
+    TestResult::combine_runs(runs)
+}
+
+/// Run every selected test once per step of `--bs-sweep` (block sizes) or
+/// `--qd-sweep` (queue depths), keeping the rest of each test's own shape
+fn run_sweep(
+    args: &BenchmarkConfig,
+    devices: &[String],
+    tests: &[(bool, &str, bool)],
+    run: &mut Run,
+    over: SweepOver,
+) -> Vec<Sweep> {
+    let steps = match over {
+        SweepOver::BlockSize => &args.bs_sweep,
+        SweepOver::QueueDepth => &args.qd_sweep,
+    };
+    let mut sweeps = Vec::new();
+    for &(_, test, _) in tests.iter().filter(|t| t.0) {
+        let (key, label, threads, qd, bs, is_write) = match test {
+            "read-tp" => ("read_throughput", "Read Throughput", args.read_tp_threads, args.read_tp_qd, args.read_tp_bs, false),
+            "write-tp" => (
+                "write_throughput",
+                "Write Throughput",
+                args.write_tp_threads,
+                args.write_tp_qd,
+                args.write_tp_bs,
+                true,
+            ),
+            "read-iops" => ("read_iops", "Read IOPS", args.read_iops_threads, args.read_iops_qd, args.read_iops_bs, false),
+            "write-iops" => ("write_iops", "Write IOPS", args.write_iops_threads, args.write_iops_qd, args.write_iops_bs, true),
+            _ => ("mixed", "Mixed Read/Write", args.mixed_threads, args.mixed_qd, args.mixed_bs, false),
+        };
+
+        let mut results = Vec::new();
+        for &step in steps {
+            if run.stop_requested() {
+                break;
+            }
+            let (bs, qd, step_label) = match over {
+                SweepOver::BlockSize => (step, qd, format!("{} KB", step)),
+                SweepOver::QueueDepth => (bs, step, format!("QD {}", step)),
+            };
+            info!("Running {} Test ({})...", label, step_label);
+            let mut config = run.test_config(args, devices, bs, threads, qd, is_write);
+            match test {
+                "read-tp" | "write-tp" => config.access_pattern = args.tp_pattern,
+                "mixed" => config.rw_mix = Some(args.rw_mix.unwrap_or(70)),
+                _ => {}
+            }
+            config.verify = args.verify && is_write;
+            if let Some(mut result) = run.record(&format!("{} {}", label, step_label), engine::run_test(&config)) {
+                if config.verify {
+                    run_verify_pass(&config, &mut result, run);
+                }
+                results.push(result);
+            }
+            info!();
+        }
+
+        if !results.is_empty() {
+            sweeps.push(Sweep {
+                test: key,
+                label,
+                over,
+                results,
+            });
+        }
+    }
+    sweeps
+}
+
+/// Read back what a verifying write test wrote. With the same seed, shape and
+/// access pattern the read workers walk the offsets the writers stamped.
+fn run_verify_pass(write_config: &TestConfig, result: &mut TestResult, run: &mut Run) {
+    let mut config = write_config.clone();
+    config.is_write = false;
+    config.warmup_secs = 0;
+    if run.stop_requested() {
+        let why = if engine::interrupted() { "interrupted" } else { "max runtime reached" };
+        info!("Verify pass skipped ({})", why);
+        return;
+    }
+
+    info!("Verifying written data...");
+    match engine::run_test(&config) {
+        Ok(r) => {
+            run.deadline_hit |= r.stop_reason == StopReason::MaxRuntime;
+            result.verify = r.verify;
+        }
+        Err(e) => warn!("Verify pass error: {}", e),
+    }
+}
+
+/// The tests `--tests` and `--rw-mix` select, as (selected, name, writes)
+pub fn selected_tests(args: &BenchmarkConfig) -> [(bool, &'static str, bool); 5] {
+    let run_all = args.tests == "all";
+    [
+        (run_all || args.tests.contains("read-tp"), "read-tp", false),
+        (run_all || args.tests.contains("write-tp"), "write-tp", true),
+        (run_all || args.tests.contains("read-iops"), "read-iops", false),
+        (run_all || args.tests.contains("write-iops"), "write-iops", true),
+        (args.rw_mix.is_some() || args.tests.contains("mixed"), "mixed", args.rw_mix != Some(100)),
+    ]
+}
+
+/// Select the devices to prep: all of them with --prep or --fill-percent,
+/// otherwise those named in --prep-devices
+pub fn prep_devices(args: &BenchmarkConfig, devices: &[String]) -> io::Result<Vec<String>> {
+    if args.prep || args.fill_percent.is_some() {
+        return Ok(devices.to_vec());
+    }
+
+    let selected = split_device_args(&args.prep_devices);
+    for device in &selected {
+        if !devices.contains(device) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("--prep-devices entry {} is not one of the devices under test", device),
+            ));
+        }
+    }
+    Ok(selected)
+}
+
+/// Say what failed on `device` without losing the kind of failure
+fn device_error(device: &str, action: &str, e: io::Error) -> BenchError {
+    match BenchError::device(device, e) {
+        BenchError::Io(e) => BenchError::Io(io::Error::new(e.kind(), format!("{} {}: {}", action, device, e))),
+        e => e,
+    }
+}
+
+/// Get the devices ready for the tests: `--create-file`, then `--trim`, then
+/// `--prep` or `--fill-percent`, each stopping early once `deadline` has
+/// passed. Returns how long each device took to trim.
+pub fn prepare_devices(
+    args: &BenchmarkConfig,
+    devices: &[String],
+    deadline: Option<Instant>,
+) -> Result<Vec<f64>, BenchError> {
+    let nf = number_format(args);
+    let open_flags = open_flags(args);
+    let write_pattern = args.write_pattern;
+
+    // Create file device if requested (only for first device)
+    if args.create_file {
+        let mut rng = engine::prep_rng(args.seed, 0);
+        engine::create_file_device(&devices[0], args.file_size, args.write_pattern, &mut rng, args.file_alloc, deadline)
+            .map_err(|e| device_error(&devices[0], "creating file device", e))?;
+        info!("File device created successfully");
+        info!();
+    }
+
+    // Discard before any prep, so the prep writes land on erased blocks
+    let mut trim_secs = Vec::new();
+    if args.trim {
+        for device in devices {
+            let secs = engine::trim_device(device, args.range_start, args.range_len, args.device_size, deadline, nf)
+                .map_err(|e| device_error(device, "trimming device", e))?;
+            trim_secs.push(secs);
+        }
+        info!();
+    }
+
+    // Prep device if requested (all devices in parallel); --fill-percent
+    // writes only the start of each test window instead
+    let prep_devices = prep_devices(args, devices)?;
+    if !prep_devices.is_empty() {
+        info!(
+            "{} {} device{}...",
+            if args.fill_percent.is_some() { "Filling" } else { "Preparing" },
+            prep_devices.len(),
+            if prep_devices.len() == 1 { "" } else { "s" }
+        );
+
+        let (range_start, range_len, device_size) = (args.range_start, args.range_len, args.device_size);
+        let (fill_percent, seed) = (args.fill_percent, args.seed);
+        let mut handles = Vec::new();
+        for device in prep_devices {
+            let index = devices.iter().position(|d| *d == device).unwrap_or_default();
+            let handle = std::thread::spawn(move || {
+                let mut rng = engine::prep_rng(seed, index);
+                let result = match fill_percent {
+                    Some(percent) => engine::fill_region(&device, range_start, range_len, device_size, percent).and_then(
+                        |(offset, len)| {
+                            info!("Filling device: {} to {}% ({} {})", device, percent, nf.size(len as f64, 2), nf.size_unit());
+                            engine::write_sequential(&device, open_flags, offset, len, write_pattern, &mut rng, deadline, nf)
+                        },
+                    ),
+                    None => engine::prep_device(&device, device_size, open_flags, write_pattern, &mut rng, deadline, nf),
+                };
+                match result {
+                    Ok(()) => {
+                        info!("  ✓ {}", device);
+                        Ok(())
+                    }
+                    Err(e) => {
+                        let action = if fill_percent.is_some() { "filling device" } else { "preparing device" };
+                        Err(device_error(&device, action, e))
+                    }
+                }
+            });
+            handles.push(handle);
+        }
+
+        // Wait for all preps to complete; the first failure is the run's error
+        let mut first = None;
+        for handle in handles {
+            if let Err(e) = handle.join().unwrap() {
+                match first {
+                    None => first = Some(e),
+                    Some(_) => warn!("Error: {}", e),
+                }
+            }
+        }
+        if let Some(e) = first {
+            return Err(e);
+        }
+        info!("All devices prepared successfully");
+        info!();
+    }
+
+    Ok(trim_secs)
+}
+
+/// Delete the file `--create-file` made, if `--cleanup-file` asked for it.
+/// Without the flag the file is kept for later runs.
+pub fn cleanup_file(args: &BenchmarkConfig, devices: &[String]) {
+    if !(args.create_file && args.cleanup_file) {
+        return;
+    }
+    let path = &devices[0];
+    match std::fs::remove_file(path) {
+        Ok(()) => info!("Removed file device {}", path),
+        Err(e) => warn!("Warning: could not remove file device {}: {}", path, e),
+    }
+}
+
+/// Prepare the devices, run every selected test and return the report. A test
+/// that fails is left out of the report and the rest still run; the run fails
+/// only when the devices can't be prepared or every test failed. Nothing is
+/// asked or saved: confirming writes to a raw device and writing the report
+/// out are up to the caller.
+///
+/// Each call keeps its own `--max-runtime` deadline, so calls may run side by
+/// side, but two things are shared by the whole process: Ctrl-C (once
+/// `engine::install_interrupt_handler` is installed it stops every run) and
+/// the console output switched on by `output::set_verbose`.
+pub fn run_benchmark(mut args: BenchmarkConfig) -> Result<BenchmarkReport, BenchError> {
+    args.apply_test_params();
+    // Recorded in the report so the run can be replayed; the verify read-back
+    // also needs it to walk the write pass's offsets again
+    args.seed.get_or_insert_with(rand::random);
+
+    let devices = resolve_devices(&args)?;
+    let device_display = if devices.len() == 1 {
+        devices[0].clone()
+    } else {
+        format!("{} devices", devices.len())
+    };
+    let selected = selected_tests(&args);
+    let [(run_read_tp, ..), (run_write_tp, ..), (run_read_iops, ..), (run_write_iops, ..), (run_mixed, ..)] = selected;
+
+    // The budget covers the whole run, device preparation included
+    let deadline = args.max_runtime.map(|secs| Instant::now() + Duration::from_secs(secs));
+    if let Some(secs) = args.max_runtime {
+        info!("Max runtime: {}s", secs);
+    }
+    let mut run = Run::new(&args, deadline);
+    let trim_secs = match prepare_devices(&args, &devices, deadline) {
+        Ok(secs) => secs,
+        // Out of time before the devices were ready: no test starts, and the
+        // report says why
+        Err(e) if run.stop_requested() && run.deadline_hit => {
+            warn!("Error: {}", e);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut report = BenchmarkReport::new(&device_display);
+    report.number_format = number_format(&args);
+    report.notes = args.notes.clone();
+    report.seed = args.seed;
+    report.fill_percent = args.fill_percent;
+    report.device_details = devices.iter().map(|d| engine::device_details(d)).collect();
+    for (d, secs) in report.device_details.iter_mut().zip(&trim_secs) {
+        d.trim_secs = Some(*secs);
+    }
+
+    info!("Starting benchmark tests...");
+    info!();
+
+    // A block-size or queue-depth sweep replaces the single run of each selected test
+    let sweep = !args.bs_sweep.is_empty() || !args.qd_sweep.is_empty();
+    if !args.bs_sweep.is_empty() {
+        report.sweeps = run_sweep(&args, &devices, &selected, &mut run, SweepOver::BlockSize);
+    }
+    if !args.qd_sweep.is_empty() {
+        report.qd_sweeps = run_sweep(&args, &devices, &selected, &mut run, SweepOver::QueueDepth);
+    }
+
+    // Read Throughput
+    if run_read_tp && !sweep && !run.stop_requested() {
+        info!("Running Read Throughput Test...");
+        let mut config = run.test_config(
+            &args,
+            &devices,
+            args.read_tp_bs,
+            args.read_tp_threads,
+            args.read_tp_qd,
+            false,
+        );
+        config.access_pattern = args.tp_pattern;
+        report.read_throughput = run_repeated(&args, "Read throughput", &config, &mut run);
+        info!();
+    }
+
+    // Write Throughput
+    if run_write_tp && !sweep && !run.stop_requested() {
+        info!("Running Write Throughput Test...");
+        let mut config = run.test_config(
+            &args,
+            &devices,
+            args.write_tp_bs,
+            args.write_tp_threads,
+            args.write_tp_qd,
+            true,
+        );
+        config.access_pattern = args.tp_pattern;
+        config.verify = args.verify;
+        if let Some(mut result) = run_repeated(&args, "Write throughput", &config, &mut run) {
+            if args.verify {
+                run_verify_pass(&config, &mut result, &mut run);
+            }
+            report.write_throughput = Some(result);
+        }
+        info!();
+    }
+
+    // Read IOPS
+    if run_read_iops && !sweep && !run.stop_requested() {
+        info!("Running Read IOPS Test...");
+        let config = run.test_config(
+            &args,
+            &devices,
+            args.read_iops_bs,
+            args.read_iops_threads,
+            args.read_iops_qd,
+            false,
+        );
+        report.read_iops = run_repeated(&args, "Read IOPS", &config, &mut run);
+        info!();
+    }
+
+    // Write IOPS
+    if run_write_iops && !sweep && !run.stop_requested() {
+        info!("Running Write IOPS Test...");
+        let mut config = run.test_config(
+            &args,
+            &devices,
+            args.write_iops_bs,
+            args.write_iops_threads,
+            args.write_iops_qd,
+            true,
+        );
+        config.verify = args.verify;
+        if let Some(mut result) = run_repeated(&args, "Write IOPS", &config, &mut run) {
+            if args.verify {
+                run_verify_pass(&config, &mut result, &mut run);
+            }
+            report.write_iops = Some(result);
+        }
+        info!();
+    }
+
+    // Mixed Read/Write
+    if run_mixed && !sweep && !run.stop_requested() {
+        let read_pct = args.rw_mix.unwrap_or(70);
+        info!("Running Mixed Read/Write Test ({}% reads)...", read_pct);
+        let mut config = run.test_config(
+            &args,
+            &devices,
+            args.mixed_bs,
+            args.mixed_threads,
+            args.mixed_qd,
+            false,
+        );
+        config.rw_mix = Some(read_pct);
+        report.mixed = run_repeated(&args, "Mixed read/write", &config, &mut run);
+        info!();
+    }
+
+    if let Some(stream) = run.stream.take() {
+        stream.finish();
+    }
+    cleanup_file(&args, &devices);
+
+    // A run where nothing could be measured must not look like a success
+    if let Some(e) = run.all_failed() {
+        return Err(e);
+    }
+
+    report.interrupted = engine::interrupted();
+    report.max_runtime_reached = run.deadline_hit;
+    report.summarize();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A devices file with `contents` in the temp directory, removed on drop
+    struct DevicesFile(PathBuf);
+
+    impl DevicesFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("4c-test-{}-{}.txt", std::process::id(), name));
+            std::fs::write(&path, contents).unwrap();
+            DevicesFile(path)
+        }
+    }
+
+    impl Drop for DevicesFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Entries as `resolve_devices` reports them (normalized on Windows)
+    fn devices(entries: &[&str]) -> Vec<String> {
+        split_device_args(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>())
+    }
+
+    fn config(device: &[&str], devices_file: Option<&DevicesFile>) -> BenchmarkConfig {
+        BenchmarkConfig {
+            device: device.iter().map(|d| d.to_string()).collect(),
+            devices_file: devices_file.map(|f| f.0.clone()),
+            ..BenchmarkConfig::default()
+        }
+    }
+
+    #[test]
+    fn devices_file_skips_comments_and_blank_lines() {
+        let file = DevicesFile::new("comments", "# data disks\n/dev/sdb\n\n# spare\n/dev/sdc\n");
+        assert_eq!(read_devices_file(&file.0).unwrap(), ["/dev/sdb", "/dev/sdc"]);
+    }
+
+    #[test]
+    fn devices_file_skips_whitespace_only_lines() {
+        let file = DevicesFile::new("whitespace", "/dev/sdb\n   \n\t\n \t \n/dev/sdc\n");
+        assert_eq!(read_devices_file(&file.0).unwrap(), ["/dev/sdb", "/dev/sdc"]);
+    }
+
+    #[test]
+    fn devices_file_trims_entries() {
+        let file = DevicesFile::new("trim", "  /dev/sdb\t\n\t/dev/sdc  \r\n   # indented comment\n");
+        assert_eq!(read_devices_file(&file.0).unwrap(), ["/dev/sdb", "/dev/sdc"]);
+    }
+
+    #[test]
+    fn devices_file_lines_may_hold_comma_lists() {
+        let file = DevicesFile::new("commas", "/dev/sdb, /dev/sdc\n/dev/sdd,,\n");
+        let resolved = resolve_devices(&config(&[], Some(&file))).unwrap();
+        assert_eq!(resolved, devices(&["/dev/sdb", "/dev/sdc", "/dev/sdd"]));
+    }
+
+    #[test]
+    fn devices_file_entries_follow_device_flags() {
+        let file = DevicesFile::new("merge", "/dev/sdc\n/dev/sdd\n");
+        let resolved = resolve_devices(&config(&["/dev/sda", "/dev/sdb"], Some(&file))).unwrap();
+        assert_eq!(resolved, devices(&["/dev/sda", "/dev/sdb", "/dev/sdc", "/dev/sdd"]));
+    }
+
+    #[test]
+    fn missing_devices_file_is_a_clean_error() {
+        let path = std::env::temp_dir().join(format!("4c-test-{}-missing.txt", std::process::id()));
+        let args = BenchmarkConfig { devices_file: Some(path.clone()), ..BenchmarkConfig::default() };
+        let err = resolve_devices(&args).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let message = err.to_string();
+        assert!(message.starts_with(&format!("cannot read devices file {}: ", path.display())), "{}", message);
+    }
+
+    #[test]
+    fn devices_file_with_only_comments_is_no_devices() {
+        let file = DevicesFile::new("empty", "# nothing here\n\n");
+        let err = resolve_devices(&config(&[], Some(&file))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "no valid devices specified");
+    }
+}